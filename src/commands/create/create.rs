@@ -0,0 +1,125 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use chrono::{Duration, UTC};
+use chrono::naive::datetime::NaiveDateTime;
+use clap::ArgMatches;
+
+use models;
+use models::migration::{Migration, TIMESTAMP_VERSION_FORMAT};
+
+#[derive(Debug)]
+pub struct CreateCmd {
+    name: String,
+    migrations_dir: String,
+    timestamp: bool
+}
+
+impl CreateCmd {
+    pub fn new(name: &str) -> CreateCmd {
+        CreateCmd {
+            name: name.to_owned(),
+            migrations_dir: "migrations".to_owned(),
+            timestamp: false
+        }
+    }
+
+    /// Determines the version of the new migration
+    ///
+    /// - *returns*: `i64` - Current UTC time if `--timestamp` was given, otherwise one more than the
+    ///   newest version in the migrations directory, error message if fail
+    ///
+    /// Timestamp versions have second resolution, when the newest version is the same or newer the
+    /// new migration is versioned a second after it, so migrations created in a row never share one.
+    fn next_version(&self) -> Result<i64, String> {
+        let newest = self.newest_version()?;
+        if !self.timestamp {
+            return Ok(newest + 1);
+        }
+
+        let now = timestamp_version(UTC::now().naive_utc())?;
+        if newest < now {
+            return Ok(now);
+        }
+
+        match NaiveDateTime::parse_from_str(&newest.to_string(), TIMESTAMP_VERSION_FORMAT) {
+            Ok(newest) => timestamp_version(newest + Duration::seconds(1)),
+            Err(_) => Ok(newest + 1)
+        }
+    }
+
+    /// Newest version in the migrations directory, 0 if it has none
+    fn newest_version(&self) -> Result<i64, String> {
+        if !Path::new(&self.migrations_dir).exists() {
+            return Ok(0);
+        }
+
+        let mut newest = 0;
+        for path in Migration::dirs(&self.migrations_dir)? {
+            newest = newest.max(Migration::load_version(&path)?);
+        }
+
+        Ok(newest)
+    }
+}
+
+impl models::command::Command <CreateCmd> for CreateCmd {
+    fn from_matches(matches: &ArgMatches) -> Result<CreateCmd, String> {
+        let name = match matches.value_of("name") {
+            Some(name) => name,
+            None => return Err("Missing migration name".to_owned())
+        };
+
+        if name.is_empty() || name.contains('/') || name.contains('\\') || name == "." || name == ".." {
+            return Err(format!("Migration name \"{}\" must be a valid directory name", name))
+        }
+
+        let mut obj = CreateCmd::new(name);
+
+        if let Some(migrations_dir) = matches.value_of("migrations-dir") {
+            obj.migrations_dir = migrations_dir.to_owned();
+        }
+
+        obj.timestamp = matches.is_present("timestamp");
+
+        Ok(obj)
+    }
+
+    fn run(&self) -> Result<(), String> {
+        let version = self.next_version()?;
+
+        let path = Path::new(&self.migrations_dir).join(&self.name);
+        if path.exists() {
+            return Err(format!("Migration \"{}\" already exists", path.display()))
+        }
+
+        if let Err(err) = fs::create_dir_all(&path) {
+            return Err(format!("Failed to create migration directory \"{}\": {}", path.display(), err))
+        }
+
+        write_file(&path.join("version"), &format!("{}\n", version))?;
+        write_file(&path.join("up.sql"), "")?;
+        write_file(&path.join("down.sql"), "")?;
+
+        println!("Created migration \"{}\" (version {})", path.display(), version);
+
+        Ok(())
+    }
+}
+
+/// Formats a time as a timestamp version, see `TIMESTAMP_VERSION_FORMAT`
+fn timestamp_version(time: NaiveDateTime) -> Result<i64, String> {
+    match time.format(TIMESTAMP_VERSION_FORMAT).to_string().parse::<i64>() {
+        Ok(version) => Ok(version),
+        Err(err) => Err(format!("Failed to generate timestamp version: {}", err))
+    }
+}
+
+/// Writes a file, failing if it already exists
+fn write_file(path: &Path, contents: &str) -> Result<(), String> {
+    match fs::OpenOptions::new().write(true).create_new(true).open(path).and_then(|mut f| f.write_all(contents.as_bytes())) {
+        Ok(_) => Ok(()),
+        Err(err) => Err(format!("Failed to write \"{}\": {}", path.display(), err))
+    }
+}
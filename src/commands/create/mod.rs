@@ -0,0 +1,8 @@
+//! Create sub-command
+
+/// The run method for the Command trait
+#[allow(clippy::module_inception)]
+pub mod create;
+
+/// Command line options for the create sub-command
+pub mod sub_cmd;
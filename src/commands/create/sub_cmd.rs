@@ -0,0 +1,23 @@
+use clap::{Arg, App, SubCommand};
+
+pub fn sub_cmd<'a>() -> App<'a, 'a> {
+    SubCommand::with_name("create")
+        .about("Create scaffold for migration")
+        .arg(Arg::with_name("name")
+            .help("Name of database migration")
+            .index(1)
+            .required(true)
+        )
+        .arg(Arg::with_name("migrations-dir")
+            .help("The directory to put the new migration in.")
+            .short("m")
+            .long("migrations-dir")
+            .takes_value(true)
+            .default_value("migrations")
+        )
+        .arg(Arg::with_name("timestamp")
+            .help("Use the current UTC time (ex., 20261018120000) as the version instead of the next sequential version, \
+                   so migrations created on different branches do not collide")
+            .long("timestamp")
+        )
+}
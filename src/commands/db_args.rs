@@ -0,0 +1,53 @@
+//! Command line options shared by sub-commands which connect to a database
+
+use clap::{Arg, App};
+
+/// Adds the options `models::config::DbConfig` loads connection information from
+pub fn db_args<'a>(cmd: App<'a, 'a>) -> App<'a, 'a> {
+    cmd.arg(Arg::with_name("environment")
+            .help("Environment which application is running in")
+            .short("e")
+            .takes_value(true)
+            .requires("config")
+        )
+        .arg(Arg::with_name("config")
+            .help("Path to database access configuration file")
+            .short("c")
+            .takes_value(true)
+        )
+        .arg(Arg::with_name("driver")
+            .help("Database driver, postgres (default), sqlite or mysql")
+            .long("driver")
+            .takes_value(true)
+        )
+        .arg(Arg::with_name("url")
+            .help("Database URL, the driver is selected by its scheme (postgres://, postgresql://, sqlite://, mysql://, mariadb://)")
+            .long("url")
+            .takes_value(true)
+        )
+        .arg(Arg::with_name("host")
+            .help("Database server host")
+            .short("h")
+            .takes_value(true)
+        )
+        .arg(Arg::with_name("user")
+            .help("Database server user")
+            .short("u")
+            .takes_value(true)
+        )
+        .arg(Arg::with_name("password")
+            .help("Database server password")
+            .short("p")
+            .takes_value(true)
+        )
+        .arg(Arg::with_name("database")
+            .help("Database to run migrations on, the database file for SQLite")
+            .short("d")
+            .takes_value(true)
+        )
+        .arg(Arg::with_name("yes-i-am-sure")
+            .help("Confirm changes which throw data away in a protected environment, instead of being asked to type \
+                   its name")
+            .long("yes-i-am-sure")
+        )
+}
@@ -0,0 +1,96 @@
+use std::fs;
+use std::path::Path;
+
+use clap::ArgMatches;
+
+use drivers::Dialect;
+use models;
+use models::config::DbConfig;
+
+#[derive(Debug)]
+pub struct DropCmd {
+    db: DbConfig,
+    maintenance_db: Option<String>,
+    recreate: bool
+}
+
+impl DropCmd {
+    pub fn new(db: DbConfig) -> DropCmd {
+        DropCmd {
+            db,
+            maintenance_db: None,
+            recreate: false
+        }
+    }
+
+    /// Creates the database again after dropping it, for the recreate sub-command
+    pub fn recreate(mut self) -> DropCmd {
+        self.recreate = true;
+        self
+    }
+
+    /// Drops and creates a SQLite database, which is a file
+    fn run_sqlite(&self, path: &str) -> Result<(), String> {
+        if Path::new(path).exists() {
+            if let Err(err) = fs::remove_file(path) {
+                return Err(format!("Failed to remove database file \"{}\": {}", path, err))
+            }
+            info!("Dropped database {}", path);
+        }
+
+        if self.recreate {
+            // Opening a database file creates it
+            self.db.connect()?;
+            info!("Created database {}", path);
+        }
+
+        Ok(())
+    }
+}
+
+impl models::command::Command <DropCmd> for DropCmd {
+    fn from_matches(matches: &ArgMatches) -> Result<DropCmd, String> {
+        let mut obj = DropCmd::new(DbConfig::from_matches(matches)?);
+
+        if let Some(maintenance_db) = matches.value_of("maintenance-database") {
+            obj.maintenance_db = Some(maintenance_db.to_owned());
+        }
+
+        Ok(obj)
+    }
+
+    fn run(&self) -> Result<(), String> {
+        let action = if self.recreate { "recreate the database" } else { "drop the database" };
+        self.db.refuse_protected(action)?;
+
+        let database = self.db.database_name();
+        if database.is_empty() {
+            return Err("No database to drop, add one to the URL or use the --database/-d option".to_owned())
+        }
+
+        let maintenance_db = match (self.db.driver, self.maintenance_db.as_ref()) {
+            (Dialect::Sqlite, _) => return self.run_sqlite(&database),
+            (_, Some(maintenance_db)) => maintenance_db.as_str(),
+            (Dialect::Postgres, None) => "postgres",
+            (Dialect::Mysql, None) => "mysql",
+        };
+
+        if maintenance_db == database {
+            return Err(format!("Can not drop the maintenance database \"{}\", select another one with \
+                                --maintenance-database", database))
+        }
+
+        let conn = self.db.maintenance(maintenance_db)?.connect()?;
+        let ident = self.db.driver.quote_ident(&database);
+
+        conn.batch_execute(&format!("DROP DATABASE IF EXISTS {}", ident))?;
+        info!("Dropped database {}", database);
+
+        if self.recreate {
+            conn.batch_execute(&format!("CREATE DATABASE {}", ident))?;
+            info!("Created database {}", database);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,8 @@
+//! Drop and recreate sub-commands
+
+/// The run method for the Command trait
+#[allow(clippy::module_inception)]
+pub mod drop;
+
+/// Command line options for the drop and recreate sub-commands
+pub mod sub_cmd;
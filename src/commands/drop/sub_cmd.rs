@@ -0,0 +1,28 @@
+use clap::{Arg, App, SubCommand};
+
+use commands::db_args::db_args;
+
+/// Options for the drop sub-command
+pub fn sub_cmd<'a>() -> App<'a, 'a> {
+    maintenance_args(SubCommand::with_name("drop")
+        .about("Drop the database")
+    )
+}
+
+/// Options for the recreate sub-command
+pub fn recreate_sub_cmd<'a>() -> App<'a, 'a> {
+    maintenance_args(SubCommand::with_name("recreate")
+        .about("Drop the database then create it again, empty")
+    )
+}
+
+fn maintenance_args<'a>(cmd: App<'a, 'a>) -> App<'a, 'a> {
+    db_args(cmd
+        .arg(Arg::with_name("maintenance-database")
+            .help("Database to connect to while dropping and creating the database, postgres for Postgres (default) \
+                   and mysql for MySQL")
+            .long("maintenance-database")
+            .takes_value(true)
+        )
+    )
+}
@@ -0,0 +1,60 @@
+use clap::ArgMatches;
+
+use models;
+use models::config::DbConfig;
+use models::dbschema::DbSchemaVer;
+use models::schema_dump;
+
+#[derive(Debug)]
+pub struct DumpSchemaCmd {
+    db: DbConfig,
+    file: String,
+    check: bool
+}
+
+impl DumpSchemaCmd {
+    pub fn new(db: DbConfig) -> DumpSchemaCmd {
+        DumpSchemaCmd {
+            db,
+            file: schema_dump::DEFAULT_FILE.to_owned(),
+            check: false
+        }
+    }
+}
+
+impl models::command::Command <DumpSchemaCmd> for DumpSchemaCmd {
+    fn from_matches(matches: &ArgMatches) -> Result<DumpSchemaCmd, String> {
+        let mut obj = DumpSchemaCmd::new(DbConfig::from_matches(matches)?);
+
+        if let Some(file) = matches.value_of("file") {
+            obj.file = file.to_owned();
+        }
+
+        obj.check = matches.is_present("check");
+
+        Ok(obj)
+    }
+
+    /// Writes the schema of the database to the schema file, or with `--check` fails if the file
+    /// differs from it, ex. when a migration was added without dumping the schema again
+    fn run(&self) -> Result<(), String> {
+        let db_conn = self.db.connect()?;
+        let schema_ver = DbSchemaVer::bootstrap(db_conn.as_ref())?;
+
+        let current = match schema_ver.current_version()? {
+            Some(entry) => entry.version,
+            None => 0
+        };
+        let content = schema_dump::dump(db_conn.as_ref(), current)?;
+
+        if self.check {
+            schema_dump::check(&self.file, &content)?;
+            info!("Schema file {} is up to date with {}", self.file, db_conn.database_name());
+        } else {
+            schema_dump::write(&self.file, &content)?;
+            info!("Wrote the schema of {} at version {} to {}", db_conn.database_name(), current, self.file);
+        }
+
+        Ok(())
+    }
+}
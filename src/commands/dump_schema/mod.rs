@@ -0,0 +1,8 @@
+//! Dump-schema sub-command
+
+/// The run method for the Command trait
+#[allow(clippy::module_inception)]
+pub mod dump_schema;
+
+/// Command line options for the dump-schema sub-command
+pub mod sub_cmd;
@@ -0,0 +1,20 @@
+use clap::{Arg, App, SubCommand};
+
+use commands::db_args::db_args;
+
+pub fn sub_cmd<'a>() -> App<'a, 'a> {
+    db_args(SubCommand::with_name("dump-schema")
+        .about("Write the types, sequences, tables, views, indexes, triggers and functions of the database to a schema file")
+        .arg(Arg::with_name("file")
+            .help("The schema file to write, commit it to review schema changes.")
+            .short("f")
+            .long("file")
+            .takes_value(true)
+            .default_value("schema.sql")
+        )
+        .arg(Arg::with_name("check")
+            .help("Write nothing and fail if the schema file differs from the database")
+            .long("check")
+        )
+    )
+}
@@ -0,0 +1,104 @@
+use clap::ArgMatches;
+use serde_json::Value;
+
+use commands::output::OutputFormat;
+use commands::table;
+use models;
+use models::config::DbConfig;
+use models::dbschema::DbSchemaVer;
+use models::history::{History, HistoryEntry};
+
+#[derive(Debug)]
+pub struct HistoryCmd {
+    db: DbConfig,
+    version: Option<i64>,
+    limit: i64,
+    output: OutputFormat
+}
+
+impl HistoryCmd {
+    pub fn new(db: DbConfig) -> HistoryCmd {
+        HistoryCmd {
+            db,
+            version: None,
+            limit: 50,
+            output: OutputFormat::Human
+        }
+    }
+}
+
+impl models::command::Command <HistoryCmd> for HistoryCmd {
+    fn from_matches(matches: &ArgMatches) -> Result<HistoryCmd, String> {
+        let mut obj = HistoryCmd::new(DbConfig::from_matches(matches)?);
+
+        if let Some(version) = matches.value_of("schema-version") {
+            obj.version = match version.parse::<i64>() {
+                Ok(version) => Some(version),
+                Err(_) => return Err(format!("Schema version must be an integer, found \"{}\"", version))
+            };
+        }
+
+        if let Some(limit) = matches.value_of("limit") {
+            obj.limit = match limit.parse::<i64>() {
+                Ok(limit) if limit > 0 => limit,
+                _ => return Err(format!("Limit must be an integer greater than 0, found \"{}\"", limit))
+            };
+        }
+
+        obj.output = OutputFormat::from_matches(matches);
+
+        Ok(obj)
+    }
+
+    fn run(&self) -> Result<(), String> {
+        let db_conn = self.db.connect()?;
+        DbSchemaVer::bootstrap(db_conn.as_ref())?;
+
+        let entries = History::new(db_conn.as_ref()).recent(self.version, self.limit)?;
+        match self.output {
+            OutputFormat::Human => print_table(&entries),
+            OutputFormat::Json => println!("{}", Value::Array(entries.iter().map(to_json).collect())),
+        }
+
+        Ok(())
+    }
+}
+
+/// Serializes a history entry for `--output json`
+fn to_json(entry: &HistoryEntry) -> Value {
+    json!({
+        "recorded": entry.recorded.to_rfc3339(),
+        "version": entry.version,
+        "name": entry.name,
+        "action": entry.action,
+        "migration_hash": entry.migration_hash,
+        "run_by": entry.run_by,
+        "db_user": entry.db_user,
+        "host": entry.host,
+        "duration_ms": entry.duration_ms,
+        "tool_version": entry.tool_version,
+        "error": entry.error,
+    })
+}
+
+/// Prints history entries as a table on stdout
+fn print_table(entries: &[HistoryEntry]) {
+    let header = ["RECORDED (UTC)", "VERSION", "NAME", "ACTION", "DURATION", "RUN BY", "MIGRATE", "ERROR"];
+
+    let rows: Vec<Vec<String>> = entries.iter().map(|entry| vec![
+        entry.recorded.format("%Y-%m-%d %H:%M:%S").to_string(),
+        entry.version.to_string(),
+        entry.name.clone().unwrap_or_default(),
+        entry.action.clone(),
+        entry.duration_ms.map(|ms| format!("{}ms", ms)).unwrap_or_default(),
+        if entry.db_user.is_empty() {
+            format!("{}@{}", entry.run_by, entry.host)
+        } else {
+            format!("{}@{} ({})", entry.run_by, entry.host, entry.db_user)
+        },
+        entry.tool_version.clone(),
+        entry.error.clone().unwrap_or_default().replace('\n', " "),
+    ]).collect();
+
+    table::print(&header, &rows);
+}
@@ -0,0 +1,8 @@
+//! History sub-command
+
+/// The run method for the Command trait
+#[allow(clippy::module_inception)]
+pub mod history;
+
+/// Command line options for the history sub-command
+pub mod sub_cmd;
@@ -0,0 +1,23 @@
+use clap::{Arg, App, SubCommand};
+
+use commands::db_args::db_args;
+use commands::output::output_arg;
+
+pub fn sub_cmd<'a>() -> App<'a, 'a> {
+    output_arg(db_args(SubCommand::with_name("history")
+        .about("Show changes made to the database schema")
+        .arg(Arg::with_name("schema-version")
+            .help("Only show changes to this schema version")
+            .short("s")
+            .long("schema-version")
+            .takes_value(true)
+        )
+        .arg(Arg::with_name("limit")
+            .help("Maximum number of changes to show, most recent first")
+            .short("l")
+            .long("limit")
+            .takes_value(true)
+            .default_value("50")
+        )
+    ))
+}
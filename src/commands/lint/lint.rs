@@ -0,0 +1,265 @@
+use std::fs;
+use std::path::Path;
+
+use chrono::UTC;
+use chrono::naive::datetime::NaiveDateTime;
+use clap::ArgMatches;
+use sqlparser::ast::Statement;
+
+use commands::run::duration;
+use drivers::Dialect;
+use models;
+use models::migration::{self, Migration, TIMESTAMP_VERSION_FORMAT};
+use models::rules::{RuleConfig, Severity};
+
+/// Number of digits in a timestamp version, see `TIMESTAMP_VERSION_FORMAT`
+const TIMESTAMP_VERSION_DIGITS: usize = 14;
+
+/// Result of checking a migrations directory
+pub struct LintReport {
+    /// Number of migrations checked
+    pub checked: usize,
+    /// Problems which fail the check
+    pub problems: Vec<String>,
+    /// Operations flagged by rules configured to warn
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct LintCmd {
+    migrations_dir: String,
+    dialect: Dialect
+}
+
+impl LintCmd {
+    pub fn new() -> LintCmd {
+        LintCmd {
+            migrations_dir: "migrations".to_owned(),
+            dialect: Dialect::Postgres
+        }
+    }
+
+    /// Checks every migration in the migrations directory
+    ///
+    /// - *returns*: `LintReport` - Every problem found, error message if the migrations directory or
+    ///   its `lint.ini` file can not be read
+    pub fn lint(&self) -> Result<LintReport, String> {
+        let dirs = Migration::dirs(&self.migrations_dir)?;
+        let rules = RuleConfig::load(&self.migrations_dir)?;
+
+        let mut problems = Vec::new();
+        let mut warnings = Vec::new();
+        let mut versions = Vec::new();
+        for path in &dirs {
+            let name = path.file_name().map_or(String::new(), |name| name.to_string_lossy().into_owned());
+
+            // The same checks `run` performs when loading the migration
+            match Migration::load(path, self.dialect) {
+                Ok(migration) => versions.push((migration.version, name.clone())),
+                Err(err) => {
+                    problems.push(err);
+                    if let Ok(version) = Migration::load_version(path) {
+                        versions.push((version, name.clone()));
+                    }
+                }
+            }
+
+            self.lint_files(path, &name, &rules, &mut problems, &mut warnings)?;
+        }
+
+        versions.sort();
+        for group in versions.chunk_by(|a, b| a.0 == b.0).filter(|group| group.len() > 1) {
+            let names: Vec<String> = group.iter().map(|(_, name)| format!("\"{}\"", name)).collect();
+            problems.push(format!("Migrations {} all provide version {}", names.join(", "), group[0].0));
+        }
+
+        let now = UTC::now().naive_utc();
+        for &(version, ref name) in &versions {
+            let version_str = version.to_string();
+            if version_str.len() != TIMESTAMP_VERSION_DIGITS {
+                continue;
+            }
+
+            match NaiveDateTime::parse_from_str(&version_str, TIMESTAMP_VERSION_FORMAT) {
+                Ok(time) if time > now => {
+                    problems.push(format!("Migration \"{}\" version {} is a timestamp in the future, migrations \
+                                           created until then will get lower versions", name, version))
+                },
+                Ok(_) => {},
+                Err(_) => problems.push(format!("Migration \"{}\" version {} looks like a timestamp but is not a valid \
+                                                 date and time", name, version)),
+            }
+        }
+
+        Ok(LintReport {
+            checked: dirs.len(),
+            problems,
+            warnings
+        })
+    }
+
+    /// Checks the files of a migration directory
+    ///
+    /// Every SQL file must be an up or down file for a known driver and parse, its timeout headers
+    /// must hold durations, and every up file must have a down file which reverses it. Up files are
+    /// checked for destructive operations, see `models::rules`. Rust files are skipped with a
+    /// warning, `run` only performs SQL.
+    fn lint_files(&self, path: &Path, name: &str, rules: &RuleConfig, problems: &mut Vec<String>,
+                  warnings: &mut Vec<String>) -> Result<(), String> {
+        let entries = match fs::read_dir(path) {
+            Ok(entries) => entries,
+            Err(err) => return Err(format!("Failed to read migration directory \"{}\": {}", path.display(), err))
+        };
+
+        let mut files = Vec::new();
+        for entry in entries {
+            match entry {
+                Ok(entry) => files.push(entry.file_name().to_string_lossy().into_owned()),
+                Err(err) => return Err(format!("Failed to read migration directory \"{}\": {}", path.display(), err))
+            }
+        }
+        files.sort();
+
+        for file in &files {
+            if file.ends_with(".rs") {
+                warnings.push(format!("Migration \"{}\" {} is not checked, Rust migrations are not supported and run \
+                                       ignores them", name, file));
+                continue;
+            }
+
+            let stem = match file.strip_suffix(".sql") {
+                Some(stem) => stem,
+                None => continue
+            };
+
+            let (kind, dialect) = match stem.split_once('.') {
+                Some((kind, driver)) => match Dialect::from_name(driver) {
+                    Some(dialect) if dialect.name() == driver => (kind, Some(dialect)),
+                    _ => {
+                        problems.push(format!("Migration \"{}\" {} is not for a known driver, expected postgres, sqlite \
+                                               or mysql", name, file));
+                        continue;
+                    }
+                },
+                None => (stem, None)
+            };
+
+            if kind != "up" && kind != "down" {
+                problems.push(format!("Migration \"{}\" {} is never run, SQL files must be named up.sql or down.sql",
+                                      name, file));
+                continue;
+            }
+
+            // Portable files are checked against the Postgres grammar, or the driver's if one was given
+            let parse_dialect = dialect.unwrap_or(self.dialect);
+            let sql = match fs::read_to_string(path.join(file)) {
+                Ok(sql) => sql,
+                Err(err) => {
+                    problems.push(format!("Migration \"{}\" {} can not be read: {}", name, file, err));
+                    continue;
+                }
+            };
+
+            for header in &[migration::STATEMENT_TIMEOUT_HEADER, migration::LOCK_TIMEOUT_HEADER] {
+                if let Some(value) = migration::header(&sql, header) {
+                    if let Err(err) = duration::parse(&format!("Migration \"{}\" {} \"{}\" header", name, file, header), value) {
+                        problems.push(err);
+                    }
+                }
+            }
+
+            // Placeholders are filled in per environment, their names stand in for their values
+            let sql = migration::replace_placeholders(&sql, |name| name.to_owned());
+
+            let parsed = parse_dialect.parse_sql(&sql);
+
+            // Postgres refuses to build an index concurrently inside a transaction
+            if let Ok(ref statements) = parsed {
+                if statements.iter().any(is_concurrent_index)
+                   && migration::header(&sql, migration::NO_TRANSACTION_HEADER).is_none() {
+                    problems.push(format!("Migration \"{}\" {} builds an index CONCURRENTLY, which can not run in a \
+                                           transaction, add a \"{}\" header", name, file, migration::NO_TRANSACTION_HEADER));
+                }
+            }
+
+            match parsed {
+                Ok(ref statements) if statements.is_empty() && kind == "up" => {
+                    problems.push(format!("Migration \"{}\" {} has no statements", name, file))
+                },
+                Ok(ref statements) if kind == "up" => {
+                    for violation in rules.check(parse_dialect, &sql, statements) {
+                        let message = format!("Migration \"{}\" {}: {}", name, file, violation);
+                        match violation.severity {
+                            Severity::Deny => problems.push(message),
+                            _ => warnings.push(message),
+                        }
+                    }
+                },
+                Ok(_) => {},
+                Err(err) => problems.push(format!("Migration \"{}\" {} does not parse as {} SQL: {}",
+                                                  name, file, parse_dialect.name(), err)),
+            }
+
+            if kind == "up" {
+                let has_down = match dialect {
+                    Some(dialect) => migration::sql_path(path, "down", dialect).is_some(),
+                    None => path.join("down.sql").exists()
+                };
+
+                if !has_down {
+                    let down = file.replacen("up", "down", 1);
+                    problems.push(format!("Migration \"{}\" has {} but no {} to reverse it", name, file, down));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn is_concurrent_index(statement: &Statement) -> bool {
+    match *statement {
+        Statement::CreateIndex(ref index) => index.concurrently,
+        _ => false
+    }
+}
+
+impl models::command::Command <LintCmd> for LintCmd {
+    fn from_matches(matches: &ArgMatches) -> Result<LintCmd, String> {
+        let mut obj = LintCmd::new();
+
+        if let Some(migrations_dir) = matches.value_of("migrations-dir") {
+            obj.migrations_dir = migrations_dir.to_owned();
+        }
+
+        if let Some(driver) = matches.value_of("driver") {
+            obj.dialect = match Dialect::from_name(driver) {
+                Some(dialect) => dialect,
+                None => return Err(format!("Unknown database driver \"{}\", expected postgres, sqlite or mysql", driver))
+            };
+        }
+
+        Ok(obj)
+    }
+
+    fn run(&self) -> Result<(), String> {
+        let report = self.lint()?;
+
+        for warning in &report.warnings {
+            println!("- warning: {}", warning);
+        }
+
+        for problem in &report.problems {
+            println!("- {}", problem);
+        }
+
+        if !report.problems.is_empty() {
+            return Err(format!("Found {} problems in {} migrations in \"{}\"", report.problems.len(), report.checked,
+                               self.migrations_dir))
+        }
+
+        println!("{} migrations in \"{}\" have no problems", report.checked, self.migrations_dir);
+
+        Ok(())
+    }
+}
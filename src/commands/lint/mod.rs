@@ -0,0 +1,8 @@
+//! Lint sub-command
+
+/// The run method for the Command trait
+#[allow(clippy::module_inception)]
+pub mod lint;
+
+/// Command line options for the lint sub-command
+pub mod sub_cmd;
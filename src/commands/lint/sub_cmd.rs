@@ -0,0 +1,19 @@
+use clap::{Arg, App, SubCommand};
+
+pub fn sub_cmd<'a>() -> App<'a, 'a> {
+    SubCommand::with_name("lint")
+        .alias("verify")
+        .about("Check a migrations directory for problems without connecting to a database")
+        .arg(Arg::with_name("migrations-dir")
+            .help("The directory to check migrations in")
+            .short("m")
+            .long("migrations-dir")
+            .takes_value(true)
+            .default_value("migrations")
+        )
+        .arg(Arg::with_name("driver")
+            .help("Database driver the migrations must run on, postgres (default), sqlite or mysql")
+            .long("driver")
+            .takes_value(true)
+        )
+}
@@ -4,4 +4,17 @@ extern crate clap;
 extern crate ini;
 extern crate postgres;
 
-pub mod run;
\ No newline at end of file
+pub mod create;
+pub mod db_args;
+pub mod drop;
+pub mod dump_schema;
+pub mod history;
+pub mod lint;
+pub mod output;
+pub mod redo;
+pub mod repair;
+pub mod reset;
+pub mod run;
+pub mod seed;
+pub mod status;
+pub mod table;
\ No newline at end of file
@@ -0,0 +1,33 @@
+//! Command line option shared by sub-commands which can print machine-readable output
+
+use clap::{Arg, App, ArgMatches};
+
+/// What a sub-command prints on stdout
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    /// Text meant to be read by people
+    Human,
+    /// A single JSON document, logs stay on stderr
+    Json,
+}
+
+impl OutputFormat {
+    /// Reads the `--output` option
+    pub fn from_matches(matches: &ArgMatches) -> OutputFormat {
+        match matches.value_of("output") {
+            Some("json") => OutputFormat::Json,
+            _ => OutputFormat::Human
+        }
+    }
+}
+
+/// Adds the `--output` option `OutputFormat` is read from
+pub fn output_arg<'a>(cmd: App<'a, 'a>) -> App<'a, 'a> {
+    cmd.arg(Arg::with_name("output")
+        .help("Format of the output written to stdout, json prints a single document scripts can parse")
+        .long("output")
+        .takes_value(true)
+        .possible_values(&["human", "json"])
+        .default_value("human")
+    )
+}
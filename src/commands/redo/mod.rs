@@ -0,0 +1,8 @@
+//! Redo sub-command
+
+/// The run method for the Command trait
+#[allow(clippy::module_inception)]
+pub mod redo;
+
+/// Command line options for the redo sub-command
+pub mod sub_cmd;
@@ -0,0 +1,42 @@
+use clap::ArgMatches;
+
+use commands::run::run::RunCmd;
+use commands::run::target::Target;
+use models;
+
+#[derive(Debug)]
+pub struct RedoCmd {
+    run: RunCmd,
+    count: i32
+}
+
+impl models::command::Command <RedoCmd> for RedoCmd {
+    fn from_matches(matches: &ArgMatches) -> Result<RedoCmd, String> {
+        let count = match matches.value_of("count") {
+            Some(count) => match count.parse::<i32>() {
+                Ok(count) if count > 0 => count,
+                _ => return Err(format!("Number of migrations to redo must be an integer greater than 0, found \"{}\"", count))
+            },
+            None => 1
+        };
+
+        Ok(RedoCmd {
+            run: RunCmd::from_matches(matches)?,
+            count
+        })
+    }
+
+    /// Reverses the last `count` migrations, then performs them again
+    ///
+    /// Both steps are recorded and reported like any other run, see `RunCmd`. Redoing migrations
+    /// loses the data their down SQL removes, so it is refused in production environments.
+    fn run(&self) -> Result<(), String> {
+        let db = self.run.db();
+        if db.production {
+            return Err(format!("Refusing to redo migrations in the production environment \"{}\"",
+                               db.environment.as_ref().map_or("", |env| env.as_str())))
+        }
+
+        self.run.migrate_and_report(&[Target::Relative(-self.count), Target::Relative(self.count)])
+    }
+}
@@ -0,0 +1,14 @@
+use clap::{Arg, App, SubCommand};
+
+use commands::run::sub_cmd::run_args;
+
+pub fn sub_cmd<'a>() -> App<'a, 'a> {
+    run_args(SubCommand::with_name("redo")
+        .about("Reverse the last applied migrations then perform them again")
+        .arg(Arg::with_name("count")
+            .help("Number of migrations to redo")
+            .index(1)
+            .default_value("1")
+        )
+    )
+}
@@ -0,0 +1,91 @@
+use clap::ArgMatches;
+
+use drivers;
+use models;
+use models::config::DbConfig;
+use models::dbschema::{DbSchemaVer, VerEntry};
+use models::history::{History, HistoryAction, HistoryEvent};
+
+#[derive(Debug)]
+pub struct RepairDuplicatesCmd {
+    db: DbConfig,
+    dry_run: bool
+}
+
+impl RepairDuplicatesCmd {
+    pub fn new(db: DbConfig) -> RepairDuplicatesCmd {
+        RepairDuplicatesCmd {
+            db,
+            dry_run: false
+        }
+    }
+}
+
+impl models::command::Command <RepairDuplicatesCmd> for RepairDuplicatesCmd {
+    fn from_matches(matches: &ArgMatches) -> Result<RepairDuplicatesCmd, String> {
+        let mut obj = RepairDuplicatesCmd::new(DbConfig::from_matches(matches)?);
+        obj.dry_run = matches.is_present("dry-run");
+
+        Ok(obj)
+    }
+
+    fn run(&self) -> Result<(), String> {
+        let db_conn = self.db.connect()?;
+        let schema_ver = DbSchemaVer::bootstrap(db_conn.as_ref())?;
+        let history = History::new(db_conn.as_ref());
+
+        let dups = drivers::transaction(db_conn.as_ref(), || {
+            // Find duplicates while holding the lock so the plan can not change before it is carried out
+            schema_ver.lock()?;
+            let dups = schema_ver.duplicates()?;
+
+            if dups.is_empty() {
+                println!("No duplicate schema version rows found");
+                return Ok(dups);
+            }
+
+            for dup in &dups {
+                println!("Version {}:", dup.keep.version);
+                println!("    keep   {}", describe(&dup.keep));
+                for entry in &dup.delete {
+                    println!("    delete {}", describe(entry));
+                }
+            }
+
+            if self.dry_run {
+                println!("Dry run, no rows deleted");
+                return Ok(Vec::new());
+            }
+
+            self.db.confirm_protected("delete duplicate schema_versions rows")?;
+
+            for dup in &dups {
+                for entry in &dup.delete {
+                    schema_ver.delete_entry(entry.id)?;
+                }
+
+                history.record(&HistoryEvent::for_entry(HistoryAction::Repair, &dup.keep))?;
+            }
+
+            Ok(dups)
+        })?;
+
+        if dups.is_empty() {
+            return Ok(());
+        }
+
+        println!("Deleted {} duplicate rows", dups.iter().map(|dup| dup.delete.len()).sum::<usize>());
+
+        Ok(())
+    }
+}
+
+/// Describes a row of the `schema_versions` table on one line
+fn describe(entry: &VerEntry) -> String {
+    format!("row {} ({}, updated {}, migration \"{}\", hash {})",
+            entry.id,
+            entry.status.as_str(),
+            entry.updated.format("%Y-%m-%d %H:%M:%S"),
+            entry.name.clone().unwrap_or_default(),
+            entry.migration_hash)
+}
@@ -0,0 +1,7 @@
+//! Repair sub-command
+
+/// The run method for the Command trait of the repair duplicates sub-command
+pub mod duplicates;
+
+/// Command line options for the repair sub-command
+pub mod sub_cmd;
@@ -0,0 +1,17 @@
+use clap::{AppSettings, Arg, App, SubCommand};
+
+use commands::db_args::db_args;
+
+pub fn sub_cmd<'a>() -> App<'a, 'a> {
+    SubCommand::with_name("repair")
+        .about("Repair inconsistencies in the tables Migrate uses to track schema versions")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(db_args(SubCommand::with_name("duplicates")
+            .about("Delete duplicate rows for the same schema version, keeping the most recently updated")
+            .arg(Arg::with_name("dry-run")
+                .help("Show which rows would be kept and deleted without deleting them")
+                .short("n")
+                .long("dry-run")
+            )
+        ))
+}
@@ -0,0 +1,8 @@
+//! Reset sub-command
+
+/// The run method for the Command trait
+#[allow(clippy::module_inception)]
+pub mod reset;
+
+/// Command line options for the reset sub-command
+pub mod sub_cmd;
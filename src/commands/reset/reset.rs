@@ -0,0 +1,24 @@
+use clap::ArgMatches;
+
+use commands::run::run::RunCmd;
+use commands::run::target::Target;
+use models;
+
+#[derive(Debug)]
+pub struct ResetCmd {
+    run: RunCmd
+}
+
+impl models::command::Command <ResetCmd> for ResetCmd {
+    fn from_matches(matches: &ArgMatches) -> Result<ResetCmd, String> {
+        Ok(ResetCmd {
+            run: RunCmd::from_matches(matches)?
+        })
+    }
+
+    fn run(&self) -> Result<(), String> {
+        self.run.db().confirm_protected("reset the database")?;
+
+        self.run.migrate_and_report(&[Target::Version(0), Target::Latest])
+    }
+}
@@ -0,0 +1,9 @@
+use clap::{App, SubCommand};
+
+use commands::run::sub_cmd::run_args;
+
+pub fn sub_cmd<'a>() -> App<'a, 'a> {
+    run_args(SubCommand::with_name("reset")
+        .about("Reverse every migration then perform them all again")
+    )
+}
@@ -0,0 +1,34 @@
+use std::time::Duration;
+
+/// Parses a duration given to an option, ex. `500ms`, `5s` or `2m`, a number without a unit is in
+/// milliseconds
+///
+/// - `option: &str` - Name of the option, for error messages
+/// - `value: &str` - Value of the option
+/// - *returns*: `Duration` - Parsed duration, error message if fail
+pub fn parse(option: &str, value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (num, unit) = value.split_at(split);
+
+    let num = match num.parse::<u64>() {
+        Ok(num) => num,
+        Err(_) => return Err(format!("{} must be a duration such as 500ms, 5s or 2m, found \"{}\"", option, value))
+    };
+
+    match (unit.trim(), num.checked_mul(60)) {
+        ("" | "ms", _) => Ok(Duration::from_millis(num)),
+        ("s", _) => Ok(Duration::from_secs(num)),
+        ("m" | "min", Some(secs)) => Ok(Duration::from_secs(secs)),
+        _ => Err(format!("{} must be a duration such as 500ms, 5s or 2m, found \"{}\"", option, value))
+    }
+}
+
+/// Formats a duration for log messages, in milliseconds below a second and seconds above
+pub fn format(duration: Duration) -> String {
+    if duration < Duration::from_secs(1) {
+        format!("{}ms", duration.as_millis())
+    } else {
+        format!("{:.1}s", duration.as_secs_f64())
+    }
+}
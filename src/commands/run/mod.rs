@@ -1,7 +1,17 @@
 //! Run sub-command
 
 /// The run method for the Command trait
+#[allow(clippy::module_inception)]
 pub mod run;
 
-///
-pub mod sub_cmd;
\ No newline at end of file
+/// Durations given to the run sub-command's options
+pub mod duration;
+
+/// Report of what a run did, printed with `--output json`
+pub mod report;
+
+/// Command line options for the run sub-command
+pub mod sub_cmd;
+
+/// Resolving the `--target/-t` option to a schema version
+pub mod target;
\ No newline at end of file
@@ -0,0 +1,175 @@
+use std::time::Duration;
+
+use serde_json::Value;
+
+use models::history::HistoryAction;
+use models::migration::Migration;
+use models::repeatable::Repeatable;
+
+/// What happened to a migration during a run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StepStatus {
+    /// Would have been performed or reversed, but an earlier step failed
+    Planned,
+    /// Performed
+    Applied,
+    /// Reversed
+    Reversed,
+    /// Not reversed because it was never applied
+    Skipped,
+    /// Performing or reversing it failed
+    Failed,
+}
+
+impl StepStatus {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            StepStatus::Planned => "planned",
+            StepStatus::Applied => "applied",
+            StepStatus::Reversed => "reversed",
+            StepStatus::Skipped => "skipped",
+            StepStatus::Failed => "failed",
+        }
+    }
+}
+
+/// How long a statement of a migration took
+#[derive(Debug)]
+pub struct StatementTiming {
+    /// Statement, as written in the migration
+    pub sql: String,
+    pub duration: Duration,
+}
+
+/// A migration a run planned to perform or reverse
+#[derive(Debug)]
+pub struct StepReport {
+    pub version: i64,
+    pub name: String,
+    /// `Apply` or `Rollback`
+    pub action: HistoryAction,
+    pub status: StepStatus,
+    pub duration: Option<Duration>,
+    /// Number of times the migration's transaction ran, more than one if it was retried
+    pub attempts: u32,
+    /// Time taken by each statement, empty if the statements could not be told apart and ran as
+    /// one batch
+    pub statements: Vec<StatementTiming>,
+    /// SQL which would have run, rendered, only known for dry runs
+    pub sql: Option<String>,
+    pub error: Option<String>,
+}
+
+impl StepReport {
+    /// Creates a step which has not been carried out yet
+    pub fn planned(action: HistoryAction, migration: &Migration) -> StepReport {
+        StepReport {
+            version: migration.version,
+            name: migration.name.clone(),
+            action,
+            status: StepStatus::Planned,
+            duration: None,
+            attempts: 0,
+            statements: Vec::new(),
+            sql: None,
+            error: None,
+        }
+    }
+}
+
+/// A repeatable migration a run planned to apply because it changed, see `models::repeatable`
+#[derive(Debug)]
+pub struct RepeatableReport {
+    pub name: String,
+    /// `Planned`, `Applied` or `Failed`
+    pub status: StepStatus,
+    pub duration: Option<Duration>,
+    pub error: Option<String>,
+}
+
+impl RepeatableReport {
+    /// Creates a step which has not been carried out yet
+    pub fn planned(repeatable: &Repeatable) -> RepeatableReport {
+        RepeatableReport {
+            name: repeatable.name.clone(),
+            status: StepStatus::Planned,
+            duration: None,
+            error: None,
+        }
+    }
+}
+
+/// What a run did, printed on stdout with `--output json`
+#[derive(Debug, Default)]
+pub struct RunReport {
+    pub database: Option<String>,
+    /// Version before the run
+    pub initial_version: Option<i64>,
+    /// Version the run tried to reach
+    pub target_version: Option<i64>,
+    /// Version after the run, even if it failed
+    pub final_version: Option<i64>,
+    /// Migrations were only planned, see the `--dry-run/-n` option
+    pub dry_run: bool,
+    pub steps: Vec<StepReport>,
+    /// Repeatable migrations applied after `steps`
+    pub repeatables: Vec<RepeatableReport>,
+    pub duration: Option<Duration>,
+    pub error: Option<String>,
+}
+
+impl RunReport {
+    pub fn new() -> RunReport {
+        RunReport::default()
+    }
+
+    /// Serializes the report
+    ///
+    /// The schema is stable, fields are only ever added. Fields which are not known, ex. the
+    /// versions if connecting to the database failed, are `null`.
+    pub fn to_json(&self) -> Value {
+        let steps: Vec<Value> = self.steps.iter().map(|step| json!({
+            "version": step.version,
+            "name": step.name,
+            "action": step.action.as_str(),
+            "status": step.status.as_str(),
+            "duration_ms": step.duration.map(|d| d.as_millis() as u64),
+            "attempts": step.attempts,
+            "statements": step.statements.iter().map(|statement| json!({
+                "sql": statement.sql,
+                "duration_ms": statement.duration.as_millis() as u64,
+            })).collect::<Vec<Value>>(),
+            "sql": step.sql,
+            "error": step.error,
+        })).collect();
+
+        let repeatables: Vec<Value> = self.repeatables.iter().map(|repeatable| json!({
+            "name": repeatable.name,
+            "status": repeatable.status.as_str(),
+            "duration_ms": repeatable.duration.map(|d| d.as_millis() as u64),
+            "error": repeatable.error,
+        })).collect();
+
+        let count = |status: StepStatus| self.steps.iter().filter(|step| step.status == status).count();
+
+        json!({
+            "status": if self.error.is_none() { "success" } else { "fail" },
+            "error": self.error,
+            "database": self.database,
+            "initial_version": self.initial_version,
+            "target_version": self.target_version,
+            "final_version": self.final_version,
+            "dry_run": self.dry_run,
+            "duration_ms": self.duration.map(|d| d.as_millis() as u64),
+            "summary": {
+                "planned": count(StepStatus::Planned),
+                "applied": count(StepStatus::Applied),
+                "reversed": count(StepStatus::Reversed),
+                "skipped": count(StepStatus::Skipped),
+                "failed": count(StepStatus::Failed),
+            },
+            "migrations": steps,
+            "repeatables": repeatables,
+        })
+    }
+}
@@ -1,106 +1,832 @@
+use std::collections::HashMap;
+use std::slice;
+use std::thread;
+use std::time::{Duration, Instant};
+
 use clap::ArgMatches;
-use ini::Ini;
-use postgres::{Connection, SslMode};
 
+use commands::output::OutputFormat;
+use commands::run::duration;
+use commands::run::report::{RepeatableReport, RunReport, StatementTiming, StepReport, StepStatus};
+use commands::run::target::Target;
+use commands::table;
+use drivers::{self, Driver};
 use models;
+use models::config::DbConfig;
+use models::dbschema::{DbSchemaVer, VerStatus};
+use models::history::{History, HistoryAction, HistoryEvent};
+use models::hooks::{HookContext, Hooks, Stage};
+use models::logger;
+use models::migration::{self, Migration};
+use models::repeatable::{Repeatable, Repeatables};
+use models::rules::{self, RuleConfig, Severity};
+use models::schema_dump;
 
 #[derive(Debug)]
 pub struct RunCmd {
-    host: String,
-    user: String,
-    password: String,
-    database: String
+    db: DbConfig,
+    target: Target,
+    migrations_dir: String,
+    allow_out_of_order: bool,
+    dry_run: bool,
+    output: OutputFormat,
+    slow_threshold: Duration,
+    statement_timeout: Option<Duration>,
+    lock_timeout: Option<Duration>,
+    retries: u32,
+    retry_delay: Duration,
+    hooks: Hooks,
+    /// Schema file written after a successful run, see `--dump-schema`
+    dump_schema: Option<String>
 }
 
 impl RunCmd {
-    pub fn new(host: String, user: String, password: String, database: String) -> RunCmd {
+    pub fn new(db: DbConfig) -> RunCmd {
         RunCmd {
-            host: host,
-            user: user,
-            password: password,
-            database: database
+            db,
+            target: Target::Latest,
+            migrations_dir: "migrations".to_owned(),
+            allow_out_of_order: false,
+            dry_run: false,
+            output: OutputFormat::Human,
+            slow_threshold: Duration::from_secs(10),
+            statement_timeout: None,
+            lock_timeout: None,
+            retries: 3,
+            retry_delay: Duration::from_millis(500),
+            hooks: Hooks::default(),
+            dump_schema: None
          }
     }
 
-    fn make_db_conn_str(&self) -> String {
-        format!("postgresql://{}:{}@{}/{}", self.user, self.password, self.host, self.database)
+    /// Database connection information the command was loaded with
+    pub fn db(&self) -> &DbConfig {
+        &self.db
     }
-}
 
-impl models::command::Command <RunCmd> for RunCmd {
-    fn from_matches(matches: &ArgMatches) -> Result<RunCmd, String> {
-        let mut obj = RunCmd::new(String::new(), String::new(), String::new(), String::new());
+    /// Performs or reverses migrations until the database reaches each target version in turn
+    ///
+    /// - `targets: &[Target]` - Versions to reach one after the other, see the `--target/-t` option
+    /// - `report: &mut RunReport` - Filled in with the versions and the migrations planned and
+    ///   carried out, even if the run fails
+    /// - *returns*: Error message if fail
+    ///
+    /// Hooks configured in the migrations directory run around the migrations, see `models::hooks`.
+    /// Sub-commands which run migrations towards several targets (ex., `redo`) reuse this with the
+    /// configuration `run` loaded, passing every target at once so the `before_run`, `after_run`
+    /// and `on_failure` hooks and the schema dump happen once per invocation.
+    pub fn migrate(&self, targets: &[Target], report: &mut RunReport) -> Result<(), String> {
+        let migrations = Migration::load_dir(&self.migrations_dir, self.db.driver)?;
+
+        let db_conn = self.db.connect()?;
+        report.database = Some(db_conn.database_name());
+
+        // Dry runs change nothing, not even the bookkeeping tables
+        let schema_ver = if self.dry_run {
+            DbSchemaVer::open(db_conn.as_ref())?
+        } else {
+            DbSchemaVer::bootstrap(db_conn.as_ref())?
+        };
+
+        let mut state = RunState::read(&schema_ver)?;
+        report.initial_version = Some(state.current);
+        report.dry_run = self.dry_run;
 
-        // Config from ini file
-        if let Some(config_path) = matches.value_of("config") {
-            let confr = Ini::load_from_file(config_path);
-            if let Err(err) = confr {
-                return Err(err.to_string())
+        let mut result = Ok(());
+        let mut target = 0;
+        for next in targets {
+            result = self.steps(db_conn.as_ref(), &schema_ver, &migrations, next, &mut state, report)
+                .map(|resolved| target = resolved);
+            if result.is_err() {
+                break;
             }
+        }
 
-            let conf = confr.unwrap();
+        if result.is_ok() {
+            result = self.repeatables(db_conn.as_ref(), &schema_ver, &migrations, target, report)
+                .and_then(|_| if self.dry_run { Ok(()) } else { self.dump_schema(db_conn.as_ref(), target) })
+                .and_then(|_| if self.dry_run { Ok(()) } else { self.hook(Stage::AfterRun, db_conn.as_ref(), report, None) });
+        }
+
+        match result {
+            Err(ref err) if state.started => {
+                let failed = report.steps.iter().position(|step| step.status == StepStatus::Failed);
+                let mut context = self.hook_context(db_conn.as_ref(), report, failed);
+                context.error = Some(err.clone());
+                if let Err(hook_err) = self.hooks.run(Stage::OnFailure, db_conn.as_ref(), &context) {
+                    error!("{}", hook_err);
+                }
+            },
+            _ => {}
+        }
+
+        report.final_version = current_version(&schema_ver).ok();
+
+        result
+    }
+
+    /// Performs or reverses migrations towards each target, see `migrate`, then reports what the
+    /// run did the way `--output` asks
+    ///
+    /// The report is a summary table in the logs, or a JSON document on stdout which is printed even
+    /// if the run fails.
+    pub fn migrate_and_report(&self, targets: &[Target]) -> Result<(), String> {
+        let mut report = RunReport::new();
+
+        let started = Instant::now();
+        let result = self.migrate(targets, &mut report);
+
+        match self.output {
+            OutputFormat::Human => log_summary(&report),
+            OutputFormat::Json => {
+                report.duration = Some(started.elapsed());
+                report.error = result.as_ref().err().cloned();
+                println!("{}", report.to_json());
+            },
+        }
+
+        result
+    }
+
+    /// Resolves a target against the applied versions, then plans and carries out the migrations
+    /// which take the database there
+    ///
+    /// - `state: &mut RunState` - What earlier targets of the run did, updated with the migrations
+    ///   planned so the next target resolves against them, even in a dry run
+    /// - *returns*: `i64` - Version the target resolved to, error message if fail
+    ///
+    /// Every migration planned is added to `report` before any of them are carried out. The
+    /// `before_run` hook runs before the first migration of the run is carried out.
+    fn steps(&self, conn: &dyn Driver, schema_ver: &DbSchemaVer, migrations: &[Migration], target: &Target,
+             state: &mut RunState, report: &mut RunReport) -> Result<i64, String> {
+        let current = state.current;
+        let target = match target.resolve(migrations, current, &state.applied) {
+            Ok(resolved) => resolved,
+            Err(err) => return Err(format!("Can not resolve target {} against \"{}\": {}", target, self.migrations_dir, err))
+        };
+        report.target_version = Some(target);
 
-            let sectionr = conf.section(matches.value_of("environment"));
-            if sectionr.is_none() {
-                return Err(format!("No config for environment \"{}\"", matches.value_of("environment").unwrap_or("None")))
+        info!("{} is at version {}, target is version {}", conn.database_name(), current, target);
+
+        // Warn about migrations which changed after they were applied, and find older migrations which
+        // were never applied, usually because they were merged after newer ones ran
+        let mut out_of_order = Vec::new();
+        for migration in migrations.iter().filter(|m| m.version <= current) {
+            if !state.applied.contains(&migration.version) {
+                out_of_order.push(migration);
+                continue;
+            }
+
+            if let Some(entry) = schema_ver.by_version_num(migration.version)? {
+                if entry.status == VerStatus::Success && entry.migration_hash != migration.hash {
+                    warn!("Migration \"{}\" (version {}) has changed since it was applied", migration.name, migration.version);
+                }
             }
+        }
+
+        if target >= current {
+            if !out_of_order.is_empty() {
+                let names: Vec<String> = out_of_order.iter()
+                    .map(|m| format!("\"{}\" (version {})", m.name, m.version))
+                    .collect();
+
+                if !self.allow_out_of_order {
+                    return Err(format!("Migrations older than the current version {} have not been applied: {}. \
+                                        Run with --allow-out-of-order to apply them", current, names.join(", ")))
+                }
+
+                warn!("Applying migrations older than the current version {}: {}", current, names.join(", "));
+            }
+
+            let pending: Vec<&Migration> = out_of_order.into_iter()
+                .chain(migrations.iter().filter(|m| m.version > current && m.version <= target))
+                .collect();
+
+            let first = report.steps.len();
+            report.steps.extend(pending.iter().map(|m| StepReport::planned(HistoryAction::Apply, m)));
+
+            let rendered = self.render(&pending)?;
+            let pending: Vec<&Migration> = rendered.iter().collect();
 
-            let section = sectionr.unwrap();
+            self.check_rules(&pending)?;
 
-            if let Some(host) = section.get("host") {
-                obj.host = host.to_owned();
+            if self.dry_run {
+                for (i, migration) in pending.iter().enumerate() {
+                    self.print_dry_run(migration, Some(&migration.up), &mut report.steps[first + i]);
+                }
+            } else {
+                self.before_run(conn, state, report)?;
+
+                for (i, migration) in pending.iter().enumerate() {
+                    self.hook(Stage::BeforeEach, conn, report, Some(first + i))?;
+                    self.up(conn, schema_ver, migration, &mut report.steps[first + i])?;
+                    self.hook(Stage::AfterEach, conn, report, Some(first + i))?;
+                }
             }
 
-            if let Some(user) = section.get("user") {
-                obj.user = user.to_owned();
+            state.applied.extend(pending.iter().map(|m| m.version));
+        } else {
+            if !migrations.iter().any(|m| m.version == current) {
+                return Err(format!("Version {} is applied but no migration in \"{}\" provides it, can not reverse it",
+                                   current, self.migrations_dir))
             }
 
-            if let Some(password) = section.get("password") {
-                obj.password = password.to_owned();
+            let reversing: Vec<(&Migration, bool)> = migrations.iter().rev()
+                .filter(|m| m.version <= current && m.version > target)
+                .map(|m| (m, state.applied.contains(&m.version)))
+                .collect();
+
+            let first = report.steps.len();
+            report.steps.extend(reversing.iter().map(|&(m, applied)| {
+                let mut step = StepReport::planned(HistoryAction::Rollback, m);
+                if !applied {
+                    step.status = StepStatus::Skipped;
+                }
+                step
+            }));
+
+            let rendered = self.render(&reversing.iter().map(|&(m, _)| m).collect::<Vec<&Migration>>())?;
+            let reversing: Vec<(&Migration, bool)> = rendered.iter().zip(reversing.iter().map(|&(_, applied)| applied)).collect();
+
+            if self.dry_run {
+                for (i, &(migration, was_applied)) in reversing.iter().enumerate() {
+                    if was_applied {
+                        self.print_dry_run(migration, migration.down.as_ref(), &mut report.steps[first + i]);
+                    }
+                }
+            } else {
+                self.db.confirm_protected(&format!("reverse migrations from version {} to version {}", current, target))?;
+                self.before_run(conn, state, report)?;
+
+                for (i, &(migration, was_applied)) in reversing.iter().enumerate() {
+                    if was_applied {
+                        self.hook(Stage::BeforeEach, conn, report, Some(first + i))?;
+                        self.down(conn, schema_ver, migration, &mut report.steps[first + i])?;
+                        self.hook(Stage::AfterEach, conn, report, Some(first + i))?;
+                    } else {
+                        warn!("Migration \"{}\" (version {}) was never applied, skipping", migration.name, migration.version);
+                    }
+                }
             }
 
-            if let Some(database) = section.get("database") {
-                obj.database = database.to_owned();
+            state.applied.retain(|version| !reversing.iter().any(|&(m, _)| m.version == *version));
+        }
+
+        state.current = state.applied.iter().max().cloned().unwrap_or(0);
+
+        Ok(target)
+    }
+
+    /// Fills in the `{{ name }}` placeholders of migrations about to be carried out with the
+    /// variables of the environment, see `migration::render`
+    ///
+    /// Every migration is rendered before any of them runs, so a placeholder without a value stops
+    /// the run before it changes anything.
+    fn render(&self, migrations: &[&Migration]) -> Result<Vec<Migration>, String> {
+        migrations.iter().map(|migration| migration.render(&self.db.variables)).collect()
+    }
+
+    /// Prints the SQL a migration would run, instead of running it, see the `--dry-run/-n` option
+    ///
+    /// - `sql: Option<&String>` - Rendered up or down SQL, None if the migration can not be reversed
+    ///
+    /// With `--output json` the SQL is added to the step instead, stdout only holds the report.
+    fn print_dry_run(&self, migration: &Migration, sql: Option<&String>, step: &mut StepReport) {
+        step.sql = sql.cloned();
+        if self.output == OutputFormat::Json {
+            return;
+        }
+
+        println!("-- Migration \"{}\" (version {}), {}", migration.name, migration.version, step.action.as_str());
+        match sql {
+            Some(sql) => println!("{}\n", sql.trim_end()),
+            None => println!("-- No down.sql, can not be reversed\n"),
+        }
+    }
+
+    /// Applies the repeatable migrations which changed since they were last applied, see
+    /// `models::repeatable`
+    ///
+    /// Repeatable migrations are written against the latest schema, they are only applied by runs
+    /// which take the database to the latest version. Each runs in its own transaction together with
+    /// the update to the `schema_repeatables` table.
+    fn repeatables(&self, conn: &dyn Driver, schema_ver: &DbSchemaVer, migrations: &[Migration], target: i64,
+                   report: &mut RunReport) -> Result<(), String> {
+        let repeatables = Repeatable::load_dir(&self.migrations_dir, self.db.driver)?;
+
+        let latest = migrations.last().map_or(0, |m| m.version);
+        if repeatables.is_empty() || target < latest {
+            return Ok(());
+        }
+
+        let tracker = Repeatables::new(conn);
+        let applied = if schema_ver.tables_exist() { tracker.applied()? } else { HashMap::new() };
+        let changed: Vec<&Repeatable> = repeatables.iter()
+            .filter(|repeatable| applied.get(&repeatable.name) != Some(&repeatable.hash))
+            .collect();
+
+        let first = report.repeatables.len();
+        report.repeatables.extend(changed.iter().map(|repeatable| RepeatableReport::planned(repeatable)));
+
+        if self.dry_run {
+            if self.output == OutputFormat::Human {
+                for repeatable in changed {
+                    println!("-- Repeatable migration \"{}\"\n{}\n", repeatable.name, repeatable.sql.trim_end());
+                }
             }
+            return Ok(());
         }
 
-        // Config from options
-        if let Some(host) = matches.value_of("host") {
-            obj.host = host.to_owned();
+        for (i, repeatable) in changed.into_iter().enumerate() {
+            let step = &mut report.repeatables[first + i];
+            info!("Applying repeatable migration \"{}\"", repeatable.name);
+
+            let started = Instant::now();
+            let result = drivers::transaction(conn, || {
+                conn.lock()?;
+                conn.set_timeouts(self.statement_timeout, self.lock_timeout)?;
+                conn.batch_execute(&repeatable.sql)?;
+                tracker.record(repeatable, started.elapsed())
+            });
+            reset_timeouts(conn, self.statement_timeout, self.lock_timeout);
+            step.duration = Some(started.elapsed());
+
+            if let Err(err) = result {
+                step.status = StepStatus::Failed;
+                step.error = Some(err.clone());
+                return Err(format!("Repeatable migration \"{}\" failed: {}", repeatable.name, err))
+            }
+
+            step.status = StepStatus::Applied;
+            info!("Applied repeatable migration \"{}\" in {}", repeatable.name, duration::format(started.elapsed()));
         }
 
-        if let Some(user) = matches.value_of("user") {
-            obj.user = user.to_owned();
+        Ok(())
+    }
+
+    /// Writes the schema file if one was asked for, see `--dump-schema`
+    fn dump_schema(&self, conn: &dyn Driver, version: i64) -> Result<(), String> {
+        let path = match self.dump_schema {
+            Some(ref path) => path,
+            None => return Ok(())
+        };
+
+        schema_dump::write(path, &schema_dump::dump(conn, version)?)?;
+        info!("Wrote the schema of {} at version {} to {}", conn.database_name(), version, path);
+
+        Ok(())
+    }
+
+    /// Runs the `before_run` hook unless an earlier target of the run already did
+    fn before_run(&self, conn: &dyn Driver, state: &mut RunState, report: &RunReport) -> Result<(), String> {
+        if state.started {
+            return Ok(());
         }
 
-        if let Some(password) = matches.value_of("password") {
-            obj.password = password.to_owned();
+        state.started = true;
+        self.hook(Stage::BeforeRun, conn, report, None)
+    }
+
+    /// Runs the hooks configured for a stage of the run, see `models::hooks`
+    ///
+    /// - `step: Option<usize>` - Index in `report` of the migration the stage is about, if any
+    fn hook(&self, stage: Stage, conn: &dyn Driver, report: &RunReport, step: Option<usize>) -> Result<(), String> {
+        self.hooks.run(stage, conn, &self.hook_context(conn, report, step))
+    }
+
+    /// What the run described by `report` is doing, for hooks
+    fn hook_context(&self, conn: &dyn Driver, report: &RunReport, step: Option<usize>) -> HookContext {
+        let mut context = HookContext {
+            database: conn.database_name(),
+            driver: self.db.driver.name().to_owned(),
+            environment: self.db.environment.clone(),
+            current_version: report.initial_version,
+            target_version: report.target_version,
+            ..HookContext::default()
+        };
+
+        if let Some(step) = step.map(|idx| &report.steps[idx]) {
+            context.version = Some(step.version);
+            context.name = Some(step.name.clone());
+            context.action = Some(step.action.as_str());
+            context.duration_ms = step.duration.map(|d| d.as_millis());
+            context.error = step.error.clone();
         }
 
-        if let Some(database) = matches.value_of("database") {
-            obj.database = database.to_owned();
+        context
+    }
+
+    /// Performs a migration, recording its progress in the `schema_versions` table
+    ///
+    /// The migration's `up.sql` runs in the same transaction which marks the version successful and
+    /// records it in the history. If it fails the transaction is rolled back, the version is marked as
+    /// failed and the failure is recorded in the history. The outcome is recorded in `step`.
+    ///
+    /// SQL with a `migration::NO_TRANSACTION_HEADER` runs outside of a transaction and is never
+    /// retried, a failure leaves the statements before the failed one performed. MySQL commits DDL
+    /// as it runs, so MySQL migrations containing DDL are never retried either.
+    fn up(&self, conn: &dyn Driver, schema_ver: &DbSchemaVer, migration: &Migration, step: &mut StepReport)
+          -> Result<(), String> {
+        info!("Applying migration \"{}\" (version {})", migration.name, migration.version);
+
+        let transactional = migration::header(&migration.up, migration::NO_TRANSACTION_HEADER).is_none();
+        let retry = transactional && !conn.dialect().commits_implicitly(&migration.up);
+        let (statement_timeout, lock_timeout) = self.timeouts(migration, &migration.up).inspect_err(|err| {
+            step.status = StepStatus::Failed;
+            step.error = Some(err.clone());
+        })?;
+
+        let history = History::new(conn);
+
+        schema_ver.start(migration)?;
+
+        let (result, started) = self.retrying(conn, migration, &history, step, retry, |step, started| {
+            let result = in_transaction(conn, transactional, || {
+                conn.set_timeouts(statement_timeout, lock_timeout)?;
+                self.execute(conn, migration, &migration.up, step)?;
+                schema_ver.set_status(migration, VerStatus::Success, Some(started.elapsed()))?;
+                history.record(&HistoryEvent::for_migration(HistoryAction::Apply, migration).duration(started.elapsed()))
+            });
+            reset_timeouts(conn, statement_timeout, lock_timeout);
+            result
+        });
+        step.duration = Some(started.elapsed());
+        self.warn_if_slow("Migration", migration, started.elapsed());
+
+        if let Err(err) = result {
+            step.status = StepStatus::Failed;
+            step.error = Some(err.clone());
+            schema_ver.set_status(migration, VerStatus::Fail, Some(started.elapsed()))?;
+            let event = HistoryEvent::for_migration(HistoryAction::Fail, migration)
+                .duration(started.elapsed())
+                .error(&err);
+            history.record(&event)?;
+            logger::step(&conn.database_name(), HistoryAction::Apply, &event);
+            return Err(format!("Migration \"{}\" (version {}) failed: {}", migration.name, migration.version, err))
         }
 
-        Ok(obj)
+        step.status = StepStatus::Applied;
+        logger::step(&conn.database_name(), HistoryAction::Apply,
+                     &HistoryEvent::for_migration(HistoryAction::Apply, migration).duration(started.elapsed()));
+
+        Ok(())
     }
 
-    fn run(&self) -> Result<(), String> {
-        /*
-        let db_connr = Connection::connect(self.make_db_conn_str(), SslMode::None);
-        if let Err(err) = db_connr {
-            println!("Error connecting to database: {:?}", err);
-            return Err(err.to_string())
-        }
-
-        let db_conn = match db_connr {
-            Ok(conn) => conn,
-            Err(err) => {
-                error!("Error connecting to database: {}", err);
-                panic!("Error connecting to database");
+    /// Reverses a migration, removing its version from the `schema_versions` table
+    ///
+    /// The migration's `down.sql` runs in the same transaction which removes the version and records
+    /// the rollback in the history. If it fails nothing is changed and the failure is recorded in the
+    /// history. The outcome is recorded in `step`.
+    ///
+    /// SQL with a `migration::NO_TRANSACTION_HEADER` runs outside of a transaction and is never
+    /// retried, a failure leaves the statements before the failed one performed. Neither are MySQL
+    /// migrations containing DDL, see `up`.
+    fn down(&self, conn: &dyn Driver, schema_ver: &DbSchemaVer, migration: &Migration, step: &mut StepReport)
+            -> Result<(), String> {
+        let down = match migration.down {
+            Some(ref down) => down,
+            None => {
+                let err = format!("Migration \"{}\" (version {}) has no down.sql and can not be reversed",
+                                  migration.name, migration.version);
+                step.status = StepStatus::Failed;
+                step.error = Some(err.clone());
+                return Err(err)
+            }
+        };
+
+        info!("Reversing migration \"{}\" (version {})", migration.name, migration.version);
+
+        let transactional = migration::header(down, migration::NO_TRANSACTION_HEADER).is_none();
+        let retry = transactional && !conn.dialect().commits_implicitly(down);
+        let (statement_timeout, lock_timeout) = self.timeouts(migration, down).inspect_err(|err| {
+            step.status = StepStatus::Failed;
+            step.error = Some(err.clone());
+        })?;
+
+        let history = History::new(conn);
+
+        let (result, started) = self.retrying(conn, migration, &history, step, retry, |step, started| {
+            let result = in_transaction(conn, transactional, || {
+                conn.set_timeouts(statement_timeout, lock_timeout)?;
+                self.execute(conn, migration, down, step)?;
+                schema_ver.remove(migration.version)?;
+                history.record(&HistoryEvent::for_migration(HistoryAction::Rollback, migration).duration(started.elapsed()))
+            });
+            reset_timeouts(conn, statement_timeout, lock_timeout);
+            result
+        });
+        step.duration = Some(started.elapsed());
+        self.warn_if_slow("Reversing migration", migration, started.elapsed());
+
+        if let Err(err) = result {
+            step.status = StepStatus::Failed;
+            step.error = Some(err.clone());
+            let event = HistoryEvent::for_migration(HistoryAction::Fail, migration)
+                .duration(started.elapsed())
+                .error(&err);
+            history.record(&event)?;
+            logger::step(&conn.database_name(), HistoryAction::Rollback, &event);
+            return Err(format!("Reversing migration \"{}\" (version {}) failed: {}", migration.name, migration.version, err))
+        }
+
+        step.status = StepStatus::Reversed;
+        logger::step(&conn.database_name(), HistoryAction::Rollback,
+                     &HistoryEvent::for_migration(HistoryAction::Rollback, migration).duration(started.elapsed()));
+
+        Ok(())
+    }
+
+    /// Runs a migration's transaction, running it again when it fails with an error the database
+    /// reports as transient, ex. a deadlock, see `Driver::is_retryable`
+    ///
+    /// - `retry: bool` - Whether a failed attempt can run again, false for SQL which may have been
+    ///   partly performed, run outside of a transaction or containing DDL MySQL commits on its own
+    /// - `run: F` - Runs the transaction, given the step to record statements in and when the
+    ///   attempt started
+    /// - *returns*: Result of the last attempt and when it started
+    ///
+    /// Waits `--retry-delay` before the first retry and twice as long before each following one, up
+    /// to `--retries` times. Every failed attempt which is retried is recorded in the history.
+    fn retrying<F>(&self, conn: &dyn Driver, migration: &Migration, history: &History, step: &mut StepReport,
+                   retry: bool, mut run: F) -> (Result<(), String>, Instant)
+        where F: FnMut(&mut StepReport, Instant) -> Result<(), String>
+    {
+        let mut attempt = 1;
+        loop {
+            step.attempts = attempt;
+            step.statements.clear();
+
+            let started = Instant::now();
+            let err = match run(step, started) {
+                Err(ref err) if retry && attempt <= self.retries && conn.is_retryable(err) => err.clone(),
+                Err(err) if attempt > 1 => return (Err(format!("{} (gave up after {} attempts)", err, attempt)), started),
+                result => return (result, started),
+            };
+
+            let delay = self.retry_delay.saturating_mul(2u32.saturating_pow(attempt - 1));
+            warn!("Attempt {} at migration \"{}\" (version {}) failed, retrying in {}: {}", attempt, migration.name,
+                  migration.version, duration::format(delay), err);
+
+            let event = HistoryEvent::for_migration(HistoryAction::Fail, migration)
+                .duration(started.elapsed())
+                .error(&format!("{} (attempt {}, retried)", err, attempt));
+            if let Err(err) = history.record(&event) {
+                return (Err(err), started);
             }
+
+            thread::sleep(delay);
+            attempt += 1;
+        }
+    }
+
+    /// Executes a migration's up or down SQL a statement at a time, recording how long each took in
+    /// `step`
+    ///
+    /// Statements can only be timed separately when `drivers::split_statements` finds as many as
+    /// the parser does, otherwise the SQL runs as one batch and only the migration is timed.
+    fn execute(&self, conn: &dyn Driver, migration: &Migration, sql: &str, step: &mut StepReport) -> Result<(), String> {
+        let statements = drivers::split_statements(sql);
+        if self.db.driver.parse_sql(sql).map(|parsed| parsed.len()) != Ok(statements.len()) {
+            debug!("Running migration \"{}\" (version {}) as one batch, its statements can not be told apart",
+                   migration.name, migration.version);
+            return conn.batch_execute(sql)
+        }
+
+        for statement in statements {
+            let started = Instant::now();
+            conn.batch_execute(statement)?;
+            let took = started.elapsed();
+
+            debug!("Statement of migration \"{}\" (version {}) took {}: {}", migration.name, migration.version,
+                   duration::format(took), summarize(statement));
+            step.statements.push(StatementTiming {
+                sql: statement.to_owned(),
+                duration: took,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Timeouts a migration's up or down SQL runs with, as (statement timeout, lock timeout)
+    ///
+    /// Header comments at the top of the SQL (See `migration::STATEMENT_TIMEOUT_HEADER` and
+    /// `migration::LOCK_TIMEOUT_HEADER`) override the `--statement-timeout` and `--lock-timeout`
+    /// options. SQL run outside of a transaction gets neither, Postgres only sets them for one.
+    fn timeouts(&self, migration: &Migration, sql: &str) -> Result<(Option<Duration>, Option<Duration>), String> {
+        let read = |header: &str, default: Option<Duration>| match migration::header(sql, header) {
+            Some(value) => {
+                let option = format!("Migration \"{}\" (version {}) \"{}\" header", migration.name, migration.version, header);
+                duration::parse(&option, value).map(Some)
+            },
+            None => Ok(default)
         };
-        */
+
+        let timeouts = (read(migration::STATEMENT_TIMEOUT_HEADER, self.statement_timeout)?,
+                        read(migration::LOCK_TIMEOUT_HEADER, self.lock_timeout)?);
+        if let (None, None) = timeouts {
+            return Ok(timeouts);
+        }
+
+        if migration::header(sql, migration::NO_TRANSACTION_HEADER).is_some() {
+            warn!("Migration \"{}\" (version {}) runs outside of a transaction, ignoring its statement and lock timeouts",
+                  migration.name, migration.version);
+            return Ok((None, None));
+        }
+
+        debug!("Running migration \"{}\" (version {}) with statement timeout {} and lock timeout {}",
+               migration.name, migration.version,
+               timeouts.0.map_or("none".to_owned(), duration::format), timeouts.1.map_or("none".to_owned(), duration::format));
+        Ok(timeouts)
+    }
+
+    /// Warns if performing or reversing a migration took longer than the `--slow-threshold` option
+    fn warn_if_slow(&self, doing: &str, migration: &Migration, took: Duration) {
+        if took > self.slow_threshold {
+            warn!("{} \"{}\" (version {}) took {}, longer than the slow threshold of {}", doing, migration.name,
+                  migration.version, duration::format(took), duration::format(self.slow_threshold));
+        }
+    }
+
+    /// Checks migrations for destructive operations before any of them are performed
+    ///
+    /// - `migrations: &[&Migration]` - Migrations about to be performed
+    /// - *returns*: Error message listing the operations if a rule configured to deny flags any, see
+    ///   `models::rules`
+    ///
+    /// Migrations whose SQL can not be parsed are performed without being checked, the parser does
+    /// not understand every statement databases accept.
+    fn check_rules(&self, migrations: &[&Migration]) -> Result<(), String> {
+        let rules = RuleConfig::load(&self.migrations_dir)?;
+
+        let mut denied = Vec::new();
+        for migration in migrations {
+            let statements = match self.db.driver.parse_sql(&migration.up) {
+                Ok(statements) => statements,
+                Err(err) => {
+                    warn!("Could not check migration \"{}\" (version {}) for destructive operations: {}",
+                          migration.name, migration.version, err);
+                    continue;
+                }
+            };
+
+            for violation in rules.check(self.db.driver, &migration.up, &statements) {
+                match violation.severity {
+                    Severity::Deny => denied.push(format!("\"{}\" (version {}): {}", migration.name, migration.version, violation)),
+                    _ => warn!("Migration \"{}\" (version {}): {}", migration.name, migration.version, violation),
+                }
+            }
+        }
+
+        if !denied.is_empty() {
+            return Err(format!("Refusing to perform destructive operations, acknowledge them by adding \"{} <rule>\" to \
+                                the migration's up SQL: {}", rules::ALLOW_ANNOTATION, denied.join("; ")))
+        }
 
         Ok(())
     }
 }
+
+impl models::command::Command <RunCmd> for RunCmd {
+    fn from_matches(matches: &ArgMatches) -> Result<RunCmd, String> {
+        let mut obj = RunCmd::new(DbConfig::from_matches(matches)?);
+
+        if let Some(target) = matches.value_of("target") {
+            obj.target = Target::parse(target)?;
+        }
+
+        if let Some(migrations_dir) = matches.value_of("migrations-dir") {
+            obj.migrations_dir = migrations_dir.to_owned();
+        }
+
+        obj.allow_out_of_order = matches.is_present("allow-out-of-order");
+        obj.dry_run = matches.is_present("dry-run");
+        obj.output = OutputFormat::from_matches(matches);
+
+        if let Some(threshold) = matches.value_of("slow-threshold") {
+            obj.slow_threshold = duration::parse("--slow-threshold", threshold)?;
+        }
+
+        if let Some(timeout) = matches.value_of("statement-timeout") {
+            obj.statement_timeout = Some(duration::parse("--statement-timeout", timeout)?);
+        }
+
+        if let Some(timeout) = matches.value_of("lock-timeout") {
+            obj.lock_timeout = Some(duration::parse("--lock-timeout", timeout)?);
+        }
+
+        if let Some(retries) = matches.value_of("retries") {
+            obj.retries = match retries.parse::<u32>() {
+                Ok(retries) => retries,
+                Err(_) => return Err(format!("Number of retries must be an integer, found \"{}\"", retries))
+            };
+        }
+
+        if let Some(delay) = matches.value_of("retry-delay") {
+            obj.retry_delay = duration::parse("--retry-delay", delay)?;
+        }
+
+        obj.hooks = Hooks::load(&obj.migrations_dir)?;
+        obj.dump_schema = matches.value_of("dump-schema").map(|path| path.to_owned());
+
+        Ok(obj)
+    }
+
+    fn run(&self) -> Result<(), String> {
+        self.migrate_and_report(slice::from_ref(&self.target))
+    }
+}
+
+/// Version the database is at, `0` if no migration has been performed
+fn current_version(schema_ver: &DbSchemaVer) -> Result<i64, String> {
+    Ok(schema_ver.current_version()?.map_or(0, |entry| entry.version))
+}
+
+/// What the targets of a run reached so far did
+///
+/// A dry run changes nothing in the database, so runs towards several targets (ex. `redo`) track
+/// the versions earlier targets would have applied here instead of reading them back.
+struct RunState {
+    /// Highest version applied, `0` if none is
+    current: i64,
+    /// Every version applied successfully
+    applied: Vec<i64>,
+    /// If the `before_run` hook started, failures before then changed nothing to react to
+    started: bool
+}
+
+impl RunState {
+    /// Reads the versions applied to the database, before the run does anything
+    fn read(schema_ver: &DbSchemaVer) -> Result<RunState, String> {
+        Ok(RunState {
+            current: current_version(schema_ver)?,
+            applied: schema_ver.entries()?.iter()
+                .filter(|entry| entry.status == VerStatus::Success)
+                .map(|entry| entry.version)
+                .collect(),
+            started: false
+        })
+    }
+}
+
+/// Runs a migration's SQL and bookkeeping in a transaction, see `drivers::transaction`, or as they
+/// are when `transactional` is false
+fn in_transaction<F>(conn: &dyn Driver, transactional: bool, f: F) -> Result<(), String>
+    where F: FnOnce() -> Result<(), String>
+{
+    if transactional {
+        drivers::transaction(conn, f)
+    } else {
+        f()
+    }
+}
+
+/// Restores the database's timeouts once a transaction which set them has ended, see
+/// `Driver::reset_timeouts`
+///
+/// A failure is only logged, the outcome of the transaction is already decided.
+fn reset_timeouts(conn: &dyn Driver, statement_timeout: Option<Duration>, lock_timeout: Option<Duration>) {
+    if statement_timeout.is_none() && lock_timeout.is_none() {
+        return;
+    }
+
+    if let Err(err) = conn.reset_timeouts() {
+        warn!("Failed to reset statement and lock timeouts for {}, error: {}", conn.database_name(), err);
+    }
+}
+
+/// Logs a table of the migrations a run carried out, with how long each and its slowest statement
+/// took
+fn log_summary(report: &RunReport) {
+    let steps: Vec<&StepReport> = report.steps.iter().filter(|step| step.duration.is_some()).collect();
+    if steps.is_empty() {
+        return;
+    }
+
+    let header = ["VERSION", "NAME", "ACTION", "STATUS", "DURATION", "SLOWEST STATEMENT"];
+    let rows: Vec<Vec<String>> = steps.iter().map(|step| vec![
+        step.version.to_string(),
+        step.name.clone(),
+        step.action.as_str().to_owned(),
+        step.status.as_str().to_owned(),
+        step.duration.map(duration::format).unwrap_or_default(),
+        step.statements.iter().max_by_key(|statement| statement.duration)
+            .map(|statement| format!("{} {}", duration::format(statement.duration), summarize(&statement.sql)))
+            .unwrap_or_default(),
+    ]).collect();
+
+    info!("Summary of {} migrations:", steps.len());
+    for line in table::format(&header, &rows) {
+        info!("{}", line);
+    }
+}
+
+/// First line of a statement, shortened to fit in a log line
+fn summarize(statement: &str) -> String {
+    let line = statement.lines().next().unwrap_or_default().trim();
+    if line.chars().count() > 60 || statement.contains('\n') {
+        format!("{}...", line.chars().take(60).collect::<String>())
+    } else {
+        line.to_owned()
+    }
+}
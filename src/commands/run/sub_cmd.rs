@@ -1,55 +1,75 @@
-
 use clap::{Arg, App, SubCommand};
 
+use commands::db_args::db_args;
+use commands::output::output_arg;
+
 pub fn sub_cmd<'a>() -> App<'a, 'a> {
-    SubCommand::with_name("run")
+    run_args(SubCommand::with_name("run")
         .about("Run migrations")
         .arg(Arg::with_name("target")
-            .help("Database schema version that \"run\" command should attempt to reach")
+            .help("Database schema version that \"run\" command should attempt to reach: latest, a version, \
+                   +N/-N migrations from the current version, or a migration directory name")
             .short("t")
+            .long("target")
             .takes_value(true)
-            .required(true)
+            .allow_hyphen_values(true)
+            .default_value("latest")
         )
+    )
+}
+
+/// Adds the options `RunCmd` loads how to carry out migrations from, along with the connection
+/// options and `--output`, for sub-commands which run migrations
+pub fn run_args<'a>(cmd: App<'a, 'a>) -> App<'a, 'a> {
+    output_arg(db_args(cmd
         .arg(Arg::with_name("migrations-dir")
-            .help("The directory to look for migrations in.")
+            .help("The directory to look for migrations in")
             .short("m")
+            .long("migrations-dir")
             .takes_value(true)
             .default_value("migrations")
         )
-        .arg(Arg::with_name("environment")
-            .help("Environment which application is running in")
-            .short("e")
+        .arg(Arg::with_name("dry-run")
+            .help("Print the SQL of the migrations which would run, with placeholders filled in, without running them")
+            .short("n")
+            .long("dry-run")
+        )
+        .arg(Arg::with_name("dump-schema")
+            .help("Write the schema of the database to this file after a successful run, see the dump-schema sub-command")
+            .long("dump-schema")
             .takes_value(true)
-            .requires("config")
         )
-        .arg(Arg::with_name("config")
-            .help("Path to database access configuration file")
-            .short("c")
+        .arg(Arg::with_name("allow-out-of-order")
+            .help("Apply migrations older than the current version which have not been applied, instead of refusing to run")
+            .long("allow-out-of-order")
+        )
+        .arg(Arg::with_name("slow-threshold")
+            .help("Warn about migrations which take longer than this, ex. 500ms, 30s or 2m")
+            .long("slow-threshold")
             .takes_value(true)
-            .required_unless_all(&["host", "user", "password", "database"])
+            .default_value("10s")
         )
-        .arg(Arg::with_name("host")
-            .help("Database server host")
-            .short("h")
+        .arg(Arg::with_name("statement-timeout")
+            .help("Fail a migration if one of its statements runs longer than this, ex. 30s")
+            .long("statement-timeout")
             .takes_value(true)
-            .required_unless("config")
         )
-        .arg(Arg::with_name("user")
-            .help("Database server user")
-            .short("u")
+        .arg(Arg::with_name("lock-timeout")
+            .help("Fail a migration if one of its statements waits longer than this for a lock, ex. 5s")
+            .long("lock-timeout")
             .takes_value(true)
-            .required_unless("config")
         )
-        .arg(Arg::with_name("password")
-            .help("Database server password")
-            .short("p")
+        .arg(Arg::with_name("retries")
+            .help("Times to retry a migration which fails on a lock timeout, serialization failure or deadlock")
+            .long("retries")
             .takes_value(true)
-            .required_unless("config")
+            .default_value("3")
         )
-        .arg(Arg::with_name("database")
-            .help("Database to run migrations on")
-            .short("d")
+        .arg(Arg::with_name("retry-delay")
+            .help("Wait before retrying a migration, doubled for every following retry, ex. 500ms or 2s")
+            .long("retry-delay")
             .takes_value(true)
-            .required_unless("config")
+            .default_value("500ms")
         )
+    ))
 }
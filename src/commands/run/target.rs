@@ -0,0 +1,109 @@
+use std::fmt;
+
+use models::migration::Migration;
+
+/// Schema version the run sub-command should reach, see the `--target/-t` option
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    /// Version of the newest migration
+    Latest,
+    /// A specific version, `0` reverses every migration
+    Version(i64),
+    /// Number of migrations to perform (Positive) or reverse (Negative), starting from the current
+    /// version
+    Relative(i32),
+    /// Version of the migration with this directory name
+    Name(String),
+}
+
+impl Target {
+    /// Parses the value of the `--target/-t` option
+    ///
+    /// - `target: &str` - `latest`, a version, `+N` or `-N`, or a migration directory name
+    /// - *returns*: `Target` - Parsed target, error message if fail
+    pub fn parse(target: &str) -> Result<Target, String> {
+        if target == "latest" {
+            return Ok(Target::Latest);
+        }
+
+        if target.starts_with('+') || target.starts_with('-') {
+            return match target.parse::<i32>() {
+                Ok(steps) => Ok(Target::Relative(steps)),
+                Err(_) => Err(format!("Relative target must be + or - followed by a number of migrations, found \"{}\"", target))
+            };
+        }
+
+        if target.chars().all(|c| c.is_ascii_digit()) {
+            return match target.parse::<i64>() {
+                Ok(version) => Ok(Target::Version(version)),
+                Err(_) => Err(format!("Target version \"{}\" is too large", target))
+            };
+        }
+
+        Ok(Target::Name(target.to_owned()))
+    }
+
+    /// Determines the schema version the target refers to
+    ///
+    /// - `migrations: &[Migration]` - Loaded migrations, ordered by version
+    /// - `current: i64` - Current schema version, `0` if no migration has been performed
+    /// - `applied: &[i64]` - Versions applied successfully, relative targets only reverse these
+    /// - *returns*: `i64` - Target schema version, error message if fail
+    ///
+    /// # Errors
+    /// - No loaded migration provides the target version, or has the target name
+    /// - A relative target steps past the first or last loaded migration
+    pub fn resolve(&self, migrations: &[Migration], current: i64, applied: &[i64]) -> Result<i64, String> {
+        match *self {
+            // Never reverses migrations, even if the database is ahead of the migrations directory
+            Target::Latest => Ok(migrations.last().map_or(current, |m| m.version.max(current))),
+            Target::Version(version) => {
+                if version != 0 && !migrations.iter().any(|m| m.version == version) {
+                    return Err(format!("No migration provides version {}", version))
+                }
+
+                Ok(version)
+            },
+            Target::Name(ref name) => match migrations.iter().find(|m| m.name == *name) {
+                Some(migration) => Ok(migration.version),
+                None => Err(format!("No migration is named \"{}\"", name))
+            },
+            Target::Relative(steps) if steps >= 0 => {
+                if steps == 0 {
+                    return Ok(current);
+                }
+
+                let pending: Vec<&Migration> = migrations.iter().filter(|m| m.version > current).collect();
+                match pending.get(steps as usize - 1) {
+                    Some(migration) => Ok(migration.version),
+                    None => Err(format!("Can not perform {} migrations, only {} are pending", steps, pending.len()))
+                }
+            },
+            Target::Relative(steps) => {
+                let steps = steps.unsigned_abs() as usize;
+
+                // Applied migrations, newest first. Out of order migrations below the current version
+                // which were never applied are not counted, reversing skips them
+                let applied: Vec<&Migration> = migrations.iter().rev()
+                    .filter(|m| m.version <= current && applied.contains(&m.version))
+                    .collect();
+                if steps > applied.len() {
+                    return Err(format!("Can not reverse {} migrations, only {} are applied", steps, applied.len()))
+                }
+
+                Ok(applied.get(steps).map_or(0, |m| m.version))
+            },
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Target::Latest => write!(f, "latest"),
+            Target::Version(version) => write!(f, "{}", version),
+            Target::Relative(steps) => write!(f, "{:+}", steps),
+            Target::Name(ref name) => write!(f, "\"{}\"", name),
+        }
+    }
+}
@@ -0,0 +1,8 @@
+//! Seed sub-command
+
+/// The run method for the Command trait
+#[allow(clippy::module_inception)]
+pub mod seed;
+
+/// Command line options for the seed sub-command
+pub mod sub_cmd;
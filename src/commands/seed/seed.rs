@@ -0,0 +1,124 @@
+use std::time::Instant;
+
+use clap::ArgMatches;
+
+use commands::run::duration;
+use drivers;
+use models;
+use models::config::DbConfig;
+use models::dbschema::DbSchemaVer;
+use models::migration::Migration;
+use models::seed::{self, Seed, Seeds};
+
+#[derive(Debug)]
+pub struct SeedCmd {
+    db: DbConfig,
+    migrations_dir: String,
+    seeds_dir: String,
+    include_protected: bool
+}
+
+impl SeedCmd {
+    pub fn new(db: DbConfig) -> SeedCmd {
+        SeedCmd {
+            db,
+            migrations_dir: "migrations".to_owned(),
+            seeds_dir: "seeds".to_owned(),
+            include_protected: false
+        }
+    }
+
+    /// Environment whose seeds are loaded, see `--environment/-e`
+    fn environment(&self) -> &str {
+        self.db.environment.as_ref().map_or(seed::DEFAULT_ENVIRONMENT, |env| env.as_str())
+    }
+}
+
+impl models::command::Command <SeedCmd> for SeedCmd {
+    fn from_matches(matches: &ArgMatches) -> Result<SeedCmd, String> {
+        let mut obj = SeedCmd::new(DbConfig::from_matches(matches)?);
+
+        if let Some(migrations_dir) = matches.value_of("migrations-dir") {
+            obj.migrations_dir = migrations_dir.to_owned();
+        }
+
+        if let Some(seeds_dir) = matches.value_of("seeds-dir") {
+            obj.seeds_dir = seeds_dir.to_owned();
+        }
+
+        obj.include_protected = matches.is_present("include-protected");
+
+        Ok(obj)
+    }
+
+    /// Loads the seeds of the environment which have not been loaded yet, in order of name
+    ///
+    /// Seeds are written against the latest schema, the database has to be at the latest version.
+    /// Each seed is loaded in its own transaction together with the row recording it in the
+    /// `schema_seeds` table. Seeds which changed since they were loaded are not loaded again.
+    /// Protected environments are skipped unless `--include-protected` is given, loading their
+    /// seeds then has to be confirmed.
+    fn run(&self) -> Result<(), String> {
+        let environment = self.environment();
+        if self.db.protected && !self.include_protected {
+            warn!("Not loading seeds in the protected environment \"{}\", run with --include-protected to load them",
+                  environment);
+            return Ok(());
+        }
+
+        let seeds = Seed::load_dir(&self.seeds_dir, environment, self.db.driver)?;
+        let migrations = Migration::load_dir(&self.migrations_dir, self.db.driver)?;
+
+        let db_conn = self.db.connect()?;
+        let schema_ver = DbSchemaVer::bootstrap(db_conn.as_ref())?;
+
+        let current = match schema_ver.current_version()? {
+            Some(entry) => entry.version,
+            None => 0
+        };
+        let latest = migrations.last().map_or(0, |m| m.version);
+        if current < latest {
+            return Err(format!("{} is at version {}, run migrations up to version {} before loading seeds",
+                               db_conn.database_name(), current, latest))
+        }
+
+        let tracker = Seeds::new(db_conn.as_ref());
+        let loaded = tracker.loaded(environment)?;
+
+        let mut pending = Vec::new();
+        for seed in &seeds {
+            match loaded.get(&seed.name) {
+                Some(hash) if *hash != seed.hash => warn!("Seed \"{}\" has changed since it was loaded, not loading it again",
+                                                          seed.name),
+                Some(_) => {},
+                None => pending.push(seed),
+            }
+        }
+
+        if pending.is_empty() {
+            info!("No seeds to load into {} for environment \"{}\"", db_conn.database_name(), environment);
+            return Ok(());
+        }
+
+        self.db.confirm_protected(&format!("load {} seeds", pending.len()))?;
+
+        for seed in &pending {
+            info!("Loading seed \"{}\"", seed.name);
+
+            let started = Instant::now();
+            let result = drivers::transaction(db_conn.as_ref(), || {
+                db_conn.lock()?;
+                db_conn.batch_execute(&seed.sql)?;
+                tracker.record(seed, environment, started.elapsed())
+            });
+
+            if let Err(err) = result {
+                return Err(format!("Seed \"{}\" failed: {}", seed.name, err))
+            }
+
+            info!("Loaded seed \"{}\" in {}", seed.name, duration::format(started.elapsed()));
+        }
+
+        Ok(())
+    }
+}
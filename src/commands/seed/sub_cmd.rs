@@ -0,0 +1,25 @@
+use clap::{Arg, App, SubCommand};
+
+use commands::db_args::db_args;
+
+pub fn sub_cmd<'a>() -> App<'a, 'a> {
+    db_args(SubCommand::with_name("seed")
+        .about("Load the seeds of the environment which have not been loaded into the database yet")
+        .arg(Arg::with_name("migrations-dir")
+            .help("The directory to look for migrations in, the database must be at the latest version.")
+            .short("m")
+            .takes_value(true)
+            .default_value("migrations")
+        )
+        .arg(Arg::with_name("seeds-dir")
+            .help("The directory to look for seeds in, they are loaded from its subdirectory for the environment.")
+            .long("seeds-dir")
+            .takes_value(true)
+            .default_value("seeds")
+        )
+        .arg(Arg::with_name("include-protected")
+            .help("Load seeds in protected environments too, after confirmation. They are skipped otherwise")
+            .long("include-protected")
+        )
+    )
+}
@@ -0,0 +1,8 @@
+//! Status sub-command
+
+/// The run method for the Command trait
+#[allow(clippy::module_inception)]
+pub mod status;
+
+/// Command line options for the status sub-command
+pub mod sub_cmd;
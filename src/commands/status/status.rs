@@ -0,0 +1,149 @@
+use clap::ArgMatches;
+use serde_json::Value;
+
+use commands::output::OutputFormat;
+use commands::table;
+use models;
+use models::config::DbConfig;
+use models::dbschema::{DbSchemaVer, VerEntry, VerStatus};
+use models::migration::Migration;
+
+/// State of a version, known from a migration, the `schema_versions` table or both
+struct VersionStatus<'a> {
+    version: i64,
+    name: String,
+    /// applied, failed, ongoing, pending, out-of-order or missing
+    status: &'static str,
+    entry: Option<&'a VerEntry>,
+    /// The migration changed since it was applied
+    changed: bool,
+}
+
+#[derive(Debug)]
+pub struct StatusCmd {
+    db: DbConfig,
+    migrations_dir: String,
+    output: OutputFormat
+}
+
+impl StatusCmd {
+    pub fn new(db: DbConfig) -> StatusCmd {
+        StatusCmd {
+            db,
+            migrations_dir: "migrations".to_owned(),
+            output: OutputFormat::Human
+        }
+    }
+}
+
+impl models::command::Command <StatusCmd> for StatusCmd {
+    fn from_matches(matches: &ArgMatches) -> Result<StatusCmd, String> {
+        let mut obj = StatusCmd::new(DbConfig::from_matches(matches)?);
+
+        if let Some(migrations_dir) = matches.value_of("migrations-dir") {
+            obj.migrations_dir = migrations_dir.to_owned();
+        }
+
+        obj.output = OutputFormat::from_matches(matches);
+
+        Ok(obj)
+    }
+
+    fn run(&self) -> Result<(), String> {
+        let migrations = Migration::load_dir(&self.migrations_dir, self.db.driver)?;
+
+        let db_conn = self.db.connect()?;
+        let schema_ver = DbSchemaVer::bootstrap(db_conn.as_ref())?;
+
+        let current = match schema_ver.current_version()? {
+            Some(entry) => entry.version,
+            None => 0
+        };
+        let entries = schema_ver.entries()?;
+        let statuses = statuses(&migrations, &entries, current);
+
+        match self.output {
+            OutputFormat::Human => {
+                println!("{} is at version {}", db_conn.database_name(), current);
+                print_table(&statuses);
+            },
+            OutputFormat::Json => println!("{}", json!({
+                "database": db_conn.database_name(),
+                "current_version": current,
+                "latest_version": migrations.last().map_or(0, |m| m.version),
+                "pending": statuses.iter().filter(|s| s.status == "pending" || s.status == "out-of-order").count(),
+                "migrations": statuses.iter().map(to_json).collect::<Vec<Value>>(),
+            })),
+        }
+
+        Ok(())
+    }
+}
+
+/// Works out the state of every version in the migrations directory or the `schema_versions` table,
+/// ordered by version
+fn statuses<'a>(migrations: &[Migration], entries: &'a [VerEntry], current: i64) -> Vec<VersionStatus<'a>> {
+    let mut statuses: Vec<VersionStatus> = migrations.iter().map(|migration| {
+        let entry = entries.iter().rev().find(|entry| entry.version == migration.version);
+        let status = match entry.map(|entry| entry.status) {
+            Some(VerStatus::Success) => "applied",
+            Some(VerStatus::Fail) => "failed",
+            Some(VerStatus::Ongoing) => "ongoing",
+            None if migration.version <= current => "out-of-order",
+            None => "pending",
+        };
+
+        VersionStatus {
+            version: migration.version,
+            name: migration.name.clone(),
+            status,
+            entry,
+            changed: entry.is_some_and(|entry| entry.status == VerStatus::Success
+                                             && entry.migration_hash != migration.hash),
+        }
+    }).collect();
+
+    // Versions recorded in the database which no migration provides anymore
+    for entry in entries {
+        if !statuses.iter().any(|s| s.version == entry.version) {
+            statuses.push(VersionStatus {
+                version: entry.version,
+                name: entry.name.clone().unwrap_or_default(),
+                status: "missing",
+                entry: Some(entry),
+                changed: false,
+            });
+        }
+    }
+
+    statuses.sort_by_key(|s| s.version);
+    statuses
+}
+
+/// Serializes the state of a version for `--output json`
+fn to_json(status: &VersionStatus) -> Value {
+    json!({
+        "version": status.version,
+        "name": status.name,
+        "status": status.status,
+        "updated": status.entry.map(|entry| entry.updated.to_rfc3339()),
+        "duration_ms": status.entry.and_then(|entry| entry.duration_ms),
+        "changed": status.changed,
+    })
+}
+
+/// Prints the state of every version as a table on stdout
+fn print_table(statuses: &[VersionStatus]) {
+    let header = ["VERSION", "NAME", "STATUS", "UPDATED (UTC)", "DURATION", "CHANGED"];
+
+    let rows: Vec<Vec<String>> = statuses.iter().map(|status| vec![
+        status.version.to_string(),
+        status.name.clone(),
+        status.status.to_owned(),
+        status.entry.map(|entry| entry.updated.format("%Y-%m-%d %H:%M:%S").to_string()).unwrap_or_default(),
+        status.entry.and_then(|entry| entry.duration_ms).map(|ms| format!("{}ms", ms)).unwrap_or_default(),
+        if status.changed { "yes".to_owned() } else { String::new() },
+    ]).collect();
+
+    table::print(&header, &rows);
+}
@@ -0,0 +1,16 @@
+use clap::{Arg, App, SubCommand};
+
+use commands::db_args::db_args;
+use commands::output::output_arg;
+
+pub fn sub_cmd<'a>() -> App<'a, 'a> {
+    output_arg(db_args(SubCommand::with_name("status")
+        .about("Show the current version and which migrations have been applied")
+        .arg(Arg::with_name("migrations-dir")
+            .help("The directory to look for migrations in.")
+            .short("m")
+            .takes_value(true)
+            .default_value("migrations")
+        )
+    ))
+}
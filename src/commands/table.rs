@@ -0,0 +1,37 @@
+//! Tables sub-commands print on stdout
+
+/// Prints rows as a table on stdout, see `format`
+pub fn print(header: &[&str], rows: &[Vec<String>]) {
+    for line in format(header, rows) {
+        println!("{}", line);
+    }
+}
+
+/// Lays rows out as a table, each column as wide as its widest cell
+///
+/// - `header: &[&str]` - Column names
+/// - `rows: &[Vec<String>]` - Cells of each row, one per column
+/// - *returns*: `Vec<String>` - Lines of the table, starting with the header
+pub fn format(header: &[&str], rows: &[Vec<String>]) -> Vec<String> {
+    let mut widths: Vec<usize> = header.iter().map(|h| h.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let format_row = |cells: Vec<&str>| -> String {
+        cells.iter().zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<String>>()
+            .join("  ")
+            .trim_end()
+            .to_owned()
+    };
+
+    let mut lines = vec![format_row(header.to_vec())];
+    for row in rows {
+        lines.push(format_row(row.iter().map(|cell| cell.as_str()).collect()));
+    }
+    lines
+}
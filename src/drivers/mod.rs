@@ -0,0 +1,488 @@
+//! Database drivers Migrate can run migrations with
+//!
+//! Every driver implements the `Driver` trait. The bookkeeping models (See `models::dbschema` and
+//! `models::history`) and the sub-commands only talk to the database through it, so the same
+//! migrations directory format works for every database.
+//!
+//! Queries use `$1`, `$2`, ... placeholders. Drivers for databases which use a different placeholder
+//! syntax translate them.
+
+use std::time::Duration;
+
+use chrono::naive::datetime::NaiveDateTime;
+use sqlparser::ast::Statement;
+use sqlparser::dialect::{MySqlDialect, PostgreSqlDialect, SQLiteDialect};
+use sqlparser::parser::Parser;
+
+use self::schema::Schema;
+
+pub mod mysql;
+pub mod postgres;
+pub mod schema;
+pub mod sqlite;
+
+/// Format timestamps are stored in by databases without a timestamp type
+pub const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.6f";
+
+/// SQL dialect a driver speaks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dialect {
+    Postgres,
+    Sqlite,
+    Mysql,
+}
+
+impl Dialect {
+    /// Parses a driver name, as used by the `--driver` option
+    pub fn from_name(name: &str) -> Option<Dialect> {
+        match name.to_lowercase().as_str() {
+            "postgres" | "postgresql" => Some(Dialect::Postgres),
+            "sqlite" | "sqlite3" => Some(Dialect::Sqlite),
+            "mysql" | "mariadb" => Some(Dialect::Mysql),
+            _ => None
+        }
+    }
+
+    /// Determines the driver a database URL is for from its scheme
+    pub fn from_url(url: &str) -> Option<Dialect> {
+        match url.find("://") {
+            Some(idx) => Dialect::from_name(&url[..idx]),
+            None => None
+        }
+    }
+
+    /// Name of the driver
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Dialect::Postgres => "postgres",
+            Dialect::Sqlite => "sqlite",
+            Dialect::Mysql => "mysql",
+        }
+    }
+
+    /// Quotes an identifier, ex. a database name, so it can be used in SQL
+    pub fn quote_ident(&self, ident: &str) -> String {
+        match *self {
+            Dialect::Mysql => format!("`{}`", ident.replace('`', "``")),
+            Dialect::Postgres | Dialect::Sqlite => format!("\"{}\"", ident.replace('"', "\"\"")),
+        }
+    }
+
+    /// Parses SQL written for the dialect, without connecting to a database
+    ///
+    /// - `sql: &str` - One or more statements
+    /// - *returns*: `Vec<Statement>` - Parsed statements, parser error message if fail
+    pub fn parse_sql(&self, sql: &str) -> Result<Vec<Statement>, String> {
+        let result = match *self {
+            Dialect::Postgres => Parser::parse_sql(&PostgreSqlDialect {}, sql),
+            Dialect::Sqlite => Parser::parse_sql(&SQLiteDialect {}, sql),
+            Dialect::Mysql => Parser::parse_sql(&MySqlDialect {}, sql),
+        };
+
+        result.map_err(|err| err.to_string())
+    }
+
+    /// Whether running SQL inside a transaction can commit part of it, because the dialect commits
+    /// the transaction around DDL statements, as MySQL does
+    ///
+    /// SQL which does not parse is assumed to contain DDL.
+    pub fn commits_implicitly(&self, sql: &str) -> bool {
+        if *self != Dialect::Mysql {
+            return false
+        }
+
+        match self.parse_sql(sql) {
+            Ok(statements) => statements.iter().any(|statement| !matches!(*statement,
+                Statement::Query(_) | Statement::Insert(_) | Statement::Update(_) | Statement::Delete(_)
+                    | Statement::Set(_))),
+            Err(_) => true,
+        }
+    }
+}
+
+/// A value passed to or read from a database
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Int(i64),
+    Text(String),
+    Timestamp(NaiveDateTime),
+}
+
+/// Conversion from a `Value` read from a database
+pub trait FromValue: Sized {
+    /// Converts the value, error message if it is not of the right type
+    fn from_value(value: &Value) -> Result<Self, String>;
+}
+
+impl FromValue for i64 {
+    fn from_value(value: &Value) -> Result<i64, String> {
+        match *value {
+            Value::Int(i) => Ok(i),
+            ref other => Err(format!("expected an integer, found {:?}", other))
+        }
+    }
+}
+
+impl FromValue for i32 {
+    fn from_value(value: &Value) -> Result<i32, String> {
+        let i = i64::from_value(value)?;
+        if i < i64::from(i32::MIN) || i > i64::from(i32::MAX) {
+            return Err(format!("integer {} is out of range", i))
+        }
+
+        Ok(i as i32)
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> Result<String, String> {
+        match *value {
+            Value::Text(ref s) => Ok(s.clone()),
+            ref other => Err(format!("expected text, found {:?}", other))
+        }
+    }
+}
+
+impl FromValue for NaiveDateTime {
+    fn from_value(value: &Value) -> Result<NaiveDateTime, String> {
+        match *value {
+            Value::Timestamp(ts) => Ok(ts),
+            Value::Text(ref s) => NaiveDateTime::parse_from_str(s, TIMESTAMP_FORMAT)
+                .map_err(|err| format!("failed to parse timestamp \"{}\": {}", s, err)),
+            ref other => Err(format!("expected a timestamp, found {:?}", other))
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: &Value) -> Result<Option<T>, String> {
+        match *value {
+            Value::Null => Ok(None),
+            ref other => T::from_value(other).map(Some)
+        }
+    }
+}
+
+impl From<i32> for Value {
+    fn from(i: i32) -> Value {
+        Value::Int(i64::from(i))
+    }
+}
+
+impl From<i64> for Value {
+    fn from(i: i64) -> Value {
+        Value::Int(i)
+    }
+}
+
+impl<'a> From<&'a str> for Value {
+    fn from(s: &'a str) -> Value {
+        Value::Text(s.to_owned())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Value {
+        Value::Text(s)
+    }
+}
+
+impl From<NaiveDateTime> for Value {
+    fn from(ts: NaiveDateTime) -> Value {
+        Value::Timestamp(ts)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(opt: Option<T>) -> Value {
+        match opt {
+            Some(v) => v.into(),
+            None => Value::Null
+        }
+    }
+}
+
+/// A row returned by a query
+#[derive(Debug)]
+pub struct Row {
+    columns: Vec<String>,
+    values: Vec<Value>
+}
+
+impl Row {
+    pub fn new(columns: Vec<String>, values: Vec<Value>) -> Row {
+        Row {
+            columns,
+            values
+        }
+    }
+
+    /// Names of the row's columns
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// Reads a column
+    ///
+    /// - `col: &str` - Name of column to read
+    /// - *returns*: `T` - Value of the column, error message if the column does not exist or its
+    ///   value is not a `T`
+    pub fn get<T: FromValue>(&self, col: &str) -> Result<T, String> {
+        match self.columns.iter().position(|c| c == col) {
+            Some(idx) => T::from_value(&self.values[idx]).map_err(|err| format!("column {}: {}", col, err)),
+            None => Err(format!("column {} missing", col))
+        }
+    }
+
+    /// Reads a column by position, see `get(&self, col: &str)`
+    pub fn get_idx<T: FromValue>(&self, idx: usize) -> Result<T, String> {
+        match self.values.get(idx) {
+            Some(value) => T::from_value(value).map_err(|err| format!("column {}: {}", idx, err)),
+            None => Err(format!("column {} missing", idx))
+        }
+    }
+}
+
+/// Operations Migrate needs from a database
+///
+/// Errors are returned as messages describing what went wrong.
+pub trait Driver {
+    /// SQL dialect the database speaks
+    fn dialect(&self) -> Dialect;
+
+    /// Name of the database connected to
+    fn database_name(&self) -> String;
+
+    /// Executes a single statement
+    ///
+    /// - `sql: &str` - Statement, with `$1`, `$2`, ... placeholders
+    /// - `params: &[Value]` - Values of the placeholders
+    /// - *returns*: `u64` - Number of rows modified
+    fn execute(&self, sql: &str, params: &[Value]) -> Result<u64, String>;
+
+    /// Executes any number of statements separated by semicolons, without parameters
+    fn batch_execute(&self, sql: &str) -> Result<(), String>;
+
+    /// Executes a query
+    ///
+    /// - `sql: &str` - Query, with `$1`, `$2`, ... placeholders
+    /// - `params: &[Value]` - Values of the placeholders
+    /// - *returns*: `Vec<Row>` - Rows returned by the query
+    fn query(&self, sql: &str, params: &[Value]) -> Result<Vec<Row>, String>;
+
+    /// Begins a transaction
+    fn begin(&self) -> Result<(), String>;
+
+    /// Commits the current transaction
+    fn commit(&self) -> Result<(), String>;
+
+    /// Rolls back the current transaction
+    fn rollback(&self) -> Result<(), String>;
+
+    /// Takes the lock which serializes changes to the bookkeeping tables
+    ///
+    /// Must be called inside a transaction, the lock is released when the transaction ends.
+    fn lock(&self) -> Result<(), String>;
+
+    /// Whether an error raised by a statement is transient, ex. a deadlock, so the transaction which
+    /// raised it can succeed if run again
+    fn is_retryable(&self, err: &str) -> bool;
+
+    /// Limits how long statements run and wait for locks for the rest of the current transaction, or
+    /// until `reset_timeouts(&self)` for drivers which can not limit them to a transaction
+    ///
+    /// - `statement_timeout: Option<Duration>` - Longest a statement may run, None for the
+    ///   database's default
+    /// - `lock_timeout: Option<Duration>` - Longest a statement may wait for a lock, None for the
+    ///   database's default
+    ///
+    /// Must be called inside a transaction. A statement which runs out of time fails.
+    fn set_timeouts(&self, statement_timeout: Option<Duration>, lock_timeout: Option<Duration>) -> Result<(), String>;
+
+    /// Restores the timeouts changed by `set_timeouts` to the database's defaults, for drivers
+    /// which can not limit them to the transaction
+    ///
+    /// Called once the transaction which set them has ended, whether it succeeded or not.
+    fn reset_timeouts(&self) -> Result<(), String>;
+
+    /// Creates the `schema_versions` and `schema_versions_lib` tables at the bootstrap layout, along
+    /// with anything they depend on, if they do not exist
+    ///
+    /// Called inside the bootstrap transaction while holding the lock, see `DbSchemaVer::bootstrap`.
+    ///
+    /// # Errors
+    /// - `type_create_fail` - Failed to create a type the tables use
+    /// - `incompatible_type` - An existing type the tables use is missing values Migrate uses
+    /// - `table_create_fail` - Failed to create the `schema_versions` table
+    /// - `lib_table_create_fail` - Failed to create the `schema_versions_lib` table
+    fn create_bookkeeping_tables(&self) -> Result<(), String>;
+
+    /// Columns of a table, as (column name, type name), empty if the table does not exist
+    ///
+    /// Type names are those the database reports, lowercased.
+    fn table_columns(&self, table: &str) -> Result<Vec<(String, String)>, String>;
+
+    /// Reads the types, sequences, tables, views, indexes, triggers and functions of the database (Or
+    /// of the current schema on Postgres) from its catalog, bookkeeping tables included
+    ///
+    /// - *returns*: `Schema` - Objects ordered by table and name, error message if fail
+    fn schema(&self) -> Result<Schema, String>;
+}
+
+/// Rewrites `$1`, `$2`, ... placeholders for databases which use a different syntax
+///
+/// - `sql: &str` - Query with `$N` placeholders
+/// - `replace: F` - Called with the number of each placeholder, in the order they appear, returns
+///   the text to replace it with
+/// - *returns*: `String` - Rewritten query
+///
+/// Dollar signs inside string literals and quoted identifiers are left alone.
+pub fn rewrite_placeholders<F>(sql: &str, mut replace: F) -> String
+    where F: FnMut(usize) -> String
+{
+    let mut out = String::with_capacity(sql.len());
+    let mut quote: Option<char> = None;
+    let mut chars = sql.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {},
+            None if c == '\'' || c == '"' || c == '`' => quote = Some(c),
+            None if c == '$' && chars.peek().is_some_and(|next| next.is_ascii_digit()) => {
+                let mut num = String::new();
+                while let Some(&digit) = chars.peek() {
+                    if !digit.is_ascii_digit() {
+                        break;
+                    }
+                    num.push(digit);
+                    chars.next();
+                }
+
+                // Only digits were collected, so this can not fail unless the number is absurdly long
+                out.push_str(&replace(num.parse().unwrap_or(0)));
+                continue;
+            },
+            None => {},
+        }
+        out.push(c);
+    }
+
+    out
+}
+
+/// Splits SQL into statements at semicolons
+///
+/// - `sql: &str` - Any number of statements separated by semicolons
+/// - *returns*: `Vec<&str>` - Statements, trimmed and without their semicolon. Pieces holding
+///   nothing but comments are left out
+///
+/// Semicolons inside string literals, quoted identifiers, comments and Postgres dollar quoted
+/// strings are left alone. Semicolons inside a statement's body, ex. a SQLite trigger's, are not
+/// told apart, callers compare the number of statements with what `Dialect::parse_sql` finds.
+pub fn split_statements(sql: &str) -> Vec<&str> {
+    let mut statements = Vec::new();
+    let mut start = 0;
+    let mut has_code = false;
+    let mut quote: Option<char> = None;
+    let mut dollar_tag: Option<&str> = None;
+    let mut chars = sql.char_indices().peekable();
+
+    while let Some((idx, c)) = chars.next() {
+        if let Some(tag) = dollar_tag {
+            if sql[idx..].starts_with(tag) {
+                // Skip the rest of the closing tag
+                for _ in 1..tag.len() {
+                    chars.next();
+                }
+                dollar_tag = None;
+            }
+            continue;
+        }
+
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {},
+            None if c == '\'' || c == '"' || c == '`' => {
+                quote = Some(c);
+                if !has_code {
+                    start = idx;
+                    has_code = true;
+                }
+            },
+            None if c == '-' && sql[idx..].starts_with("--") => {
+                while chars.peek().is_some_and(|&(_, next)| next != '\n') {
+                    chars.next();
+                }
+            },
+            None if c == '/' && sql[idx..].starts_with("/*") => {
+                chars.next();
+                while let Some((idx, _)) = chars.next() {
+                    if sql[idx..].starts_with("*/") {
+                        chars.next();
+                        break;
+                    }
+                }
+            },
+            None if c == '$' => {
+                // $tag$ or $$ opens a dollar quoted string, $1 is a placeholder
+                let rest = &sql[idx + 1..];
+                let tag_len = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+                if rest[tag_len..].starts_with('$') && !rest.starts_with(|c: char| c.is_ascii_digit()) {
+                    let tag = &sql[idx..idx + tag_len + 2];
+                    for _ in 1..tag.len() {
+                        chars.next();
+                    }
+                    dollar_tag = Some(tag);
+                }
+                if !has_code {
+                    start = idx;
+                    has_code = true;
+                }
+            },
+            None if c == ';' => {
+                if has_code {
+                    statements.push(sql[start..idx].trim());
+                }
+                has_code = false;
+            },
+            None if !c.is_whitespace() && !has_code => {
+                // Comments before a statement are left out of it
+                start = idx;
+                has_code = true;
+            },
+            None => {},
+        }
+    }
+
+    if has_code {
+        statements.push(sql[start..].trim());
+    }
+
+    statements
+}
+
+/// Runs a function inside a transaction
+///
+/// - `driver: &Driver` - Database to run the transaction on
+/// - `f: F` - Function to run, its result decides whether the transaction commits or rolls back
+/// - *returns*: `T` - Result of the function, error message if it or the transaction failed
+pub fn transaction<T, F>(driver: &dyn Driver, f: F) -> Result<T, String>
+    where F: FnOnce() -> Result<T, String>
+{
+    driver.begin()?;
+
+    match f() {
+        Ok(result) => {
+            driver.commit()?;
+            Ok(result)
+        },
+        Err(err) => {
+            if let Err(rollback_err) = driver.rollback() {
+                error!("Failed to roll back transaction, error: {}", rollback_err);
+            }
+            Err(err)
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! Description of the objects a database holds, read from its catalog by `Driver::schema`
+//!
+//! Every list is ordered by table and name, so the same schema is always described the same way.
+
+/// Types, sequences, tables, views, indexes, triggers and functions of a database
+#[derive(Debug, Default)]
+pub struct Schema {
+    /// Enum types and domains, Postgres only
+    pub types: Vec<SchemaObject>,
+    /// Sequences, Postgres only. Those of `SERIAL` columns belong to the column's table
+    pub sequences: Vec<SchemaObject>,
+    pub tables: Vec<Table>,
+    pub views: Vec<SchemaObject>,
+    /// Indexes which do not back a constraint
+    pub indexes: Vec<SchemaObject>,
+    pub triggers: Vec<SchemaObject>,
+    /// Functions and procedures, Postgres and MySQL only
+    pub functions: Vec<SchemaObject>,
+}
+
+impl Schema {
+    /// Adds a column to a table, adding the table after the others if it is not the last one
+    ///
+    /// Columns have to be added ordered by table.
+    pub fn add_column(&mut self, table: &str, column: Column) {
+        if self.tables.last().is_none_or(|last| last.name != table) {
+            self.tables.push(Table {
+                name: table.to_owned(),
+                columns: Vec::new(),
+                constraints: Vec::new(),
+            });
+        }
+
+        if let Some(last) = self.tables.last_mut() {
+            last.columns.push(column);
+        }
+    }
+
+    /// Adds a constraint to a table, ignored if the table is not known
+    pub fn add_constraint(&mut self, table: &str, constraint: Constraint) {
+        if let Some(table) = self.tables.iter_mut().find(|t| t.name == table) {
+            table.constraints.push(constraint);
+        }
+    }
+}
+
+/// A table with its columns and constraints
+#[derive(Debug)]
+pub struct Table {
+    pub name: String,
+    /// Columns in the order they were defined
+    pub columns: Vec<Column>,
+    pub constraints: Vec<Constraint>,
+}
+
+/// A column of a table
+#[derive(Debug)]
+pub struct Column {
+    pub name: String,
+    /// Type as the database reports it, ex. `character varying(20)`
+    pub data_type: String,
+    pub not_null: bool,
+    /// Default expression, as the database reports it
+    pub default: Option<String>,
+}
+
+/// A primary key, unique, foreign key, check or exclusion constraint of a table
+#[derive(Debug)]
+pub struct Constraint {
+    /// Name, generated from the table and columns where the database does not name constraints
+    pub name: String,
+    /// Definition, ex. `PRIMARY KEY (id)`
+    pub definition: String,
+}
+
+/// An object defined by a single statement, ex. `CREATE INDEX ...`
+#[derive(Debug)]
+pub struct SchemaObject {
+    /// Table the object belongs to, None for types, sequences without an owner, views and functions
+    pub table: Option<String>,
+    pub name: String,
+    /// Statement which creates the object, as the database reports it
+    pub definition: String,
+}
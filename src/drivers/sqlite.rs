@@ -0,0 +1,298 @@
+//! SQLite driver
+use std::time::Duration;
+
+use rusqlite;
+use rusqlite::types::{ToSqlOutput, ValueRef};
+
+use drivers::{self, Dialect, Driver, Row, Value, TIMESTAMP_FORMAT};
+use drivers::schema::{Column, Constraint, Schema, SchemaObject};
+
+/// Creates the `schema_versions` table at the bootstrap layout
+const VER_TABLE_DDL: &str = "CREATE TABLE IF NOT EXISTS schema_versions (
+                                 id INTEGER PRIMARY KEY NOT NULL,
+                                 updated TIMESTAMP NOT NULL,
+                                 version INTEGER NOT NULL,
+                                 migration_hash TEXT NOT NULL,
+                                 status TEXT NOT NULL CHECK (status IN ('ongoing', 'success', 'fail')),
+                                 lib_ver INTEGER NOT NULL)";
+
+/// Creates the `schema_versions_lib` table
+const LIB_TABLE_DDL: &str = "CREATE TABLE IF NOT EXISTS schema_versions_lib (
+                                 lib_ver INTEGER PRIMARY KEY NOT NULL,
+                                 applied TIMESTAMP NOT NULL,
+                                 tool_version TEXT NOT NULL)";
+
+/// Columns of the tables, `pk` is the position of the column in the primary key, 0 if not part of it
+const SCHEMA_COLUMNS_SQL: &str = "SELECT m.name AS table_name, c.name AS column_name, c.type AS data_type,
+                                      c.\"notnull\" AS not_null, c.dflt_value AS column_default, c.pk AS pk
+                                  FROM sqlite_master m, pragma_table_info(m.name) c
+                                  WHERE m.type = 'table' AND m.name NOT LIKE 'sqlite_%'
+                                  ORDER BY m.name, c.cid";
+
+/// Foreign keys of the tables, one row per column
+const SCHEMA_FOREIGN_KEYS_SQL: &str = "SELECT m.name AS table_name, f.id AS id, f.\"from\" AS from_column,
+                                           f.\"table\" AS to_table, f.\"to\" AS to_column,
+                                           f.on_update AS on_update, f.on_delete AS on_delete
+                                       FROM sqlite_master m, pragma_foreign_key_list(m.name) f
+                                       WHERE m.type = 'table' AND m.name NOT LIKE 'sqlite_%'
+                                       ORDER BY m.name, f.id, f.seq";
+
+/// Unique constraints of the tables, one row per column
+const SCHEMA_UNIQUE_SQL: &str = "SELECT m.name AS table_name, l.name AS index_name, i.name AS column_name
+                                 FROM sqlite_master m, pragma_index_list(m.name) l, pragma_index_info(l.name) i
+                                 WHERE m.type = 'table' AND m.name NOT LIKE 'sqlite_%' AND l.origin = 'u'
+                                 ORDER BY m.name, l.name, i.seqno";
+
+/// Indexes, views and triggers, leaving out the indexes SQLite creates for constraints which have no SQL
+const SCHEMA_OBJECTS_SQL: &str = "SELECT type, tbl_name AS table_name, name, sql AS definition
+                                  FROM sqlite_master
+                                  WHERE type IN ('index', 'view', 'trigger') AND sql IS NOT NULL
+                                  ORDER BY tbl_name, name";
+
+/// Connection to a SQLite database file
+pub struct SqliteDriver {
+    conn: rusqlite::Connection,
+    path: String
+}
+
+impl SqliteDriver {
+    /// Opens a SQLite database, creating it if it does not exist
+    ///
+    /// - `path: &str` - Path of the database file
+    /// - *returns*: `SqliteDriver` - Connected driver, error message if fail
+    pub fn connect(path: &str) -> Result<SqliteDriver, String> {
+        match rusqlite::Connection::open(path) {
+            Ok(conn) => Ok(SqliteDriver {
+                conn,
+                path: path.to_owned()
+            }),
+            Err(err) => Err(err.to_string())
+        }
+    }
+}
+
+impl Driver for SqliteDriver {
+    fn dialect(&self) -> Dialect {
+        Dialect::Sqlite
+    }
+
+    fn database_name(&self) -> String {
+        self.path.clone()
+    }
+
+    fn execute(&self, sql: &str, params: &[Value]) -> Result<u64, String> {
+        self.conn.execute(&placeholders(sql), rusqlite::params_from_iter(params.iter()))
+            .map(|changed| changed as u64)
+            .map_err(|err| err.to_string())
+    }
+
+    fn batch_execute(&self, sql: &str) -> Result<(), String> {
+        self.conn.execute_batch(sql).map_err(|err| err.to_string())
+    }
+
+    fn query(&self, sql: &str, params: &[Value]) -> Result<Vec<Row>, String> {
+        let mut stmt = self.conn.prepare(&placeholders(sql)).map_err(|err| err.to_string())?;
+        let columns: Vec<String> = stmt.column_names().iter().map(|col| (*col).to_owned()).collect();
+
+        let mut rows = stmt.query(rusqlite::params_from_iter(params.iter())).map_err(|err| err.to_string())?;
+
+        let mut result = Vec::new();
+        while let Some(row) = rows.next().map_err(|err| err.to_string())? {
+            let mut values = Vec::new();
+            for (idx, col) in columns.iter().enumerate() {
+                let value = match row.get_ref(idx) {
+                    Ok(ValueRef::Null) => Value::Null,
+                    Ok(ValueRef::Integer(i)) => Value::Int(i),
+                    Ok(ValueRef::Text(text)) => Value::Text(String::from_utf8_lossy(text).into_owned()),
+                    Ok(_) => return Err(format!("Failed to read column {}: unsupported type", col)),
+                    Err(err) => return Err(format!("Failed to read column {}: {}", col, err)),
+                };
+                values.push(value);
+            }
+            result.push(Row::new(columns.clone(), values));
+        }
+
+        Ok(result)
+    }
+
+    fn begin(&self) -> Result<(), String> {
+        // Take the write lock up front, see `lock(&self)`
+        self.batch_execute("BEGIN IMMEDIATE")
+    }
+
+    fn commit(&self) -> Result<(), String> {
+        self.batch_execute("COMMIT")
+    }
+
+    fn rollback(&self) -> Result<(), String> {
+        self.batch_execute("ROLLBACK")
+    }
+
+    fn lock(&self) -> Result<(), String> {
+        // Transactions begin with the database write lock held, which already serializes changes
+        Ok(())
+    }
+
+    /// Transactions take the database lock when they begin, so statements never deadlock
+    fn is_retryable(&self, _err: &str) -> bool {
+        false
+    }
+
+    /// SQLite has neither, it only waits for the database lock when a transaction begins
+    fn set_timeouts(&self, statement_timeout: Option<Duration>, lock_timeout: Option<Duration>) -> Result<(), String> {
+        if statement_timeout.is_some() || lock_timeout.is_some() {
+            warn!("SQLite does not support statement or lock timeouts, ignoring them for {}", self.path);
+        }
+
+        Ok(())
+    }
+
+    fn reset_timeouts(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn create_bookkeeping_tables(&self) -> Result<(), String> {
+        if let Err(err) = self.batch_execute(VER_TABLE_DDL) {
+            error!("Failed to create 'schema_versions' table for {}, error: {}", self.path, err);
+            return Err("table_create_fail".to_owned());
+        }
+
+        if let Err(err) = self.batch_execute(LIB_TABLE_DDL) {
+            error!("Failed to create 'schema_versions_lib' table for {}, error: {}", self.path, err);
+            return Err("lib_table_create_fail".to_owned());
+        }
+
+        Ok(())
+    }
+
+    fn table_columns(&self, table: &str) -> Result<Vec<(String, String)>, String> {
+        let rows = self.query("SELECT name, type FROM pragma_table_info($1)", &[Value::from(table)])?;
+
+        rows.iter()
+            .map(|row| Ok((row.get_idx(0)?, row.get_idx::<String>(1)?.to_lowercase())))
+            .collect()
+    }
+
+    /// SQLite does not name constraints, they are named after the table and columns as Postgres
+    /// does. CHECK constraints are only part of the table's SQL, they are left out.
+    fn schema(&self) -> Result<Schema, String> {
+        let mut schema = Schema::default();
+        let mut primary_keys: Vec<(String, i64, String)> = Vec::new();
+
+        for row in self.query(SCHEMA_COLUMNS_SQL, &[])? {
+            let table: String = row.get("table_name")?;
+            let column: String = row.get("column_name")?;
+            let pk: i64 = row.get("pk")?;
+            if pk > 0 {
+                primary_keys.push((table.clone(), pk, column.clone()));
+            }
+
+            schema.add_column(&table, Column {
+                name: column,
+                data_type: row.get("data_type")?,
+                not_null: row.get::<i64>("not_null")? != 0,
+                default: row.get("column_default")?,
+            });
+        }
+
+        primary_keys.sort();
+        for (table, columns) in group(primary_keys.into_iter().map(|(table, _, column)| (table, column))) {
+            schema.add_constraint(&table, Constraint {
+                name: format!("{}_pkey", table),
+                definition: format!("PRIMARY KEY ({})", columns.join(", ")),
+            });
+        }
+
+        let mut unique = Vec::new();
+        for row in self.query(SCHEMA_UNIQUE_SQL, &[])? {
+            unique.push(((row.get::<String>("table_name")?, row.get::<String>("index_name")?),
+                         row.get::<String>("column_name")?));
+        }
+        for ((table, _), columns) in group(unique.into_iter()) {
+            schema.add_constraint(&table, Constraint {
+                name: format!("{}_{}_key", table, columns.join("_")),
+                definition: format!("UNIQUE ({})", columns.join(", ")),
+            });
+        }
+
+        let mut foreign_keys = Vec::new();
+        for row in self.query(SCHEMA_FOREIGN_KEYS_SQL, &[])? {
+            let to_column: Option<String> = row.get("to_column")?;
+            let mut actions = String::new();
+            for action in &["on_update", "on_delete"] {
+                let rule: String = row.get(action)?;
+                if rule != "NO ACTION" {
+                    actions.push_str(&format!(" {} {}", action.replace('_', " ").to_uppercase(), rule));
+                }
+            }
+
+            let key = (row.get::<String>("table_name")?, row.get::<i64>("id")?.to_string(),
+                       row.get::<String>("to_table")?, actions);
+            foreign_keys.push((key, (row.get::<String>("from_column")?, to_column)));
+        }
+        for ((table, _, to_table, actions), columns) in group(foreign_keys.into_iter()) {
+            let from: Vec<&str> = columns.iter().map(|c| c.0.as_str()).collect();
+            // Foreign keys without columns reference the primary key of the other table
+            let to: Option<Vec<&str>> = columns.iter().map(|c| c.1.as_deref()).collect();
+            let to = to.map_or(String::new(), |to| format!("({})", to.join(", ")));
+
+            schema.add_constraint(&table, Constraint {
+                name: format!("{}_{}_fkey", table, from.join("_")),
+                definition: format!("FOREIGN KEY ({}) REFERENCES {}{}{}", from.join(", "), to_table, to, actions),
+            });
+        }
+
+        for table in &mut schema.tables {
+            table.constraints.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+
+        for row in self.query(SCHEMA_OBJECTS_SQL, &[])? {
+            let kind: String = row.get("type")?;
+            let table: String = row.get("table_name")?;
+            let object = SchemaObject {
+                table: if kind == "view" { None } else { Some(table) },
+                name: row.get("name")?,
+                definition: row.get("definition")?,
+            };
+
+            match kind.as_str() {
+                "index" => schema.indexes.push(object),
+                "view" => schema.views.push(object),
+                _ => schema.triggers.push(object),
+            }
+        }
+        schema.views.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(schema)
+    }
+}
+
+/// Groups consecutive rows sharing a key, keeping the values of each group in order
+fn group<K: PartialEq, V, I: Iterator<Item = (K, V)>>(rows: I) -> Vec<(K, Vec<V>)> {
+    let mut groups: Vec<(K, Vec<V>)> = Vec::new();
+    for (key, value) in rows {
+        match groups.last_mut() {
+            Some(last) if last.0 == key => last.1.push(value),
+            _ => groups.push((key, vec![value])),
+        }
+    }
+
+    groups
+}
+
+impl rusqlite::ToSql for Value {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(match *self {
+            Value::Null => ToSqlOutput::Borrowed(ValueRef::Null),
+            Value::Int(i) => ToSqlOutput::Borrowed(ValueRef::Integer(i)),
+            Value::Text(ref s) => ToSqlOutput::Borrowed(ValueRef::Text(s.as_bytes())),
+            // SQLite has no timestamp type, store them as text which sorts chronologically
+            Value::Timestamp(ts) => ToSqlOutput::from(ts.format(TIMESTAMP_FORMAT).to_string()),
+        })
+    }
+}
+
+/// Translates `$1`, `$2`, ... placeholders into SQLite's `?1`, `?2`, ... syntax
+fn placeholders(sql: &str) -> String {
+    drivers::rewrite_placeholders(sql, |num| format!("?{}", num))
+}
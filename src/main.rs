@@ -5,71 +5,130 @@
 #[macro_use]
 extern crate log;
 extern crate clap;
+extern crate chrono;
 extern crate ini;
+extern crate mysql;
+#[macro_use]
 extern crate postgres;
+extern crate rusqlite;
+#[macro_use]
+extern crate serde_json;
+extern crate sha2;
+extern crate sqlparser;
 
 mod commands;
+mod drivers;
 mod models;
 
 use models::command::Command;
 
+use std::process;
+
 //use std::io::{self, Write};
-use clap::{Arg, App, SubCommand};
+use clap::{App, Arg};
+
+use commands::create::create::CreateCmd;
+use commands::drop::drop::DropCmd;
+use commands::dump_schema::dump_schema::DumpSchemaCmd;
+use commands::history::history::HistoryCmd;
+use commands::lint::lint::LintCmd;
+use commands::redo::redo::RedoCmd;
+use commands::repair::duplicates::RepairDuplicatesCmd;
+use commands::reset::reset::ResetCmd;
+use commands::run::run::RunCmd;
+use commands::seed::seed::SeedCmd;
+use commands::status::status::StatusCmd;
+use models::logger::{self, LogFormat};
 
 fn main() {
     let prgm_ver = env!("CARGO_PKG_VERSION");
-    info!("Migrate v{}", prgm_ver);
 
     // Define command line options
     let app_matches = App::new("Migrate")
                             .version(prgm_ver)
                             .about("Lightweight database migration runner")
-                            .subcommand(SubCommand::with_name("create")
-                                .about("Create scaffold for migration")
-                                .arg(Arg::with_name("name")
-                                    .help("Name of database migration")
-                                    .index(1)
-                                    .required(true)
-                                )
-                                .arg(Arg::with_name("migrations-dir")
-                                    .help("The directory to put the new migration in.")
-                                    .short("m")
-                                    .takes_value(true)
-                                    .default_value("migrations")
-                                )
+                            .arg(Arg::with_name("verbose")
+                                .help("Log more, repeat to log even more (-vv)")
+                                .short("v")
+                                .multiple(true)
+                                .global(true)
+                            )
+                            .arg(Arg::with_name("quiet")
+                                .help("Log less, repeat to only log errors (-qq)")
+                                .short("q")
+                                .multiple(true)
+                                .global(true)
                             )
+                            .arg(Arg::with_name("log-format")
+                                .help("Format of the logs written to stderr")
+                                .long("log-format")
+                                .takes_value(true)
+                                .possible_values(&["human", "json"])
+                                .default_value("human")
+                                .global(true)
+                            )
+                            .subcommand(commands::create::sub_cmd::sub_cmd())
                             .subcommand(commands::run::sub_cmd::sub_cmd())
+                            .subcommand(commands::redo::sub_cmd::sub_cmd())
+                            .subcommand(commands::reset::sub_cmd::sub_cmd())
+                            .subcommand(commands::seed::sub_cmd::sub_cmd())
+                            .subcommand(commands::drop::sub_cmd::sub_cmd())
+                            .subcommand(commands::drop::sub_cmd::recreate_sub_cmd())
+                            .subcommand(commands::status::sub_cmd::sub_cmd())
+                            .subcommand(commands::history::sub_cmd::sub_cmd())
+                            .subcommand(commands::lint::sub_cmd::sub_cmd())
+                            .subcommand(commands::dump_schema::sub_cmd::sub_cmd())
+                            .subcommand(commands::repair::sub_cmd::sub_cmd())
                             .get_matches();
 
-    match app_matches.subcommand() {
-        ("create", Some(sub_matches)) => {
-            println!("TODO: create command -> {:?}", sub_matches);
+    let (sub_name, sub_matches) = app_matches.subcommand();
+    let log_matches = sub_matches.unwrap_or(&app_matches);
+    let verbosity = log_matches.occurrences_of("verbose") as i64 - log_matches.occurrences_of("quiet") as i64;
+    let log_format = LogFormat::from_name(log_matches.value_of("log-format").unwrap_or("human")).unwrap_or(LogFormat::Human);
+    if let Err(err) = logger::init(verbosity, log_format) {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+    info!("Migrate v{}", prgm_ver);
+
+    match (sub_name, sub_matches) {
+        ("create", Some(sub_matches)) => run_command("create", CreateCmd::from_matches(sub_matches)),
+        ("run", Some(sub_matches)) => run_command("run", RunCmd::from_matches(sub_matches)),
+        ("redo", Some(sub_matches)) => run_command("redo", RedoCmd::from_matches(sub_matches)),
+        ("reset", Some(sub_matches)) => run_command("reset", ResetCmd::from_matches(sub_matches)),
+        ("seed", Some(sub_matches)) => run_command("seed", SeedCmd::from_matches(sub_matches)),
+        ("drop", Some(sub_matches)) => run_command("drop", DropCmd::from_matches(sub_matches)),
+        ("recreate", Some(sub_matches)) => {
+            run_command("recreate", DropCmd::from_matches(sub_matches).map(|cmd| cmd.recreate()))
         }
-        ("run", Some(sub_matches)) => {
-            let cmd = match commands::run::run::RunCmd::from_matches(sub_matches) {
-                Ok(cmd) => { cmd }
-                Err(err) => {
-                    println!("Failed to load \"run\" command: {}", err);
-                    commands::run::run::RunCmd::new(String::new(), String::new(), String::new(), String::new())
-                }
-            };
-            match cmd.run() {
-                Ok(res) => {
-                    println!("OK! => {:?}", res);
-                }
-                Err(err) => {
-                    println!("ERR! => {:?}", err);
-                }
+        ("history", Some(sub_matches)) => run_command("history", HistoryCmd::from_matches(sub_matches)),
+        ("status", Some(sub_matches)) => run_command("status", StatusCmd::from_matches(sub_matches)),
+        ("lint", Some(sub_matches)) => run_command("lint", LintCmd::from_matches(sub_matches)),
+        ("dump-schema", Some(sub_matches)) => run_command("dump-schema", DumpSchemaCmd::from_matches(sub_matches)),
+        ("repair", Some(repair_matches)) => {
+            if let ("duplicates", Some(sub_matches)) = repair_matches.subcommand() {
+                run_command("repair duplicates", RepairDuplicatesCmd::from_matches(sub_matches));
             }
-            /*
-            match commands::run::sub_matches) {
-                Ok(_) => {}
-                Err(err) => {
-                    writeln!(&mut io::stderr(), "{}", err).expect("Failed to write to stderr");
-                }
-            }
-            */
         }
         _ => {}
     }
 }
+
+/// Runs a sub-command loaded from its arguments, exiting with status 1 if it fails to load or run
+///
+/// Errors are logged, so they go to stderr with every other log and stdout only holds what the
+/// command prints for its user, ex. the `--output json` report deploy tooling parses.
+fn run_command<C: Command<C>>(name: &str, cmd: Result<C, String>) {
+    let cmd = match cmd {
+        Ok(cmd) => cmd,
+        Err(err) => {
+            error!("Failed to load \"{}\" command: {}", name, err);
+            process::exit(1);
+        }
+    };
+
+    if let Err(err) = cmd.run() {
+        error!("{}", err);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,364 @@
+//! The `config` module loads database connection information shared by sub-commands.
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use clap::ArgMatches;
+use ini::Ini;
+
+use drivers::{Dialect, Driver};
+use drivers::mysql::MysqlDriver;
+use drivers::postgres::PostgresDriver;
+use drivers::sqlite::SqliteDriver;
+
+/// Keys of an environment's section which configure Migrate rather than provide variables
+const SETTINGS: &[&str] = &["driver", "url", "host", "user", "password", "database", "production", "protected"];
+
+/// Information needed to connect to the database Migrate operates on
+///
+/// Loaded from the section of the `--config/-c` file selected by `--environment/-e`, then overridden
+/// by the `--driver`, `--url`, `--host/-h`, `--user/-u`, `--password/-p` and `--database/-d` options.
+///
+/// A database URL takes the place of the other connection settings, its scheme selects the driver.
+/// Without one Postgres and MySQL need a host, user, password and database, SQLite only needs a
+/// database file.
+///
+/// An environment is a production environment if its section sets `production = true`, or if it is
+/// named `production` and does not set `production = false`. An environment is protected if its
+/// section sets `protected = true`, production environments are protected unless they set
+/// `protected = false`.
+///
+/// Every other key of the environment's section is a variable migrations can use, see
+/// `migration::render`. Connection settings are not, so credentials never end up in rendered SQL.
+#[derive(Debug, Clone)]
+pub struct DbConfig {
+    pub environment: Option<String>,
+    pub production: bool,
+    pub protected: bool,
+    /// If `--yes-i-am-sure` confirms changes to protected environments up front
+    pub assume_yes: bool,
+    /// If a change to the protected environment has been confirmed, see `confirm_protected`
+    confirmed: Cell<bool>,
+    pub driver: Dialect,
+    pub url: Option<String>,
+    pub host: String,
+    pub user: String,
+    pub password: String,
+    pub database: String,
+    /// Values of `{{ name }}` placeholders in migrations, from the environment's section
+    pub variables: HashMap<String, String>
+}
+
+impl DbConfig {
+    pub fn new(host: String, user: String, password: String, database: String) -> DbConfig {
+        DbConfig {
+            environment: None,
+            production: false,
+            protected: false,
+            assume_yes: false,
+            confirmed: Cell::new(false),
+            driver: Dialect::Postgres,
+            url: None,
+            host,
+            user,
+            password,
+            database,
+            variables: HashMap::new()
+        }
+    }
+
+    /// Loads connection information from a sub-command's arguments, see `commands::db_args`
+    ///
+    /// - `matches: &ArgMatches` - Arguments of the sub-command
+    /// - *returns*: `DbConfig` - Connection information, error message if fail
+    ///
+    /// # Errors
+    /// - The config file can not be loaded or has no section for the environment
+    /// - The driver is unknown, or does not match the database URL's scheme
+    /// - Connection settings the driver needs are missing
+    pub fn from_matches(matches: &ArgMatches) -> Result<DbConfig, String> {
+        let mut obj = DbConfig::new(String::new(), String::new(), String::new(), String::new());
+        let mut driver: Option<String> = None;
+
+        // Config from ini file
+        if let Some(config_path) = matches.value_of("config") {
+            let confr = Ini::load_from_file(config_path);
+            if let Err(err) = confr {
+                return Err(err.to_string())
+            }
+
+            let conf = confr.unwrap();
+
+            let sectionr = conf.section(matches.value_of("environment"));
+            if sectionr.is_none() {
+                return Err(format!("No config for environment \"{}\"", matches.value_of("environment").unwrap_or("None")))
+            }
+
+            let section = sectionr.unwrap();
+            obj.variables = section.iter()
+                .filter(|&(key, _)| !SETTINGS.contains(&key.as_str()))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect();
+
+            obj.environment = matches.value_of("environment").map(|env| env.to_owned());
+            obj.production = match section.get("production") {
+                Some(production) => parse_bool("production", production)?,
+                None => obj.environment.as_ref().is_some_and(|env| env == "production")
+            };
+            obj.protected = match section.get("protected") {
+                Some(protected) => parse_bool("protected", protected)?,
+                None => obj.production
+            };
+
+            if let Some(name) = section.get("driver") {
+                driver = Some(name.to_owned());
+            }
+
+            if let Some(url) = section.get("url") {
+                obj.url = Some(url.to_owned());
+            }
+
+            if let Some(host) = section.get("host") {
+                obj.host = host.to_owned();
+            }
+
+            if let Some(user) = section.get("user") {
+                obj.user = user.to_owned();
+            }
+
+            if let Some(password) = section.get("password") {
+                obj.password = password.to_owned();
+            }
+
+            if let Some(database) = section.get("database") {
+                obj.database = database.to_owned();
+            }
+        }
+
+        // Config from options
+        obj.assume_yes = matches.is_present("yes-i-am-sure");
+
+        if let Some(name) = matches.value_of("driver") {
+            driver = Some(name.to_owned());
+        }
+
+        if let Some(url) = matches.value_of("url") {
+            obj.url = Some(url.to_owned());
+        }
+
+        if let Some(host) = matches.value_of("host") {
+            obj.host = host.to_owned();
+        }
+
+        if let Some(user) = matches.value_of("user") {
+            obj.user = user.to_owned();
+        }
+
+        if let Some(password) = matches.value_of("password") {
+            obj.password = password.to_owned();
+        }
+
+        if let Some(database) = matches.value_of("database") {
+            obj.database = database.to_owned();
+        }
+
+        // Select driver
+        let named = match driver {
+            Some(name) => match Dialect::from_name(&name) {
+                Some(dialect) => Some(dialect),
+                None => return Err(format!("Unknown database driver \"{}\", expected postgres, sqlite or mysql", name))
+            },
+            None => None
+        };
+
+        obj.driver = match obj.url {
+            Some(ref url) => {
+                let scheme = url.split("://").next().unwrap_or_default();
+                let from_url = match Dialect::from_url(url) {
+                    Some(dialect) => dialect,
+                    None => return Err(format!("Database URL scheme \"{}\" is not supported, expected postgres://, postgresql://, sqlite://, mysql:// or mariadb://",
+                                               scheme))
+                };
+
+                match named {
+                    Some(dialect) if dialect != from_url => {
+                        return Err(format!("Database URL is for the {} driver, but the {} driver was selected",
+                                           from_url.name(), dialect.name()))
+                    },
+                    _ => from_url
+                }
+            },
+            None => named.unwrap_or(Dialect::Postgres)
+        };
+
+        // Check driver has everything it needs
+        if obj.url.is_none() {
+            let required: Vec<(&str, &String)> = match obj.driver {
+                Dialect::Postgres | Dialect::Mysql => vec![("host", &obj.host), ("user", &obj.user),
+                                                           ("password", &obj.password), ("database", &obj.database)],
+                Dialect::Sqlite => vec![("database", &obj.database)],
+            };
+
+            let missing: Vec<&str> = required.iter()
+                .filter(|setting| setting.1.is_empty())
+                .map(|setting| setting.0)
+                .collect();
+            if !missing.is_empty() {
+                return Err(format!("Missing database connection settings for the {} driver: {}",
+                                   obj.driver.name(), missing.join(", ")))
+            }
+        }
+
+        Ok(obj)
+    }
+
+    /// Refuses an action which throws data away if the environment is protected
+    ///
+    /// - `action: &str` - What the command would do, ex. `drop the database`
+    /// - *returns*: Error message if the environment is protected
+    pub fn refuse_protected(&self, action: &str) -> Result<(), String> {
+        if self.protected {
+            return Err(format!("Refusing to {} in the protected environment \"{}\"", action,
+                               self.environment.as_ref().map_or("", |env| env.as_str())))
+        }
+
+        Ok(())
+    }
+
+    /// Asks for confirmation before an action which throws data away if the environment is protected
+    ///
+    /// - `action: &str` - What the command is about to do, ex. `reset the database`
+    /// - *returns*: Error message if the action was not confirmed
+    ///
+    /// On a terminal the name of the environment has to be typed to confirm. Elsewhere, ex. in CI,
+    /// the `--yes-i-am-sure` option confirms every action. The decision is logged. Once an action is
+    /// confirmed later actions of the same command are not asked about again.
+    ///
+    /// # Errors
+    /// - The typed name does not match the environment
+    /// - Standard input is not a terminal and `--yes-i-am-sure` was not given
+    pub fn confirm_protected(&self, action: &str) -> Result<(), String> {
+        if !self.protected || self.confirmed.get() {
+            return Ok(());
+        }
+
+        let env = self.environment.as_ref().map_or("", |env| env.as_str());
+
+        if self.assume_yes {
+            warn!("Confirmed with --yes-i-am-sure: {} in the protected environment \"{}\"", action, env);
+            self.confirmed.set(true);
+            return Ok(());
+        }
+
+        if !io::stdin().is_terminal() {
+            warn!("Refused to {} in the protected environment \"{}\", no terminal to confirm on", action, env);
+            return Err(format!("Refusing to {} in the protected environment \"{}\" without confirmation, run with \
+                                --yes-i-am-sure to confirm", action, env))
+        }
+
+        eprint!("About to {} in the protected environment \"{}\" ({}). Type the name of the environment to confirm: ",
+                action, env, self.database_name());
+        io::stderr().flush().map_err(|err| err.to_string())?;
+
+        let mut answer = String::new();
+        if let Err(err) = io::stdin().lock().read_line(&mut answer) {
+            return Err(format!("Failed to read confirmation: {}", err))
+        }
+
+        if answer.trim() != env {
+            warn!("Declined to {} in the protected environment \"{}\"", action, env);
+            return Err(format!("Confirmation did not match \"{}\", refusing to {}", env, action))
+        }
+
+        warn!("Confirmed: {} in the protected environment \"{}\"", action, env);
+        self.confirmed.set(true);
+
+        Ok(())
+    }
+
+    /// Name of the database Migrate operates on, for SQLite the path of the database file
+    pub fn database_name(&self) -> String {
+        match self.url {
+            Some(_) if self.driver == Dialect::Sqlite => self.sqlite_path(),
+            Some(ref url) => {
+                let path = url.split_once("://").map_or("", |(_, rest)| rest);
+                let path = path.split('?').next().unwrap_or_default();
+                path.split_once('/').map_or("", |(_, database)| database).to_owned()
+            },
+            None => self.database.clone()
+        }
+    }
+
+    /// Connection information for the maintenance database of the server, which databases can be
+    /// created and dropped from
+    ///
+    /// - `maintenance_db: &str` - Name of the maintenance database, ex. `postgres`
+    /// - *returns*: `DbConfig` - Same connection information with the database replaced, error
+    ///   message if the driver has no server
+    pub fn maintenance(&self, maintenance_db: &str) -> Result<DbConfig, String> {
+        if self.driver == Dialect::Sqlite {
+            return Err("SQLite databases are files, there is no maintenance database".to_owned())
+        }
+
+        let mut obj = self.clone();
+        obj.database = maintenance_db.to_owned();
+        obj.url = self.url.as_ref().map(|url| {
+            let (base, query) = match url.split_once('?') {
+                Some((base, query)) => (base, format!("?{}", query)),
+                None => (url.as_str(), String::new())
+            };
+            let (scheme, rest) = base.split_once("://").unwrap_or(("", base));
+            let server = rest.split('/').next().unwrap_or_default();
+            format!("{}://{}/{}{}", scheme, server, maintenance_db, query)
+        });
+
+        Ok(obj)
+    }
+
+    fn make_db_conn_str(&self, scheme: &str) -> String {
+        format!("{}://{}:{}@{}/{}", scheme, self.user, self.password, self.host, self.database)
+    }
+
+    /// Path of the SQLite database file
+    fn sqlite_path(&self) -> String {
+        match self.url {
+            Some(ref url) => url.split_once("://").map(|(_, path)| path).unwrap_or_default().to_owned(),
+            None => self.database.clone()
+        }
+    }
+
+    /// Connects to the database
+    ///
+    /// - *returns*: `Driver` - Connection to the database using the selected driver, see `drivers`,
+    ///   error message if fail
+    pub fn connect(&self) -> Result<Box<dyn Driver>, String> {
+        let connr: Result<Box<dyn Driver>, String> = match self.driver {
+            Dialect::Postgres => {
+                let url = self.url.clone().unwrap_or_else(|| self.make_db_conn_str("postgresql"));
+                PostgresDriver::connect(&url).map(|driver| Box::new(driver) as Box<dyn Driver>)
+            },
+            Dialect::Mysql => {
+                let url = self.url.clone().unwrap_or_else(|| self.make_db_conn_str("mysql"));
+                MysqlDriver::connect(&url).map(|driver| Box::new(driver) as Box<dyn Driver>)
+            },
+            Dialect::Sqlite => SqliteDriver::connect(&self.sqlite_path()).map(|driver| Box::new(driver) as Box<dyn Driver>),
+        };
+
+        match connr {
+            Ok(conn) => Ok(conn),
+            Err(err) => {
+                error!("Error connecting to database: {}", err);
+                Err(format!("Error connecting to database: {}", err))
+            }
+        }
+    }
+}
+
+/// Parses a boolean setting from a config file
+fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "1" => Ok(true),
+        "false" | "no" | "0" => Ok(false),
+        _ => Err(format!("Config setting \"{}\" must be true or false, found \"{}\"", key, value))
+    }
+}
@@ -1,13 +1,41 @@
 //! The `db_schema_ver` module provides an interface for determining and modifying the schema version.
-use chrono::datetime;
-use postgres::Connection;
-use postgres::rows::Row;
+use std::error;
+use std::fmt;
+use std::time::Duration;
+
+use chrono::datetime::DateTime;
+use chrono::naive::datetime::NaiveDateTime;
+use chrono::UTC;
+
+use drivers::{Dialect, Driver, FromValue, Row, Value};
+use models::internal_migrations::{INTERNAL_MIGRATIONS, BOOTSTRAP_LIB_VER, LIB_VER};
+use models::migration::Migration;
+
+/// A column of the `schema_versions` table, as (column name, Postgres type names, SQLite type names,
+/// MySQL type names)
+type VerTableCol = (&'static str, &'static [&'static str], &'static [&'static str], &'static [&'static str]);
+
+/// Columns the `schema_versions` table must provide
+///
+/// Columns whose type is changed by an internal migration accept the type of every layout.
+const VER_TABLE_COLS: &[VerTableCol] = &[
+    ("id", &["int4"], &["integer"], &["int"]),
+    ("updated", &["timestamp"], &["timestamp"], &["datetime"]),
+    ("version", &["int4", "int8"], &["integer"], &["int", "bigint"]),
+    ("migration_hash", &["text"], &["text"], &["text"]),
+    ("status", &["schema_version_status"], &["text"], &["varchar"]),
+    ("lib_ver", &["int4"], &["integer"], &["int"]),
+];
 
 pub struct DbSchemaVer<'a> {
-    conn: &'a Connection,
-    db_name: String
+    conn: &'a dyn Driver,
+    db_name: String,
+    /// If the bookkeeping tables exist, reads find no versions otherwise, see `open`
+    tables_exist: bool
 }
 
+/// Status of a migration, stored as the `schema_version_status` enum on Postgres and as text elsewhere
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum VerStatus {
     Ongoing,
     Fail,
@@ -15,403 +43,698 @@ pub enum VerStatus {
 }
 
 impl VerStatus {
-    // TODO: Find out how to either have match statement with keys of type String, or take type &str as an argument and figure out how to lowercase
-    fn from_string(str: String) -> Option<VerStatus> {
-        str = str.to_lowercase();
-        return match str {
+    fn from_string(str: &str) -> Option<VerStatus> {
+        match str.to_lowercase().as_str() {
             "ongoing" => Some(VerStatus::Ongoing),
             "fail" => Some(VerStatus::Fail),
             "success" => Some(VerStatus::Success),
             _ => None
         }
     }
+
+    /// Value of the `schema_version_status` enum which represents the status
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            VerStatus::Ongoing => "ongoing",
+            VerStatus::Fail => "fail",
+            VerStatus::Success => "success",
+        }
+    }
+}
+
+/// Reasons a row from the `schema_versions` table could not be turned into a VerEntry
+#[derive(Debug)]
+pub enum RowError {
+    /// The row does not contain these columns
+    MissingCols(Vec<String>),
+    /// The `status` column holds a value which is not a VerStatus
+    BadStatus(String),
+    /// A column holds a value of the wrong type, (column name, conversion error)
+    BadValue(String, String),
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RowError::MissingCols(ref cols) => write!(f, "columns missing: {}", cols.join(", ")),
+            RowError::BadStatus(ref status) => write!(f, "\"{}\" is not a schema version status", status),
+            RowError::BadValue(ref col, ref err) => write!(f, "bad value in column {}: {}", col, err),
+        }
+    }
 }
 
+impl error::Error for RowError {}
+
+/// A row of the `schema_versions` table
+#[allow(dead_code)]
+#[derive(Debug)]
 pub struct VerEntry {
-    id: i32,
-    updated: DateTime,
-    version: i32,
-    migration_hash: String,
-    status: VerStatus,
-    lib_ver: i32
+    /// Unique id
+    pub id: i32,
+    /// Most recent update in status
+    pub updated: DateTime<UTC>,
+    /// Schema version
+    pub version: i64,
+    /// Hash of migration directory
+    pub migration_hash: String,
+    /// Status of the migration
+    pub status: VerStatus,
+    /// Bookkeeping layout version Migrate used to perform the migration
+    pub lib_ver: i32,
+    /// Name of the migration directory, not known for rows recorded before layout 2
+    pub name: Option<String>,
+    /// How long performing the migration took, not known for rows recorded before layout 5 or
+    /// while the migration is ongoing
+    pub duration_ms: Option<i64>
+}
+
+/// A version represented by more than one row of the `schema_versions` table
+#[derive(Debug)]
+pub struct DuplicateVer {
+    /// Row which represents the version once duplicates are resolved
+    pub keep: VerEntry,
+    /// Duplicate rows
+    pub delete: Vec<VerEntry>
 }
 
 impl<'a> DbSchemaVer<'a> {
     /// Creates a new DbSchemaVer struct
     ///
-    /// - `connection: &Connection` - [Postgres Connection](https://sfackler.github.io/rust-postgres/doc/v0.11.11/postgres/struct.Connection.html)
+    /// - `connection: &Driver` - Database to operate on, see `drivers`
     /// - *returns*: `DbSchemaVer` - New DbSchemaVer if successful, Error code if not.
     ///
-    /// This function will attempt to create the 'schema_ver_status' type and the
-    /// 'schema_versions' table if they do not exist.
+    /// This function will attempt to create the 'schema_versions' table and the 'schema_versions_lib'
+    /// table, along with anything the driver needs for them such as Postgres' 'schema_version_status'
+    /// type, if they do not exist. All work is done inside one transaction which holds the
+    /// bookkeeping lock, so concurrent bootstraps of the same database do not race each other. On
+    /// Postgres and SQLite a failure leaves the database untouched. MySQL commits each `CREATE
+    /// TABLE` as it runs, tables created before a failure are kept and reused by the next bootstrap.
+    ///
+    /// If the tables already exist they are checked against the layout Migrate expects. Once
+    /// bootstrapped the tables are upgraded to the current layout, see `upgrade(&self)`.
     ///
     /// # Errors
-    /// - `get_db_name_fail` - Query to get current database name failed
-    /// - `type_create_fail` - Query to create `schema_version_status` enum failed
-    /// - `table_create_fail` - Query to create `schema_versions` table failed
-    fn bootstrap(connection: &'a Connection) -> Result<DbSchemaVer, String> {
-        // Get current database name
-        let db_name = match connection.execute("SELECT current_database()", &[]) {
-            Ok(name) => name,
-            Err(err) => {
-                error!("Failed to get name of current database, error: {}", err);
-                return Err("get_db_name_fail");
-            }
-        };
-
+    /// - `transaction_fail` - Failed to begin or commit the bootstrap transaction
+    /// - `lock_fail` - Failed to acquire the bookkeeping lock
+    /// - See `Driver::create_bookkeeping_tables` for errors raised while creating the tables
+    /// - `incompatible_table` - Existing `schema_versions` table is missing columns Migrate uses, or
+    ///   they have an unexpected type
+    /// - See `upgrade(&self)` for errors raised while upgrading
+    pub fn bootstrap(connection: &'a dyn Driver) -> Result<DbSchemaVer<'a>, String> {
         // Create new schema_ver obj to return
         let schema_ver = DbSchemaVer {
             conn: connection,
-            db_name: db_name
+            db_name: connection.database_name(),
+            tables_exist: true
         };
 
-        // Create 'schema_ver_status' if type doesn't exist
-        let create_typer = schema_ver.conn.execute("IF NOT EXISTS
-                                                (SELECT 1 FROM pg_type WHERE typename = '$1') THEN
-                                                    CREATE TYPE schema_version_status AS ENUM (
-                                                        'Ongoing', 'Success', 'Fail
-                                                     )", &[]);
-        match create_typer {
-            Ok(rows_up) => {
-                info!("Created 'schema_version_status' type for {}", schema_ver.db_name);
-            },
+        schema_ver.transaction("bootstrap", || {
+            connection.create_bookkeeping_tables()?;
+            schema_ver.check_layout()
+        })?;
+
+        info!("Bootstrapped 'schema_versions' table for {}", schema_ver.db_name);
+
+        schema_ver.upgrade()?;
+
+        Ok(schema_ver)
+    }
+
+    /// Opens the bookkeeping tables without creating or upgrading them, for runs which must not
+    /// change the database such as dry runs
+    ///
+    /// - `connection: &Driver` - Database to operate on, see `drivers`
+    /// - *returns*: `DbSchemaVer` - DbSchemaVer which finds no versions if the tables do not exist,
+    ///   error code if fail
+    ///
+    /// Does not modify the database.
+    ///
+    /// # Errors
+    /// - `table_create_fail` - Failed to read the layout of the `schema_versions` table
+    /// - `incompatible_table` - Existing `schema_versions` table is missing columns Migrate uses
+    /// - `lib_ver_query_fail` - Query to determine the current layout version failed
+    /// - `newer_lib_ver` - The database was last upgraded by a newer version of Migrate
+    /// - `outdated_lib_ver` - The tables are at an older layout, which only `bootstrap` upgrades
+    pub fn open(connection: &'a dyn Driver) -> Result<DbSchemaVer<'a>, String> {
+        let mut schema_ver = DbSchemaVer {
+            conn: connection,
+            db_name: connection.database_name(),
+            tables_exist: false
+        };
+
+        match connection.table_columns("schema_versions") {
+            Ok(ref cols) if cols.is_empty() => return Ok(schema_ver),
+            Ok(_) => schema_ver.check_layout()?,
             Err(err) => {
-                error!("Failed to created type 'schema_version_status' type, error: {}", err);
-                return Err("type_create_fail");
+                error!("Failed to read layout of 'schema_versions' table for {}, error: {}", schema_ver.db_name, err);
+                return Err("table_create_fail".to_owned());
             }
         }
 
-        // Create 'schema_versions' table if it doesn't exist
-        let create_tlbr = schema_ver.conn.execute("CREATE TABLE IF NOT EXISTS schema_versions (
-                                id INT PRIMARY KEY NOT NULL,
-                                updated TIMESTAMP NOT NULL,
-                                version INT NOT NULL,
-                                migration_hash TEXT NOT NULL,
-                                status schema_ver_status NOT NULL,
-                                lib_ver INT NOT NULL)", &[]);
-        match create_tlbr {
-            Ok(rows_updated) => {
-                info!("Created 'schema_versions' table for {}", schema_ver.db_name);
-            },
-            Err(err) => {
-                error!("Failed to create 'schema_versions' table for {}, error: {}", schema_ver.db_name, err);
-                return Err("table_create_fail");
-            }
+        let (cur_lib_ver, tool_version) = schema_ver.layout()?;
+        if cur_lib_ver < LIB_VER {
+            error!("Bookkeeping tables of {} are at layout {} (Migrate v{}), run \"status\" to upgrade them to layout {} first",
+                   schema_ver.db_name, cur_lib_ver, tool_version, LIB_VER);
+            return Err("outdated_lib_ver".to_owned());
         }
 
+        schema_ver.tables_exist = true;
         Ok(schema_ver)
     }
 
-    /// Construct a struct (VerEntry struct) which represents a row in the `schema_versions` table
-    ///
-    /// - `row: &Row - [Postgres Row](https://sfackler.github.io/rust-postgres/doc/v0.11.11/postgres/rows/struct.Row.html)
-    ///                 to construct row struct from
-    /// - *returns*: `VerEntry` - VerEntry which represents provided Row, error code if fail
+    /// Finds the layout the bookkeeping tables are at and which version of Migrate put them there
     ///
     /// # Errors
-    /// - `missing_cols` - The Row provided does not contain all the columns necessary
-    /// - `date_parse_fail` - Failed to parse a DateTime struct from `updated` column in row
-    /// - `status_parse_fail` - Failed to find VerStatus enum value which matched `status` column in row
-    fn construct_row(row: &'a Row) -> Result<VerEntry, String> {
-        // Check that required columns are provided
-        let req_cols = vec!["id", "updated", "version", "migration_hash", "status", "lib_ver"];
-        let mut prov_cols = Vec::new();
-
-        /*
-        row.columns().any(|row| {
-            debug!("{}", row);
-            false
-        });
-        */
-
-        Err("BREAK")
-        /*
-        row.columns().iter().map(|col| {
-            prov_cols.push(col.name());
-        });
-
-        let prov_cols_slice = prov_cols.as_slice();
-        let mut missing_cols = Vec::new();
-
-        req_cols.iter().map(|col| {
-            if prov_cols_slice.contains(col) == false {
-                missing_cols.push(col);
+    /// - `lib_ver_query_fail` - Query to determine the current layout version failed
+    /// - `newer_lib_ver` - The database was last upgraded by a newer version of Migrate
+    fn layout(&self) -> Result<(i32, String), String> {
+        let latest = self.conn.query("SELECT lib_ver, tool_version FROM schema_versions_lib
+                                      ORDER BY lib_ver DESC LIMIT 1", &[])
+            .and_then(|rows| match rows.first() {
+                Some(row) => Ok((row.get("lib_ver")?, Some(row.get("tool_version")?))),
+                None => Ok((BOOTSTRAP_LIB_VER, None)),
+            });
+        let (cur_lib_ver, tool_version): (i32, Option<String>) = match latest {
+            Ok(latest) => latest,
+            Err(err) => {
+                error!("Failed to determine bookkeeping layout version of {}, error: {}", self.db_name, err);
+                return Err("lib_ver_query_fail".to_owned());
             }
-        });
+        };
+
+        let tool_version = tool_version.unwrap_or_else(|| "unknown".to_owned());
 
-        if missing_cols.len() > 0 {
-            error!("Cannot construct row, columns missing: {}", missing_cols);
-            return Err("missing_cols")
+        if cur_lib_ver > LIB_VER {
+            error!("Bookkeeping tables of {} are at layout {} (Migrate v{}), this version of Migrate only understands up to layout {}",
+                   self.db_name, cur_lib_ver, tool_version, LIB_VER);
+            return Err("newer_lib_ver".to_owned());
         }
 
-        // Map values
-        // -- Create and error handle more complex values ahead of time
-        let updatedv = match DateTime.parse_from_rfc3339(row.get("update")) {
-            Ok(up) => up,
+        Ok((cur_lib_ver, tool_version))
+    }
+
+    /// If the bookkeeping tables exist, always the case once bootstrapped
+    pub fn tables_exist(&self) -> bool {
+        self.tables_exist
+    }
+
+    /// Checks the `schema_versions` table provides every column Migrate uses
+    ///
+    /// # Errors
+    /// - `table_create_fail` - Failed to read the layout of the table
+    /// - `incompatible_table` - Columns are missing or have an unexpected type
+    fn check_layout(&self) -> Result<(), String> {
+        let cols = match self.conn.table_columns("schema_versions") {
+            Ok(cols) => cols,
             Err(err) => {
-                error!("Failed to parse date time string \"{}\" to DateTime, error: {}", row.get("update"), err.description());
-                return Err("date_parse_fail")
+                error!("Failed to read layout of 'schema_versions' table for {}, error: {}", self.db_name, err);
+                return Err("table_create_fail".to_owned());
             }
         };
 
-        let statusv = match VerStatus::from_string(row.get("status")) {
-            Some(status) => status,
-            None => {
-                error!("Failed to parse string \"{}\" into VerStatus enum, did not match any text", row.get("status"));
-                return Err("status_parse_fail")
+        let mut bad_cols = Vec::new();
+        for &(name, pg_tys, sqlite_tys, mysql_tys) in VER_TABLE_COLS {
+            let tys = match self.conn.dialect() {
+                Dialect::Postgres => pg_tys,
+                Dialect::Sqlite => sqlite_tys,
+                Dialect::Mysql => mysql_tys,
+            };
+
+            match cols.iter().find(|col| col.0 == name) {
+                Some(col) if tys.contains(&col.1.as_str()) => {},
+                Some(col) => bad_cols.push(format!("{} (expected type {}, found {})", name, tys.join(" or "), col.1)),
+                None => bad_cols.push(format!("{} (missing)", name)),
             }
+        }
+
+        if !bad_cols.is_empty() {
+            error!("Existing 'schema_versions' table for {} has an incompatible layout, columns: {}", self.db_name, bad_cols.join(", "));
+            return Err("incompatible_table".to_owned());
+        }
+
+        Ok(())
+    }
+
+    /// Runs a function inside a transaction which holds the bookkeeping lock
+    ///
+    /// - `what: &str` - What the transaction does, for log messages
+    /// - `f: F` - Function to run, the transaction is rolled back if it fails
+    /// - *returns*: `T` - Result of the function, error code if fail
+    ///
+    /// # Errors
+    /// - `transaction_fail` - Failed to begin or commit the transaction
+    /// - `lock_fail` - Failed to acquire the bookkeeping lock
+    fn transaction<T, F>(&self, what: &str, f: F) -> Result<T, String>
+        where F: FnOnce() -> Result<T, String>
+    {
+        if let Err(err) = self.conn.begin() {
+            error!("Failed to begin {} transaction for {}, error: {}", what, self.db_name, err);
+            return Err("transaction_fail".to_owned());
+        }
+
+        let result = self.lock().and_then(|_| f());
+
+        let end = match result {
+            Ok(_) => self.conn.commit(),
+            Err(_) => self.conn.rollback(),
+        };
+        if let Err(err) = end {
+            error!("Failed to end {} transaction for {}, error: {}", what, self.db_name, err);
+            return Err("transaction_fail".to_owned());
+        }
+
+        result
+    }
+
+    /// Upgrades the bookkeeping tables to the layout this version of Migrate expects
+    ///
+    /// - *returns*: Nothing if the tables are at the current layout, error code if fail
+    ///
+    /// Runs every internal migration (See `models::internal_migrations`) which the database has not
+    /// seen yet, in order, inside one transaction. Each is recorded in the `schema_versions_lib`
+    /// table along with the version of Migrate which ran it. Databases bootstrapped before the
+    /// `schema_versions_lib` table existed are treated as being at the bootstrap layout.
+    ///
+    /// MySQL commits DDL statements as they run, so there the transaction only covers each record
+    /// in `schema_versions_lib`. Internal migrations which completed stay recorded if a later one
+    /// fails, and the failed one is run again in full by the next upgrade.
+    ///
+    /// # Errors
+    /// - `transaction_fail` - Failed to begin or commit the upgrade transaction
+    /// - `lock_fail` - Failed to acquire the bookkeeping lock
+    /// - `lib_ver_query_fail` - Query to determine the current layout version failed
+    /// - `newer_lib_ver` - The database was last upgraded by a newer version of Migrate
+    /// - `internal_migration_fail` - An internal migration failed, no changes were made except for
+    ///   DDL already committed by MySQL
+    pub fn upgrade(&self) -> Result<(), String> {
+        self.transaction("upgrade", || {
+            let (cur_lib_ver, tool_version) = self.layout()?;
+
+            if cur_lib_ver == LIB_VER {
+                return Ok(());
+            }
+
+            info!("Bookkeeping tables of {} are at layout {} (Migrate v{}), upgrading to layout {}",
+                  self.db_name, cur_lib_ver, tool_version, LIB_VER);
+
+            for migration in INTERNAL_MIGRATIONS.iter().filter(|m| m.lib_ver > cur_lib_ver) {
+                info!("Running internal migration {}: {}", migration.lib_ver, migration.description);
+
+                if let Err(err) = self.conn.batch_execute(migration.sql(self.conn.dialect())) {
+                    error!("Internal migration {} failed for {}, error: {}", migration.lib_ver, self.db_name, err);
+                    return Err("internal_migration_fail".to_owned());
+                }
+
+                let recordr = self.conn.execute("INSERT INTO schema_versions_lib (lib_ver, applied, tool_version)
+                                                 VALUES ($1, $2, $3)",
+                                                &[migration.lib_ver.into(), now(), env!("CARGO_PKG_VERSION").into()]);
+                if let Err(err) = recordr {
+                    error!("Failed to record internal migration {} for {}, error: {}", migration.lib_ver, self.db_name, err);
+                    return Err("internal_migration_fail".to_owned());
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Construct a struct (VerEntry struct) which represents a row in the `schema_versions` table
+    ///
+    /// - `row: &Row` - Row to construct row struct from
+    /// - *returns*: `VerEntry` - VerEntry which represents provided Row, error if fail
+    ///
+    /// # Errors
+    /// - `RowError::MissingCols` - The Row provided does not contain all the columns necessary
+    /// - `RowError::BadStatus` - The `status` column did not match any VerStatus value
+    /// - `RowError::BadValue` - A column could not be converted to the type VerEntry uses
+    fn construct_row(row: &Row) -> Result<VerEntry, RowError> {
+        // Check that required columns are provided
+        let missing_cols: Vec<String> = VER_TABLE_COLS.iter()
+            .map(|col| col.0)
+            .filter(|col| !row.columns().iter().any(|prov| prov == col))
+            .map(|col| col.to_owned())
+            .collect();
+        if !missing_cols.is_empty() {
+            return Err(RowError::MissingCols(missing_cols))
+        }
+
+        // `updated` is stored without a time zone, Migrate always writes it in UTC
+        let updated: NaiveDateTime = get_col(row, "updated")?;
+
+        let status_str: String = get_col(row, "status")?;
+        let status = match VerStatus::from_string(&status_str) {
+            Some(status) => status,
+            None => return Err(RowError::BadStatus(status_str)),
         };
 
-        Some(VerEntry {
-            id: row.get("id"),
-            updated: updatedv,// Parse date time from ISO 8601 string (RFC-3339 == ISO-8601 in this case)
-            version: row.get("version"),
-            migration_hash: row.get("migration_hash"),
-            status: statusv,
-            lib_ver: row.get("lib_ver")
+        Ok(VerEntry {
+            id: get_col(row, "id")?,
+            updated: DateTime::from_utc(updated, UTC),
+            version: get_col(row, "version")?,
+            migration_hash: get_col(row, "migration_hash")?,
+            status,
+            lib_ver: get_col(row, "lib_ver")?,
+            name: if row.columns().iter().any(|col| col == "name") {
+                get_col(row, "name")?
+            } else {
+                None
+            },
+            duration_ms: if row.columns().iter().any(|col| col == "duration_ms") {
+                get_col(row, "duration_ms")?
+            } else {
+                None
+            }
         })
-        */
     }
 
     /// Retrieves most recent schema version information
     ///
-    /// - *returns*: `VerEntry` - VerEntry struct representing current schema version, error code if fail
+    /// - *returns*: `Option<VerEntry>` - VerEntry struct representing the highest version which was
+    ///   successfully applied, None if no version has been applied, error code if fail
     ///
     /// # Errors
     /// - `row_parse_fail` - Failed to parse retrieved row from db
     /// - `query_error` - Query to retrieve most recent schema version information failed
-    fn current_version(&self) -> Result<VerEntry, String> {
-        let currentr = self.conn.query("SELECT * FROM schema_versions ORDER BY updated, desc LIMIT 1", &[]);
+    pub fn current_version(&self) -> Result<Option<VerEntry>, String> {
+        if !self.tables_exist {
+            return Ok(None)
+        }
+
+        let currentr = self.conn.query("SELECT * FROM schema_versions WHERE status = $1
+                                        ORDER BY version DESC, updated DESC LIMIT 1", &[VerStatus::Success.as_str().into()]);
 
         match currentr {
             Ok(rows) => {
-                // Expecting exactly one row
-                if rows.is_empty() || rows.len() > 1 {
-                    return Err("incorrect_row_count")
+                if rows.is_empty() {
+                    return Ok(None)
                 }
 
-                return match DbSchemaVer::construct_row(rows.get(0)) {
-                    Ok(v) => Ok(v),
+                match DbSchemaVer::construct_row(&rows[0]) {
+                    Ok(v) => Ok(Some(v)),
                     Err(err) => {
                         error!("Failed to construct current version row, error: {}", err);
-                        return Err("row_parse_fail")
+                        Err("row_parse_fail".to_owned())
                     }
                 }
             },
             Err(err) => {
-                error!("Failed to execute query, error: {}", err.description());
-                return Err("query_error")
+                error!("Failed to execute query, error: {}", err);
+                Err("query_error".to_owned())
             }
-        };
+        }
     }
 
     /// Retrieves schema version information based on provided `version` column value
     ///
-    /// - `ver: i32` - Version to retrieve
-    /// - *returns*: `VerEntry` - VerEntry representing requested version, error code if fail
+    /// - `ver: i64` - Version to retrieve
+    /// - *returns*: `Option<VerEntry>` - VerEntry representing requested version, None if the
+    ///   version has never been recorded, error code if fail
+    ///
+    /// Does not modify the database. If the version is represented by more than one row it is
+    /// reported as an inconsistency, see `duplicates(&self)`.
     ///
     /// # Errors
-    /// `resolve_dup_rows_fail` - Failed to resolve multiple schema version rows, see resolve_dup_ver_rows(ver: i32, rows: Option<[Row]>)
+    /// `query_error` - Query to retrieve the version failed
+    /// `duplicate_versions` - Multiple rows represent the version
     /// `row_construct_fail` - Failed to construct row from db row provided
-    fn by_version_num(&self, ver: i32) -> Result<VerEntry, String> {
-        let verr = self.conn.query("SELECT * FROM schema_version WHERE version = $1", &vec![ver]);
+    pub fn by_version_num(&self, ver: i64) -> Result<Option<VerEntry>, String> {
+        if !self.tables_exist {
+            return Ok(None)
+        }
 
-        let row = match verr {
-            Ok(rows) => {
-                // Check for duplicates
-                if rows.len() > 1 {
-                    warn!("Multiple rows found for same version, merging");
-
-                    let resolver = self.resolve_dup_ver_rows(ver);
-                    match resolver {
-                        Ok(rrow) => return rrow,
-                        Err(err) => {
-                            error!("Failed to resolve duplicate schema version rows, error: {}", err);
-                            return Err("resolve_dup_rows_fail")
-                        }
-                    };
-                }
-            },
-            Err(err) => {}
+        let rows = match self.conn.query("SELECT * FROM schema_versions WHERE version = $1", &[ver.into()]) {
+            Ok(rows) => rows,
+            Err(err) => {
+                error!("Failed to execute query, error: {}", err);
+                return Err("query_error".to_owned())
+            }
         };
 
-        let contructr = DbSchemaVer::construct_row(&row);
+        // Check for duplicates
+        if rows.len() > 1 {
+            error!("{} rows represent version {} in {}, run \"repair duplicates\" to resolve", rows.len(), ver, self.db_name);
+            return Err("duplicate_versions".to_owned())
+        }
+
+        if rows.is_empty() {
+            return Ok(None)
+        }
 
-        match contructr {
-            Ok(crow) => return Ok(crow),
+        match DbSchemaVer::construct_row(&rows[0]) {
+            Ok(crow) => Ok(Some(crow)),
             Err(err) => {
-                warn!("Error constructing row from db row provided, error: {}", err.describe());
-                return Err("row_construct_fail")
+                warn!("Error constructing row from db row provided, error: {}", err);
+                Err("row_construct_fail".to_owned())
             }
         }
     }
 
-    /// Proxy method for `resolve_dup_ver_rows_backend(&self, ver: i32, rows: [Row]`
+    /// Retrieves every row of the `schema_versions` table
+    ///
+    /// - *returns*: `Vec<VerEntry>` - Rows ordered by version, error code if fail
     ///
-    /// See parent method for more information
+    /// Does not modify the database.
     ///
     /// # Errors
-    /// - `db_query_fail` - Query to retrieve version failed
-    fn resolve_dup_ver_rows(&self, ver: i32) -> Result<Row, String> {
-        let verr = self.conn.query("SELECT * FROM schema_version WHERE version = $1", &vec![ver]);
+    /// - `query_error` - Query to retrieve the rows failed
+    /// - `row_construct_fail` - Failed to construct row from db row provided
+    pub fn entries(&self) -> Result<Vec<VerEntry>, String> {
+        if !self.tables_exist {
+            return Ok(Vec::new())
+        }
 
-        match verr {
-            Ok(rows) => {
-                return self.resolve_dup_ver_rows_backend(ver, Some(rows))
+        let rows = match self.conn.query("SELECT * FROM schema_versions ORDER BY version, updated", &[]) {
+            Ok(rows) => rows,
+            Err(err) => {
+                error!("Failed to execute query, error: {}", err);
+                return Err("query_error".to_owned())
+            }
+        };
+
+        let mut entries = Vec::new();
+        for row in rows.iter() {
+            match DbSchemaVer::construct_row(row) {
+                Ok(entry) => entries.push(entry),
+                Err(err) => {
+                    warn!("Error constructing row from db row provided, error: {}", err);
+                    return Err("row_construct_fail".to_owned())
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Records that a migration has started
+    ///
+    /// - `migration: &Migration` - Migration being performed
+    /// - *returns*: Nothing, error code if fail
+    ///
+    /// Marks the migration's version as `ongoing`. A row left behind by a previous failed or
+    /// interrupted attempt at the same version is reused.
+    ///
+    /// # Errors
+    /// - `already_applied` - The version has already been applied successfully
+    /// - `record_fail` - Query to record the migration failed
+    /// - See `by_version_num(&self, ver: i64)`
+    pub fn start(&self, migration: &Migration) -> Result<(), String> {
+        let existing = self.by_version_num(migration.version)?;
+
+        if let Some(ref entry) = existing {
+            if entry.status == VerStatus::Success {
+                error!("Version {} has already been applied to {}", migration.version, self.db_name);
+                return Err("already_applied".to_owned())
+            }
+
+            warn!("Retrying version {} on {}, previous attempt left it with status {}",
+                  migration.version, self.db_name, entry.status.as_str());
+            return self.set_status(migration, VerStatus::Ongoing, None)
+        }
+
+        let insertr = self.conn.execute("INSERT INTO schema_versions
+                                             (updated, version, migration_hash, status, lib_ver, name)
+                                         VALUES
+                                             ($1, $2, $3, $4, $5, $6)",
+                                        &[now(), migration.version.into(), migration.hash.as_str().into(),
+                                          VerStatus::Ongoing.as_str().into(), LIB_VER.into(), migration.name.as_str().into()]);
+        match insertr {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                error!("Failed to record start of version {} on {}, error: {}", migration.version, self.db_name, err);
+                Err("record_fail".to_owned())
+            }
+        }
+    }
+
+    /// Updates the status of a migration's version
+    ///
+    /// - `migration: &Migration` - Migration which was performed
+    /// - `status: VerStatus` - New status
+    /// - `duration: Option<Duration>` - How long performing the migration took, None while it is
+    ///   ongoing
+    /// - *returns*: Nothing, error code if fail
+    ///
+    /// # Errors
+    /// - `record_fail` - Query to update the version failed, or the version has not been recorded
+    pub fn set_status(&self, migration: &Migration, status: VerStatus, duration: Option<Duration>) -> Result<(), String> {
+        let duration_ms = duration.map_or(Value::Null, |d| (d.as_millis() as i64).into());
+        let updater = self.conn.execute("UPDATE schema_versions
+                                         SET updated = $2, migration_hash = $3, status = $4, lib_ver = $5, name = $6,
+                                             duration_ms = $7
+                                         WHERE version = $1",
+                                        &[migration.version.into(), now(), migration.hash.as_str().into(),
+                                          status.as_str().into(), LIB_VER.into(), migration.name.as_str().into(),
+                                          duration_ms]);
+        match updater {
+            Ok(0) => {
+                error!("Can not set status of version {} on {}, it has not been recorded", migration.version, self.db_name);
+                Err("record_fail".to_owned())
             },
+            Ok(_) => Ok(()),
             Err(err) => {
-                return Err("db_query_fail")
+                error!("Failed to set status of version {} on {}, error: {}", migration.version, self.db_name, err);
+                Err("record_fail".to_owned())
             }
         }
     }
 
-    /// In the rare event that multiple rows represent the same schema version this function will resolve that version
+    /// Removes the record of a version, once the migration which provided it has been reversed
     ///
-    /// - `ver: i32` - Version with duplicate rows
-    /// - `rows: [Row]` - Array of db rows to analyse when resolving duplicate
-    /// - *returns*: `Row` - The correct row to use for the specified version, error code if fail
+    /// - `ver: i64` - Version to remove
+    /// - *returns*: Nothing, error code if fail
     ///
-    /// Resolves duplicate version rows by keeping the most recent and deleting the rest
+    /// Refuses to remove a version represented by more than one row, as the rows to keep are for
+    /// `repair duplicates` to decide.
     ///
     /// # Errors
-    /// - `no_dup_rows` - No duplicate rows where found for the supplied version
-    /// - `date_parse_fail` - Failed to parse `updated` column in a row
-    /// - `disappearing_ver_row` - The correct version row that was determined earlier in the
-    ///                             function did not survive the deletion process
-    fn resolve_dup_ver_rows_backend(&self, ver: i32, rows: [Row]) -> Result<Row, String> {
-        // Check that there are in fact duplicate rows
-        if rows.length <= 1 {
-            error!("No duplicate rows for schema version {}", ver);
-            return Err("no_dup_rows")
+    /// - `query_error` - Query to count the rows of the version failed
+    /// - `duplicate_versions` - Multiple rows represent the version
+    /// - `record_fail` - Query to remove the version failed
+    pub fn remove(&self, ver: i64) -> Result<(), String> {
+        let rows = match self.conn.query("SELECT id FROM schema_versions WHERE version = $1", &[ver.into()]) {
+            Ok(rows) => rows,
+            Err(err) => {
+                error!("Failed to execute query, error: {}", err);
+                return Err("query_error".to_owned())
+            }
+        };
+
+        if rows.len() > 1 {
+            error!("{} rows represent version {} in {}, run \"repair duplicates\" to resolve", rows.len(), ver, self.db_name);
+            return Err("duplicate_versions".to_owned())
+        }
+
+        match self.conn.execute("DELETE FROM schema_versions WHERE version = $1", &[ver.into()]) {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                error!("Failed to remove version {} from {}, error: {}", ver, self.db_name, err);
+                Err("record_fail".to_owned())
+            }
         }
+    }
 
-        // Find id of row with most recent updated date
-        // (id, updated)
-        let newest_date = (-1, None);
-        rows.iter().map(|row| {
-            let date = match DateTime.from_rfc3331(row.get("updated")) {
-                Ok(date) => date,
+    /// Finds versions which are represented by more than one row in the `schema_versions` table
+    ///
+    /// - *returns*: `Vec<DuplicateVer>` - For each duplicated version the row which should be kept
+    ///   and the rows which should be deleted, error code if fail
+    ///
+    /// The most recently updated row of a version is the one kept. Nothing is modified, see the
+    /// `repair duplicates` sub-command.
+    ///
+    /// # Errors
+    /// - `query_error` - Query to retrieve duplicated versions failed
+    /// - `row_construct_fail` - Failed to construct row from db row provided
+    pub fn duplicates(&self) -> Result<Vec<DuplicateVer>, String> {
+        let rowsr = self.conn.query("SELECT * FROM schema_versions
+                                     WHERE version IN (SELECT version FROM schema_versions
+                                                       GROUP BY version HAVING count(*) > 1)
+                                     ORDER BY version, updated DESC, id DESC", &[]);
+        let rows = match rowsr {
+            Ok(rows) => rows,
+            Err(err) => {
+                error!("Failed to retrieve duplicated versions, error: {}", err);
+                return Err("query_error".to_owned())
+            }
+        };
+
+        let mut dups: Vec<DuplicateVer> = Vec::new();
+        for row in rows.iter() {
+            let entry = match DbSchemaVer::construct_row(row) {
+                Ok(entry) => entry,
                 Err(err) => {
-                    error!("Error parsing DateTime from date string \"{}\" (row.id: {}), error: {}", row.get("updated"), row.get("id"), err.description());
-                    return Err("date_parse_fail")
+                    warn!("Error constructing row from db row provided, error: {}", err);
+                    return Err("row_construct_fail".to_owned())
                 }
             };
 
-            if newest_date == None || date > newest_date {
-                newest_date = (row.get("id"), Some(date));
+            // Rows are ordered by updated, so the first row of each version is the most recent
+            match dups.last_mut() {
+                Some(ref mut dup) if dup.keep.version == entry.version => {
+                    dup.delete.push(entry);
+                    continue;
+                },
+                _ => {}
             }
-        });
-
-        // Store history of all db transactions for after action report
-        // (deletes, bad_deletes, restores, bad_restores)
-        let transactions_status = (0, 0, 0, 0);
-
-        // Delete non most recent rows
-        let correct_ver_row: Option<Row> = None;
-        rows.iter().map(|row| {
-            let id = row.get("id");
-
-            if id == newest_date.0 {// If the current row is the row determined to me the most recent, mark it as so and do nothing
-                correct_ver_row = Some(row);
-            } else {// If not most recent row (aka a duplicate) delete from table
-                let delr = self.conn.execute("DELETE FROM schema_versions WHERE id = $1", &vec![id]);
-
-                match delr {
-                    Ok(rows_changed) => {
-                        if rows_changed != 1 {
-                            error!("Unexpected behavior when deleting duplicate schema version entry, rows changed: {} (Should be 1)", rows_changed);
-                            transactions_status.1 += 1;
-                            return Err("dup_del_fail")
-                        }
-
-                        transactions_status.0 += 1;
-                    },
-                    Err(err) => {
-                        transactions_status.1 += 1;
-                        error!("Failed to execute query to delete duplicate schema version entry, error: {}", err.describe());
-                        return Err("dup_del_fail")
-                    }
-                }
+
+            dups.push(DuplicateVer {
+                keep: entry,
+                delete: Vec::new()
+            });
+        }
+
+        Ok(dups)
+    }
+
+    /// Takes the lock which serializes changes to the bookkeeping tables
+    ///
+    /// - *returns*: Nothing, error code if fail
+    ///
+    /// Must be called inside a transaction, the lock is released when the transaction ends.
+    ///
+    /// # Errors
+    /// - `lock_fail` - Failed to acquire the bookkeeping lock
+    pub fn lock(&self) -> Result<(), String> {
+        match self.conn.lock() {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                error!("Failed to acquire bookkeeping lock for {}, error: {}", self.db_name, err);
+                Err("lock_fail".to_owned())
+            }
+        }
+    }
+
+    /// Deletes a single row of the `schema_versions` table
+    ///
+    /// - `id: i32` - Id of the row to delete
+    /// - *returns*: Nothing, error code if fail
+    ///
+    /// # Errors
+    /// - `record_fail` - Query to delete the row failed, or the row did not exist
+    pub fn delete_entry(&self, id: i32) -> Result<(), String> {
+        match self.conn.execute("DELETE FROM schema_versions WHERE id = $1", &[id.into()]) {
+            Ok(1) => Ok(()),
+            Ok(rows_changed) => {
+                error!("Unexpected behavior when deleting schema version entry {}, rows changed: {} (Should be 1)", id, rows_changed);
+                Err("record_fail".to_owned())
+            },
+            Err(err) => {
+                error!("Failed to delete schema version entry {} from {}, error: {}", id, self.db_name, err);
+                Err("record_fail".to_owned())
             }
-        });
-
-        // Deal with the highly unlikely case that the code above just deleted every single row of
-        // the specified version.
-        //
-        // However unlikely it may be (Maybe only possible via something crazly unlikely like a
-        // random bit being flipped in RAM due to a hardware failure, idk :/), still need to support
-        // this case, this table is kinda important :)
-       if correct_ver_row == None {
-           error!("Could not find correct version entry row, restoring rows");
-
-           let columns = vec!["id", "updated", "version", "migration_hash", "status", "lib_version"];
-
-           // Put values into array in order expected by SQL VALUES
-           let mut value_tuples: Vec<Vec<String>> = Vec::new();
-
-           rows.iter().map(|row| {
-               let mut values: Vec<String> = Vec::new();
-
-               columns.iter().map(|column| {
-                   values.push(row.get(column));
-               });
-
-               value_tuples.push(values);
-           });
-
-           // Convert each tuple in value_tuples array into SQL format
-           let mut value_blocks: Vec<String> = Vec::new();
-           value_blocks.iter().map(|block| {
-               value_blocks.push(format!("({})", block.join(", ")));
-           });
-
-           // Generate a list of value tuples in SQL format
-           let mut value_blocks_str = "";
-           let i = 0;
-           value_blocks.map(|block| {
-               let mut separator = ",\n";
-
-               if i == value_blocks.len() {
-                   separator = "\n";
-               }
-
-               value_blocks_str = format!("{}{}", block.join(", "), separator);
-               i += 1;
-           });
-
-           // Execute query
-           let query_str = r#"INSERT INTO schema_versions
-               ($1)
-               VALUES
-                    $2
-               ON CONFLICT DO UPDATE"#;
-
-           let queryr = self.conn.execute(query_str, &vec![columns.join(", "), value_blocks_str]);
-
-           match queryr {
-               Ok(rows_upserted) => {
-                   transactions_status.3 += rows_upserted;
-               },
-               Err(err) => {
-                   error!("Error restoring schema version rows, error: {}", err.describe());
-                   transactions_status.4 += value_tuples.len();
-               }
-           };
-
-           // Print after action report
-           error!("Finished restoring rows, database hopefully now in state it was before attempted dup resolve, \
-                    {tot_restores}/{tot_deletes} deleted rows restored \
-                    (row deletions: {deletes}, failed deletions: {bad_deletes}, restores: {restores}, failed restores: {bad_restores})",
-                tot_restores=transactions_status.3 - transactions_status.4,
-                tot_deletes=transactions_status.0 - transactions_status.1,
-                deletes=transactions_status.0,
-                bad_deletes=transactions_status.1,
-                restores=transactions_status.3,
-                bad_restores=transactions_status.4);
-
-           return Err("disappearing_ver_row")
-       };
-
-        return Ok(correct_ver_row.unwrap())
+        }
     }
 }
+
+/// Reads a column of a `schema_versions` row
+///
+/// - `row: &Row` - Row to read from
+/// - `col: &str` - Name of column to read
+/// - *returns*: `T` - Value of the column, error if the value is not a `T`
+fn get_col<T: FromValue>(row: &Row, col: &str) -> Result<T, RowError> {
+    row.get(col).map_err(|err| RowError::BadValue(col.to_owned(), err))
+}
+
+/// Current time in UTC, the time zone bookkeeping timestamps are stored in
+pub fn now() -> Value {
+    UTC::now().naive_utc().into()
+}
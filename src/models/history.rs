@@ -0,0 +1,231 @@
+//! The `history` module records and retrieves the append-only log of changes made to the schema.
+use std::env;
+use std::fs;
+use std::process;
+use std::time::Duration;
+
+use chrono::datetime::DateTime;
+use chrono::naive::datetime::NaiveDateTime;
+use chrono::UTC;
+
+use drivers::{Driver, Row};
+use models::dbschema::{self, VerEntry};
+use models::migration::Migration;
+
+/// Kinds of changes recorded in the `schema_history` table
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HistoryAction {
+    /// A migration was performed
+    Apply,
+    /// A migration was reversed
+    Rollback,
+    /// Performing or reversing a migration failed
+    Fail,
+    /// The bookkeeping tables were repaired
+    Repair,
+}
+
+impl HistoryAction {
+    /// Value of the `action` column which represents the action
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            HistoryAction::Apply => "apply",
+            HistoryAction::Rollback => "rollback",
+            HistoryAction::Fail => "fail",
+            HistoryAction::Repair => "repair",
+        }
+    }
+}
+
+/// A change to be recorded in the `schema_history` table
+pub struct HistoryEvent {
+    pub action: HistoryAction,
+    pub version: i64,
+    pub name: Option<String>,
+    pub migration_hash: Option<String>,
+    pub duration: Option<Duration>,
+    pub error: Option<String>,
+}
+
+impl HistoryEvent {
+    /// Creates an event for a change which involved a migration
+    pub fn for_migration(action: HistoryAction, migration: &Migration) -> HistoryEvent {
+        HistoryEvent {
+            action,
+            version: migration.version,
+            name: Some(migration.name.clone()),
+            migration_hash: Some(migration.hash.clone()),
+            duration: None,
+            error: None,
+        }
+    }
+
+    /// Creates an event for a change which involved a row of the `schema_versions` table
+    pub fn for_entry(action: HistoryAction, entry: &VerEntry) -> HistoryEvent {
+        HistoryEvent {
+            action,
+            version: entry.version,
+            name: entry.name.clone(),
+            migration_hash: Some(entry.migration_hash.clone()),
+            duration: None,
+            error: None,
+        }
+    }
+
+    /// Sets how long the change took
+    pub fn duration(mut self, duration: Duration) -> HistoryEvent {
+        self.duration = Some(duration);
+        self
+    }
+
+    /// Sets the error which caused the change to fail
+    pub fn error(mut self, error: &str) -> HistoryEvent {
+        self.error = Some(error.to_owned());
+        self
+    }
+}
+
+/// A row of the `schema_history` table
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct HistoryEntry {
+    pub id: i32,
+    pub recorded: DateTime<UTC>,
+    pub version: i64,
+    pub name: Option<String>,
+    pub action: String,
+    pub migration_hash: Option<String>,
+    pub run_by: String,
+    pub db_user: String,
+    pub host: String,
+    pub duration_ms: Option<i64>,
+    pub tool_version: String,
+    pub error: Option<String>,
+}
+
+/// Selects every column of the `schema_history` table
+const HISTORY_SELECT: &str = "SELECT id, recorded, version, name, action, migration_hash, run_by, db_user, host,
+                                     duration_ms, tool_version, error
+                              FROM schema_history";
+
+/// Interface to the `schema_history` table, which `DbSchemaVer::bootstrap` creates
+pub struct History<'a> {
+    conn: &'a dyn Driver
+}
+
+impl<'a> History<'a> {
+    pub fn new(conn: &'a dyn Driver) -> History<'a> {
+        History {
+            conn
+        }
+    }
+
+    /// Appends an event to the history
+    ///
+    /// - `event: &HistoryEvent` - Change to record
+    /// - *returns*: Nothing, error code if fail
+    ///
+    /// The event is recorded along with the user and host running Migrate and Migrate's version. If
+    /// called inside a transaction the event is only recorded if the transaction commits.
+    ///
+    /// # Errors
+    /// - `history_record_fail` - Query to record the event failed
+    pub fn record(&self, event: &HistoryEvent) -> Result<(), String> {
+        let duration_ms = event.duration.map(|d| d.as_millis() as i64);
+
+        let recordr = self.conn.execute("INSERT INTO schema_history
+                                             (recorded, version, name, action, migration_hash, run_by, host,
+                                              duration_ms, tool_version, error)
+                                         VALUES
+                                             ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+                                        &[dbschema::now(), event.version.into(), event.name.clone().into(),
+                                          event.action.as_str().into(), event.migration_hash.clone().into(),
+                                          local_user().into(), local_host().into(), duration_ms.into(),
+                                          env!("CARGO_PKG_VERSION").into(), event.error.clone().into()]);
+        match recordr {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                error!("Failed to record {} of version {} in history, error: {}", event.action.as_str(), event.version, err);
+                Err("history_record_fail".to_owned())
+            }
+        }
+    }
+
+    /// Retrieves the most recent history
+    ///
+    /// - `version: Option<i64>` - Only retrieve events for this version
+    /// - `limit: i64` - Maximum number of events to retrieve
+    /// - *returns*: `Vec<HistoryEntry>` - Events, oldest first, error code if fail
+    ///
+    /// # Errors
+    /// - `history_query_fail` - Query to retrieve history failed
+    pub fn recent(&self, version: Option<i64>, limit: i64) -> Result<Vec<HistoryEntry>, String> {
+        let rowsr = match version {
+            Some(version) => self.conn.query(&format!("{} WHERE version = $1 ORDER BY id DESC LIMIT $2", HISTORY_SELECT),
+                                             &[version.into(), limit.into()]),
+            None => self.conn.query(&format!("{} ORDER BY id DESC LIMIT $1", HISTORY_SELECT), &[limit.into()]),
+        };
+        let rows = match rowsr {
+            Ok(rows) => rows,
+            Err(err) => {
+                error!("Failed to retrieve history, error: {}", err);
+                return Err("history_query_fail".to_owned())
+            }
+        };
+
+        let entriesr: Result<Vec<HistoryEntry>, String> = rows.iter().map(construct_entry).collect();
+        let mut entries = match entriesr {
+            Ok(entries) => entries,
+            Err(err) => {
+                error!("Failed to read history, error: {}", err);
+                return Err("history_query_fail".to_owned())
+            }
+        };
+        entries.reverse();
+
+        Ok(entries)
+    }
+}
+
+/// Constructs a HistoryEntry from a row of the `schema_history` table
+fn construct_entry(row: &Row) -> Result<HistoryEntry, String> {
+    let recorded: NaiveDateTime = row.get("recorded")?;
+
+    Ok(HistoryEntry {
+        id: row.get("id")?,
+        recorded: DateTime::from_utc(recorded, UTC),
+        version: row.get("version")?,
+        name: row.get("name")?,
+        action: row.get("action")?,
+        migration_hash: row.get("migration_hash")?,
+        run_by: row.get("run_by")?,
+        db_user: row.get("db_user")?,
+        host: row.get("host")?,
+        duration_ms: row.get("duration_ms")?,
+        tool_version: row.get("tool_version")?,
+        error: row.get("error")?,
+    })
+}
+
+/// Name of the user running Migrate
+fn local_user() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_owned())
+}
+
+/// Name of the host running Migrate
+fn local_host() -> String {
+    if let Ok(host) = env::var("HOSTNAME") {
+        return host;
+    }
+
+    if let Ok(host) = fs::read_to_string("/etc/hostname") {
+        return host.trim().to_owned();
+    }
+
+    match process::Command::new("hostname").output() {
+        Ok(out) => String::from_utf8_lossy(&out.stdout).trim().to_owned(),
+        Err(_) => "unknown".to_owned(),
+    }
+}
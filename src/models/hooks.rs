@@ -0,0 +1,214 @@
+//! The `hooks` module runs shell commands and SQL files around migrations.
+//!
+//! Hooks are configured in a `hooks.ini` file in the migrations directory, with a section per stage
+//! of a run. Each section runs a SQL file, a shell command, or both, the SQL file first. SQL files
+//! are relative to the migrations directory and can not be in a subdirectory, those are migrations.
+//! Commands run from the current directory:
+//!
+//! ```ini
+//! [after_each]
+//! sql = analyze.sql
+//!
+//! [on_failure]
+//! command = ./scripts/notify.sh
+//! ```
+//!
+//! Commands get what the run is doing in `MIGRATE_*` environment variables, see `HookContext`. Their
+//! output goes to stderr, so it never mixes with a command's output.
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use ini::Ini;
+
+use drivers::Driver;
+
+/// Name of the file in a migrations directory which configures hooks
+pub const CONFIG_FILE: &str = "hooks.ini";
+
+/// Stages of a run hooks can run at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stage {
+    /// Before the first migration of a run
+    BeforeRun,
+    /// Before each migration is performed or reversed
+    BeforeEach,
+    /// After each migration is performed or reversed
+    AfterEach,
+    /// After every migration of a run succeeded
+    AfterRun,
+    /// After a run failed
+    OnFailure,
+}
+
+/// Every stage, in the order they run
+pub const STAGES: &[Stage] = &[Stage::BeforeRun, Stage::BeforeEach, Stage::AfterEach, Stage::AfterRun, Stage::OnFailure];
+
+impl Stage {
+    /// Name of the stage, as used for `hooks.ini` sections
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Stage::BeforeRun => "before_run",
+            Stage::BeforeEach => "before_each",
+            Stage::AfterEach => "after_each",
+            Stage::AfterRun => "after_run",
+            Stage::OnFailure => "on_failure",
+        }
+    }
+}
+
+/// What a stage runs
+#[derive(Debug, Default)]
+struct Hook {
+    sql: Option<PathBuf>,
+    command: Option<String>,
+}
+
+/// What a run is doing when a hook runs, passed to commands as environment variables
+#[derive(Debug, Default, Clone)]
+pub struct HookContext {
+    /// `MIGRATE_DATABASE` - Name of the database
+    pub database: String,
+    /// `MIGRATE_DRIVER` - Driver, postgres, sqlite or mysql
+    pub driver: String,
+    /// `MIGRATE_ENVIRONMENT` - Environment selected by `--environment/-e`
+    pub environment: Option<String>,
+    /// `MIGRATE_CURRENT_VERSION` - Version the database was at when the run started
+    pub current_version: Option<i64>,
+    /// `MIGRATE_TARGET_VERSION` - Version the run is trying to reach
+    pub target_version: Option<i64>,
+    /// `MIGRATE_VERSION` - Version of the migration, for `before_each` and `after_each`, and
+    /// `on_failure` if a migration failed
+    pub version: Option<i64>,
+    /// `MIGRATE_NAME` - Name of the migration
+    pub name: Option<String>,
+    /// `MIGRATE_ACTION` - apply or rollback
+    pub action: Option<&'static str>,
+    /// `MIGRATE_DURATION_MS` - How long the migration took, for `after_each` and `on_failure`
+    pub duration_ms: Option<u128>,
+    /// `MIGRATE_ERROR` - Why the run failed, for `on_failure`
+    pub error: Option<String>,
+}
+
+impl HookContext {
+    /// Environment variables for a stage's command
+    fn vars(&self, stage: Stage) -> Vec<(&'static str, String)> {
+        let mut vars = vec![
+            ("MIGRATE_HOOK", stage.as_str().to_owned()),
+            ("MIGRATE_DATABASE", self.database.clone()),
+            ("MIGRATE_DRIVER", self.driver.clone()),
+        ];
+
+        let optional = [
+            ("MIGRATE_ENVIRONMENT", self.environment.clone()),
+            ("MIGRATE_CURRENT_VERSION", self.current_version.map(|v| v.to_string())),
+            ("MIGRATE_TARGET_VERSION", self.target_version.map(|v| v.to_string())),
+            ("MIGRATE_VERSION", self.version.map(|v| v.to_string())),
+            ("MIGRATE_NAME", self.name.clone()),
+            ("MIGRATE_ACTION", self.action.map(|action| action.to_owned())),
+            ("MIGRATE_DURATION_MS", self.duration_ms.map(|ms| ms.to_string())),
+            ("MIGRATE_ERROR", self.error.clone()),
+        ];
+        vars.extend(optional.iter().filter_map(|(name, value)| value.clone().map(|value| (*name, value))));
+
+        vars
+    }
+}
+
+/// Hooks configured for a migrations directory
+#[derive(Debug, Default)]
+pub struct Hooks {
+    hooks: Vec<(Stage, Hook)>
+}
+
+impl Hooks {
+    /// Loads hooks from the `hooks.ini` file of a migrations directory
+    ///
+    /// - `migrations_dir: &str` - Migrations directory, see the `--migrations-dir/-m` option
+    /// - *returns*: `Hooks` - Configured hooks, none if the directory has no `hooks.ini` file, error
+    ///   message if fail
+    ///
+    /// # Errors
+    /// - The `hooks.ini` file can not be loaded
+    /// - A section is not a stage, or a key is neither `sql` nor `command`
+    pub fn load(migrations_dir: &str) -> Result<Hooks, String> {
+        let path = Path::new(migrations_dir).join(CONFIG_FILE);
+        if !path.exists() {
+            return Ok(Hooks::default());
+        }
+
+        let conf = match Ini::load_from_file(&path.to_string_lossy()) {
+            Ok(conf) => conf,
+            Err(err) => return Err(format!("Failed to load \"{}\": {}", path.display(), err))
+        };
+
+        let stage_names: Vec<&str> = STAGES.iter().map(|stage| stage.as_str()).collect();
+        let mut hooks = Vec::new();
+        for (section, props) in conf.iter() {
+            let section = match *section {
+                Some(ref section) => section.as_str(),
+                None if props.is_empty() => continue,
+                None => ""
+            };
+            let stage = match STAGES.iter().find(|stage| stage.as_str() == section) {
+                Some(&stage) => stage,
+                None => return Err(format!("Unknown hook stage \"{}\" in \"{}\", expected one of {}", section,
+                                           path.display(), stage_names.join(", ")))
+            };
+
+            let mut hook = Hook::default();
+            for (key, value) in props.iter() {
+                match key.as_str() {
+                    "sql" => hook.sql = Some(Path::new(migrations_dir).join(value)),
+                    "command" => hook.command = Some(value.to_owned()),
+                    _ => return Err(format!("Unknown key \"{}\" for hook {} in \"{}\", expected sql or command", key,
+                                            section, path.display()))
+                }
+            }
+            hooks.push((stage, hook));
+        }
+
+        Ok(Hooks { hooks })
+    }
+
+    /// Runs the hook configured for a stage, if any
+    ///
+    /// - `stage: Stage` - Stage the run is at
+    /// - `conn: &Driver` - Database the SQL file runs on, outside any transaction
+    /// - `context: &HookContext` - What the run is doing
+    /// - *returns*: Error message if the SQL file fails or the command exits unsuccessfully
+    pub fn run(&self, stage: Stage, conn: &dyn Driver, context: &HookContext) -> Result<(), String> {
+        for (_, hook) in self.hooks.iter().filter(|&&(hook_stage, _)| hook_stage == stage) {
+            if let Some(ref path) = hook.sql {
+                debug!("Running {} hook {}", stage.as_str(), path.display());
+
+                let sql = match fs::read_to_string(path) {
+                    Ok(sql) => sql,
+                    Err(err) => return Err(format!("Failed to read {} hook \"{}\": {}", stage.as_str(), path.display(), err))
+                };
+                if let Err(err) = conn.batch_execute(&sql) {
+                    return Err(format!("Hook {} \"{}\" failed: {}", stage.as_str(), path.display(), err))
+                }
+            }
+
+            if let Some(ref command) = hook.command {
+                debug!("Running {} hook `{}`", stage.as_str(), command);
+
+                let status = Command::new("sh")
+                    .arg("-c")
+                    .arg(command)
+                    .envs(context.vars(stage))
+                    .stdout(Stdio::from(io::stderr()))
+                    .status();
+                match status {
+                    Ok(status) if status.success() => {},
+                    Ok(status) => return Err(format!("Hook {} `{}` failed: {}", stage.as_str(), command, status)),
+                    Err(err) => return Err(format!("Failed to run {} hook `{}`: {}", stage.as_str(), command, err)),
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,229 @@
+//! The `internal_migrations` module lists changes Migrate makes to its own bookkeeping tables.
+//!
+//! Each entry upgrades the layout of the `schema_versions` table (and any tables alongside it) by
+//! one `lib_ver`. The layout version a database is currently at is recorded in the
+//! `schema_versions_lib` table, see `DbSchemaVer::upgrade`. Every entry provides SQL for each
+//! driver, see `drivers::Dialect`.
+//!
+//! MySQL commits each DDL statement as it runs, so a MySQL internal migration which fails part way
+//! is not rolled back and is run again in full by the next upgrade. Its SQL must either be a
+//! single statement or only contain statements which are safe to repeat.
+
+use drivers::Dialect;
+
+/// A change to the tables Migrate uses to track schema versions
+pub struct InternalMigration {
+    /// Layout version the database is at after the migration runs
+    pub lib_ver: i32,
+    /// Short description of the change, logged when the migration runs
+    pub description: &'static str,
+    /// SQL which performs the change on Postgres
+    pub postgres: &'static str,
+    /// SQL which performs the change on SQLite
+    pub sqlite: &'static str,
+    /// SQL which performs the change on MySQL and MariaDB
+    pub mysql: &'static str,
+}
+
+impl InternalMigration {
+    /// SQL which performs the change on a database speaking `dialect`
+    pub fn sql(&self, dialect: Dialect) -> &'static str {
+        match dialect {
+            Dialect::Postgres => self.postgres,
+            Dialect::Sqlite => self.sqlite,
+            Dialect::Mysql => self.mysql,
+        }
+    }
+}
+
+/// Layout created by `DbSchemaVer::bootstrap`, before any internal migration runs
+pub const BOOTSTRAP_LIB_VER: i32 = 1;
+
+/// Layout version this build of Migrate expects
+pub const LIB_VER: i32 = 9;
+
+/// Internal migrations, ordered by `lib_ver`
+pub const INTERNAL_MIGRATIONS: &[InternalMigration] = &[
+    InternalMigration {
+        lib_ver: 2,
+        description: "Generate schema_versions ids, record migration names and index versions",
+        postgres: "CREATE SEQUENCE schema_versions_id_seq OWNED BY schema_versions.id;
+              SELECT setval('schema_versions_id_seq', COALESCE((SELECT MAX(id) FROM schema_versions), 0) + 1, false);
+              ALTER TABLE schema_versions ALTER COLUMN id SET DEFAULT nextval('schema_versions_id_seq');
+              ALTER TABLE schema_versions ADD COLUMN name TEXT;
+              CREATE INDEX schema_versions_version_idx ON schema_versions (version);",
+        // INTEGER PRIMARY KEY columns already generate ids in SQLite
+        sqlite: "ALTER TABLE schema_versions ADD COLUMN name TEXT;
+                 CREATE INDEX schema_versions_version_idx ON schema_versions (version);",
+        // One statement, so a failure leaves nothing behind
+        mysql: "ALTER TABLE schema_versions
+                    MODIFY id INT NOT NULL AUTO_INCREMENT,
+                    ADD COLUMN name TEXT,
+                    ADD INDEX schema_versions_version_idx (version);",
+    },
+    InternalMigration {
+        lib_ver: 3,
+        description: "Record every change to the schema in an append-only schema_history table",
+        postgres: "CREATE TABLE schema_history (
+                  id SERIAL PRIMARY KEY,
+                  recorded TIMESTAMP NOT NULL,
+                  version INT NOT NULL,
+                  name TEXT,
+                  action TEXT NOT NULL CHECK (action IN ('apply', 'rollback', 'fail', 'repair')),
+                  migration_hash TEXT,
+                  run_by TEXT NOT NULL,
+                  db_user TEXT NOT NULL DEFAULT current_user,
+                  host TEXT NOT NULL,
+                  duration_ms BIGINT,
+                  tool_version TEXT NOT NULL,
+                  error TEXT);
+              CREATE INDEX schema_history_version_idx ON schema_history (version);
+              CREATE FUNCTION schema_history_append_only() RETURNS trigger AS $$
+              BEGIN
+                  RAISE EXCEPTION 'schema_history is append-only';
+              END
+              $$ LANGUAGE plpgsql;
+              CREATE TRIGGER schema_history_append_only BEFORE UPDATE OR DELETE ON schema_history
+                  FOR EACH STATEMENT EXECUTE PROCEDURE schema_history_append_only();",
+        // SQLite has no database users, db_user is left empty
+        sqlite: "CREATE TABLE schema_history (
+                     id INTEGER PRIMARY KEY AUTOINCREMENT,
+                     recorded TIMESTAMP NOT NULL,
+                     version INTEGER NOT NULL,
+                     name TEXT,
+                     action TEXT NOT NULL CHECK (action IN ('apply', 'rollback', 'fail', 'repair')),
+                     migration_hash TEXT,
+                     run_by TEXT NOT NULL,
+                     db_user TEXT NOT NULL DEFAULT '',
+                     host TEXT NOT NULL,
+                     duration_ms INTEGER,
+                     tool_version TEXT NOT NULL,
+                     error TEXT);
+                 CREATE INDEX schema_history_version_idx ON schema_history (version);
+                 CREATE TRIGGER schema_history_no_update BEFORE UPDATE ON schema_history
+                 BEGIN
+                     SELECT RAISE(ABORT, 'schema_history is append-only');
+                 END;
+                 CREATE TRIGGER schema_history_no_delete BEFORE DELETE ON schema_history
+                 BEGIN
+                     SELECT RAISE(ABORT, 'schema_history is append-only');
+                 END;",
+        // Column defaults can not call CURRENT_USER() in MySQL, db_user is filled in by a trigger.
+        // Creating triggers can fail (eg: binary logging without log_bin_trust_function_creators)
+        // after the table was committed, so every statement is safe to run again.
+        mysql: "CREATE TABLE IF NOT EXISTS schema_history (
+                    id INT AUTO_INCREMENT PRIMARY KEY,
+                    recorded DATETIME(6) NOT NULL,
+                    version INT NOT NULL,
+                    name TEXT,
+                    action VARCHAR(16) NOT NULL CHECK (action IN ('apply', 'rollback', 'fail', 'repair')),
+                    migration_hash TEXT,
+                    run_by TEXT NOT NULL,
+                    db_user VARCHAR(288) NOT NULL DEFAULT '',
+                    host TEXT NOT NULL,
+                    duration_ms BIGINT,
+                    tool_version TEXT NOT NULL,
+                    error TEXT,
+                    INDEX schema_history_version_idx (version));
+                DROP TRIGGER IF EXISTS schema_history_db_user;
+                CREATE TRIGGER schema_history_db_user BEFORE INSERT ON schema_history
+                    FOR EACH ROW SET NEW.db_user = CURRENT_USER();
+                DROP TRIGGER IF EXISTS schema_history_no_update;
+                CREATE TRIGGER schema_history_no_update BEFORE UPDATE ON schema_history
+                    FOR EACH ROW SIGNAL SQLSTATE '45000' SET MESSAGE_TEXT = 'schema_history is append-only';
+                DROP TRIGGER IF EXISTS schema_history_no_delete;
+                CREATE TRIGGER schema_history_no_delete BEFORE DELETE ON schema_history
+                    FOR EACH ROW SIGNAL SQLSTATE '45000' SET MESSAGE_TEXT = 'schema_history is append-only';",
+    },
+    InternalMigration {
+        lib_ver: 4,
+        description: "Widen versions to 64 bits so they can hold timestamps",
+        postgres: "ALTER TABLE schema_versions ALTER COLUMN version TYPE BIGINT;
+                   ALTER TABLE schema_history ALTER COLUMN version TYPE BIGINT;",
+        // INTEGER columns already hold 64 bit values in SQLite
+        sqlite: "",
+        mysql: "ALTER TABLE schema_versions MODIFY version BIGINT NOT NULL;
+                ALTER TABLE schema_history MODIFY version BIGINT NOT NULL;",
+    },
+    InternalMigration {
+        lib_ver: 5,
+        description: "Record how long each migration took next to its status",
+        postgres: "ALTER TABLE schema_versions ADD COLUMN duration_ms BIGINT;",
+        sqlite: "ALTER TABLE schema_versions ADD COLUMN duration_ms INTEGER;",
+        mysql: "ALTER TABLE schema_versions ADD COLUMN duration_ms BIGINT;",
+    },
+    InternalMigration {
+        lib_ver: 6,
+        description: "Track repeatable migrations by name and hash in a schema_repeatables table",
+        postgres: "CREATE TABLE schema_repeatables (
+                      name TEXT PRIMARY KEY NOT NULL,
+                      updated TIMESTAMP NOT NULL,
+                      migration_hash TEXT NOT NULL,
+                      duration_ms BIGINT);",
+        sqlite: "CREATE TABLE schema_repeatables (
+                     name TEXT PRIMARY KEY NOT NULL,
+                     updated TIMESTAMP NOT NULL,
+                     migration_hash TEXT NOT NULL,
+                     duration_ms INTEGER);",
+        // TEXT columns can not be primary keys in MySQL without a prefix length
+        mysql: "CREATE TABLE IF NOT EXISTS schema_repeatables (
+                    name VARCHAR(255) PRIMARY KEY NOT NULL,
+                    updated DATETIME(6) NOT NULL,
+                    migration_hash TEXT NOT NULL,
+                    duration_ms BIGINT);",
+    },
+    InternalMigration {
+        lib_ver: 7,
+        description: "Track seeds loaded into the database by name in a schema_seeds table",
+        postgres: "CREATE TABLE schema_seeds (
+                      name TEXT PRIMARY KEY NOT NULL,
+                      environment TEXT NOT NULL,
+                      loaded TIMESTAMP NOT NULL,
+                      seed_hash TEXT NOT NULL,
+                      duration_ms BIGINT);",
+        sqlite: "CREATE TABLE schema_seeds (
+                     name TEXT PRIMARY KEY NOT NULL,
+                     environment TEXT NOT NULL,
+                     loaded TIMESTAMP NOT NULL,
+                     seed_hash TEXT NOT NULL,
+                     duration_ms INTEGER);",
+        mysql: "CREATE TABLE IF NOT EXISTS schema_seeds (
+                    name VARCHAR(255) PRIMARY KEY NOT NULL,
+                    environment TEXT NOT NULL,
+                    loaded DATETIME(6) NOT NULL,
+                    seed_hash TEXT NOT NULL,
+                    duration_ms BIGINT);",
+    },
+    InternalMigration {
+        lib_ver: 8,
+        description: "Refuse to truncate the append-only schema_history table",
+        postgres: "DROP TRIGGER schema_history_append_only ON schema_history;
+                   CREATE TRIGGER schema_history_append_only BEFORE UPDATE OR DELETE OR TRUNCATE ON schema_history
+                       FOR EACH STATEMENT EXECUTE PROCEDURE schema_history_append_only();",
+        // SQLite has no TRUNCATE, a DELETE without a WHERE clause already fires the delete trigger.
+        // MySQL never fires triggers on TRUNCATE, it is left to privileges.
+        sqlite: "",
+        mysql: "",
+    },
+    InternalMigration {
+        lib_ver: 9,
+        description: "Track seeds by environment and name, environments sharing a database may use the same names",
+        postgres: "ALTER TABLE schema_seeds DROP CONSTRAINT schema_seeds_pkey, ADD PRIMARY KEY (environment, name);",
+        // SQLite can not change the primary key of a table, it is rebuilt
+        sqlite: "CREATE TABLE schema_seeds_new (
+                     name TEXT NOT NULL,
+                     environment TEXT NOT NULL,
+                     loaded TIMESTAMP NOT NULL,
+                     seed_hash TEXT NOT NULL,
+                     duration_ms INTEGER,
+                     PRIMARY KEY (environment, name));
+                 INSERT INTO schema_seeds_new (name, environment, loaded, seed_hash, duration_ms)
+                     SELECT name, environment, loaded, seed_hash, duration_ms FROM schema_seeds;
+                 DROP TABLE schema_seeds;
+                 ALTER TABLE schema_seeds_new RENAME TO schema_seeds;",
+        mysql: "ALTER TABLE schema_seeds
+                    MODIFY environment VARCHAR(255) NOT NULL,
+                    DROP PRIMARY KEY,
+                    ADD PRIMARY KEY (environment, name);",
+    },
+];
@@ -0,0 +1,146 @@
+//! The `logger` module installs the logger the `log` macros used throughout Migrate write to.
+//!
+//! Logs are written to standard error, so they never mix with a command's output. By default they
+//! are human readable lines. With `--log-format json` every record is a JSON object on its own line,
+//! and every migration step (performing or reversing a migration) is logged as a structured event
+//! with its version, name, duration and status, see `step`.
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use chrono::UTC;
+use log::{self, LogLevel, LogLevelFilter, LogMetadata, LogRecord};
+
+use models::history::{HistoryAction, HistoryEvent};
+
+/// Target of the records `step` logs
+const STEP_TARGET: &str = "migrate::step";
+
+/// If records are formatted as JSON, see `init`
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// How log records are formatted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    Human,
+    Json,
+}
+
+impl LogFormat {
+    /// Parses the value of the `--log-format` option
+    pub fn from_name(name: &str) -> Option<LogFormat> {
+        match name {
+            "human" => Some(LogFormat::Human),
+            "json" => Some(LogFormat::Json),
+            _ => None
+        }
+    }
+}
+
+struct Logger {
+    level: LogLevelFilter
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &LogMetadata) -> bool {
+        // Records from dependencies are only interesting when tracing
+        metadata.level() <= self.level
+            && (metadata.target().starts_with("migrate") || self.level == LogLevelFilter::Trace)
+    }
+
+    fn log(&self, record: &LogRecord) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = if !JSON.load(Ordering::Relaxed) {
+            format!("{:<5} {}", record.level(), record.args())
+        } else if record.target() == STEP_TARGET {
+            // Already a JSON object, see `step`
+            record.args().to_string()
+        } else {
+            json!({
+                "time": UTC::now().to_rfc3339(),
+                "level": record.level().to_string().to_lowercase(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            }).to_string()
+        };
+
+        // Nowhere to report a failure to write a log
+        let _ = writeln!(io::stderr(), "{}", line);
+    }
+}
+
+/// Installs the logger
+///
+/// - `verbosity: i64` - Number of `-v` options minus the number of `-q` options. `0` logs info,
+///   warnings and errors, every step up or down logs more or less
+/// - `format: LogFormat` - How records are formatted, see the `--log-format` option
+/// - *returns*: Error message if a logger is already installed
+pub fn init(verbosity: i64, format: LogFormat) -> Result<(), String> {
+    let level = match verbosity {
+        v if v <= -2 => LogLevelFilter::Error,
+        -1 => LogLevelFilter::Warn,
+        0 => LogLevelFilter::Info,
+        1 => LogLevelFilter::Debug,
+        _ => LogLevelFilter::Trace,
+    };
+
+    JSON.store(format == LogFormat::Json, Ordering::Relaxed);
+
+    log::set_logger(|max_level| {
+        max_level.set(level);
+        Box::new(Logger { level })
+    }).map_err(|err| format!("Failed to install logger: {}", err))
+}
+
+/// Logs a migration step, a migration being performed or reversed
+///
+/// - `database: &str` - Name of the database the step ran on
+/// - `action: HistoryAction` - What the step did, `Apply` or `Rollback`
+/// - `event: &HistoryEvent` - Step, as recorded in the history. Its action is `Fail` if the step
+///   failed
+///
+/// With `--log-format json` the record is a structured event:
+///
+/// ```json
+/// {"time": "...", "level": "info", "event": "migration", "database": "app", "action": "apply",
+///  "version": 3, "name": "add-posts-table", "status": "success", "duration_ms": 12, "error": null}
+/// ```
+pub fn step(database: &str, action: HistoryAction, event: &HistoryEvent) {
+    let (level, status) = match event.action {
+        HistoryAction::Fail => (LogLevel::Error, "fail"),
+        _ => (LogLevel::Info, "success"),
+    };
+    let duration_ms = event.duration.map(|d| d.as_millis() as u64);
+    let name = event.name.as_ref().map_or("", |name| name.as_str());
+
+    if JSON.load(Ordering::Relaxed) {
+        log!(target: STEP_TARGET, level, "{}", json!({
+            "time": UTC::now().to_rfc3339(),
+            "level": level.to_string().to_lowercase(),
+            "event": "migration",
+            "database": database,
+            "action": action.as_str(),
+            "version": event.version,
+            "name": event.name,
+            "status": status,
+            "duration_ms": duration_ms,
+            "error": event.error,
+        }));
+        return;
+    }
+
+    let took = duration_ms.map_or(String::new(), |ms| format!(" in {}ms", ms));
+    match (action, event.action) {
+        (_, HistoryAction::Fail) => {
+            let doing = if action == HistoryAction::Rollback { "Reversing migration" } else { "Migration" };
+            let after = duration_ms.map_or(String::new(), |ms| format!(" after {}ms", ms));
+            log!(target: STEP_TARGET, level, "{} \"{}\" (version {}) failed{}: {}", doing, name, event.version, after,
+                 event.error.as_ref().map_or("", |err| err.as_str()))
+        },
+        (HistoryAction::Rollback, _) => log!(target: STEP_TARGET, level, "Reversed migration \"{}\" (version {}){}",
+                                             name, event.version, took),
+        _ => log!(target: STEP_TARGET, level, "Applied migration \"{}\" (version {}){}", name, event.version, took),
+    }
+}
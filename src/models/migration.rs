@@ -0,0 +1,363 @@
+//! The `migration` module loads migrations from a migrations directory.
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use drivers::Dialect;
+use models::repeatable::REPEATABLE_DIR;
+
+/// Format of timestamp versions (ex., `20261018120000`), UTC times which sort chronologically as integers
+pub const TIMESTAMP_VERSION_FORMAT: &str = "%Y%m%d%H%M%S";
+
+/// Drivers a SQL file can be specific to, see `sql_path`
+const DIALECTS: &[Dialect] = &[Dialect::Postgres, Dialect::Sqlite, Dialect::Mysql];
+
+/// Header comment which overrides the `--statement-timeout` option for one migration's SQL file
+pub const STATEMENT_TIMEOUT_HEADER: &str = "-- migrate:statement-timeout";
+
+/// Header comment which overrides the `--lock-timeout` option for one migration's SQL file
+pub const LOCK_TIMEOUT_HEADER: &str = "-- migrate:lock-timeout";
+
+/// Header comment which runs one migration's SQL file outside of a transaction, for statements
+/// which can not run in one, ex. `CREATE INDEX CONCURRENTLY` on Postgres
+pub const NO_TRANSACTION_HEADER: &str = "-- migrate:no-transaction";
+
+/// A single migration directory
+pub struct Migration {
+    /// Name of the migration directory
+    pub name: String,
+    /// Schema version the migration provides
+    pub version: i64,
+    /// SQL run when performing the migration
+    pub up: String,
+    /// SQL which reverses changes made by `up`, if the migration can be reversed
+    pub down: Option<String>,
+    /// Hash of the migration's version and the SQL files selected for the driver
+    pub hash: String,
+}
+
+impl Migration {
+    /// Loads a single migration directory
+    ///
+    /// - `path: &Path` - Migration directory to load
+    /// - `dialect: Dialect` - Dialect of the database the migration will run on
+    /// - *returns*: `Migration` - Loaded migration, error message if fail
+    ///
+    /// A migration can provide SQL for a specific driver in `up.<driver>.sql` and `down.<driver>.sql`
+    /// files (ex., `up.postgres.sql`), which are used instead of the portable `up.sql` and `down.sql`
+    /// files when running on that driver.
+    ///
+    /// # Errors
+    /// - The `version` file is missing or can not be read
+    /// - Neither `up.<driver>.sql` nor `up.sql` exist, or the one selected can not be read
+    /// - The `version` file does not contain an integer greater than 0
+    pub fn load(path: &Path, dialect: Dialect) -> Result<Migration, String> {
+        let name = match path.file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => return Err(format!("Migration path \"{}\" has no directory name", path.display()))
+        };
+
+        let (version, version_str) = read_version(path)?;
+
+        let up = match read_sql(path, "up", dialect)? {
+            Some(up) => up,
+            None => return Err(format!("Migration \"{}\" has no up.{}.sql or up.sql file to run on the {} driver",
+                                       name, dialect.name(), dialect.name()))
+        };
+
+        let down = read_sql(path, "down", dialect)?;
+
+        let mut hasher = Sha256::new();
+        hasher.update(version_str.as_bytes());
+        hasher.update(up.as_bytes());
+        if let Some(ref down) = down {
+            hasher.update(down.as_bytes());
+        }
+        let hash = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+
+        Ok(Migration {
+            name,
+            version,
+            up,
+            down,
+            hash
+        })
+    }
+
+    /// Fills in the `{{ name }}` placeholders of the migration's SQL, see `render`
+    ///
+    /// - `variables: &HashMap<String, String>` - Values of the placeholders, see `DbConfig::variables`
+    /// - *returns*: `Migration` - Copy of the migration with its SQL rendered and the hash of its
+    ///   templates, error message naming the placeholders without a value if fail
+    pub fn render(&self, variables: &HashMap<String, String>) -> Result<Migration, String> {
+        let render = |kind: &str, sql: &str| render(sql, variables).map_err(|err| {
+            format!("Migration \"{}\" (version {}) {} SQL {}", self.name, self.version, kind, err)
+        });
+
+        Ok(Migration {
+            name: self.name.clone(),
+            version: self.version,
+            up: render("up", &self.up)?,
+            down: match self.down {
+                Some(ref down) => Some(render("down", down)?),
+                None => None
+            },
+            hash: self.hash.clone()
+        })
+    }
+
+    /// Reads the version of a migration directory without loading its SQL files
+    ///
+    /// - `path: &Path` - Migration directory
+    /// - *returns*: `i64` - Version in the migration's `version` file, error message if fail
+    ///
+    /// Versions are either sequential (`1`, `2`, ...) or UTC timestamps (`20261018120000`), which
+    /// migrations created on different branches are unlikely to share.
+    ///
+    /// # Errors
+    /// - The `version` file is missing or can not be read
+    /// - The `version` file does not contain an integer greater than 0
+    pub fn load_version(path: &Path) -> Result<i64, String> {
+        read_version(path).map(|(version, _)| version)
+    }
+
+    /// Lists the migration directories in a migrations directory
+    ///
+    /// Every directory is a migration except the `repeatable` directory, see `models::repeatable`.
+    ///
+    /// - `dir: &str` - Migrations directory, see the `--migrations-dir/-m` option
+    /// - *returns*: `Vec<PathBuf>` - Migration directories ordered by name, error message if the
+    ///   migrations directory can not be read
+    pub fn dirs(dir: &str) -> Result<Vec<PathBuf>, String> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(err) => return Err(format!("Failed to read migrations directory \"{}\": {}", dir, err))
+        };
+
+        let mut dirs = Vec::new();
+        for entry in entries {
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(err) => return Err(format!("Failed to read migrations directory \"{}\": {}", dir, err))
+            };
+
+            if path.is_dir() && !path.ends_with(REPEATABLE_DIR) {
+                dirs.push(path);
+            }
+        }
+        dirs.sort();
+
+        Ok(dirs)
+    }
+
+    /// Loads every migration in a migrations directory
+    ///
+    /// - `dir: &str` - Migrations directory, see the `--migrations-dir/-m` option
+    /// - `dialect: Dialect` - Dialect of the database the migrations will run on
+    /// - *returns*: `Vec<Migration>` - Migrations ordered by version, error message if fail
+    ///
+    /// # Errors
+    /// - The migrations directory can not be read
+    /// - Any migration fails to load, see `load(path: &Path, dialect: Dialect)`
+    /// - Two migrations provide the same version
+    pub fn load_dir(dir: &str, dialect: Dialect) -> Result<Vec<Migration>, String> {
+        let mut migrations = Vec::new();
+        for path in Migration::dirs(dir)? {
+            migrations.push(Migration::load(&path, dialect)?);
+        }
+
+        migrations.sort_by_key(|m| m.version);
+
+        for pair in migrations.windows(2) {
+            if pair[0].version == pair[1].version {
+                return Err(format!("Migrations \"{}\" and \"{}\" both provide version {}",
+                                   pair[0].name, pair[1].name, pair[0].version))
+            }
+        }
+
+        Ok(migrations)
+    }
+}
+
+/// Reads and parses the `version` file of a migration
+///
+/// - `path: &Path` - Migration directory
+/// - *returns*: `(i64, String)` - Parsed version and the trimmed contents it was parsed from, error
+///   message if fail
+fn read_version(path: &Path) -> Result<(i64, String), String> {
+    let version_str = read_file(&path.join("version"))?.trim().to_owned();
+    match version_str.parse::<i64>() {
+        Ok(version) if version > 0 => Ok((version, version_str)),
+        _ => Err(format!("Migration \"{}\" version file must contain an integer greater than 0, found \"{}\"",
+                         path.file_name().map_or(path.to_string_lossy(), |name| name.to_string_lossy()),
+                         version_str))
+    }
+}
+
+/// Reads the SQL file of a migration for a dialect
+///
+/// - `path: &Path` - Migration directory
+/// - `kind: &str` - Either `up` or `down`
+/// - `dialect: Dialect` - Dialect of the database the migration will run on
+/// - *returns*: `Option<String>` - Contents of `<kind>.<dialect>.sql` if it exists, otherwise of
+///   `<kind>.sql`, None if neither exist, error message if the file can not be read
+fn read_sql(path: &Path, kind: &str, dialect: Dialect) -> Result<Option<String>, String> {
+    match sql_path(path, kind, dialect) {
+        Some(sql_path) => read_file(&sql_path).map(Some),
+        None => Ok(None)
+    }
+}
+
+/// Selects the SQL file of a migration for a dialect
+///
+/// - `path: &Path` - Migration directory
+/// - `kind: &str` - Either `up` or `down`
+/// - `dialect: Dialect` - Dialect of the database the migration will run on
+/// - *returns*: `Option<PathBuf>` - `<kind>.<dialect>.sql` if it exists, otherwise `<kind>.sql`, None
+///   if neither exist
+pub fn sql_path(path: &Path, kind: &str, dialect: Dialect) -> Option<PathBuf> {
+    let dialect_path = path.join(format!("{}.{}.sql", kind, dialect.name()));
+    if dialect_path.exists() {
+        return Some(dialect_path);
+    }
+
+    let portable_path = path.join(format!("{}.sql", kind));
+    if portable_path.exists() {
+        return Some(portable_path);
+    }
+
+    None
+}
+
+/// Loads the SQL files of a directory which are not part of a migration, ex. repeatable migrations
+///
+/// - `dir: &Path` - Directory holding `<name>.sql` and `<name>.<driver>.sql` files
+/// - `dialect: Dialect` - Dialect of the database the SQL will run on
+/// - *returns*: `Vec<(String, String)>` - Name and contents of each file selected for the driver,
+///   ordered by name, error message if the directory or a file can not be read
+///
+/// Like migrations, `<name>.<driver>.sql` is used instead of `<name>.sql` when running on that
+/// driver. Names which only provide SQL for other drivers are left out.
+pub fn load_sql_files(dir: &Path, dialect: Dialect) -> Result<Vec<(String, String)>, String> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => return Err(format!("Failed to read directory \"{}\": {}", dir.display(), err))
+    };
+
+    let mut names = BTreeSet::new();
+    for entry in entries {
+        let file_name = match entry {
+            Ok(entry) => entry.file_name().to_string_lossy().into_owned(),
+            Err(err) => return Err(format!("Failed to read directory \"{}\": {}", dir.display(), err))
+        };
+
+        if let Some(stem) = file_name.strip_suffix(".sql") {
+            let name = DIALECTS.iter()
+                .find_map(|dialect| stem.strip_suffix(&format!(".{}", dialect.name())))
+                .unwrap_or(stem);
+            names.insert(name.to_owned());
+        }
+    }
+
+    let mut files = Vec::new();
+    for name in names {
+        if let Some(path) = sql_path(dir, &name, dialect) {
+            let sql = read_file(&path)?;
+            files.push((name, sql));
+        }
+    }
+
+    Ok(files)
+}
+
+/// Replaces `{{ name }}` placeholders in SQL with the values of variables
+///
+/// - `sql: &str` - SQL to render
+/// - `variables: &HashMap<String, String>` - Values of the placeholders
+/// - *returns*: `String` - Rendered SQL, error message naming every placeholder without a value if
+///   fail
+///
+/// Names are made of letters, digits and underscores, and may be surrounded by spaces inside the
+/// braces. Values are inserted as they are, without quoting. Braces around anything else, ex. the
+/// Postgres array literal `'{{1,2},{3,4}}'`, are left alone.
+pub fn render(sql: &str, variables: &HashMap<String, String>) -> Result<String, String> {
+    let mut unresolved: Vec<String> = Vec::new();
+    let rendered = replace_placeholders(sql, |name| match variables.get(name) {
+        Some(value) => value.clone(),
+        None => {
+            if !unresolved.iter().any(|unresolved| unresolved == name) {
+                unresolved.push(name.to_owned());
+            }
+            String::new()
+        }
+    });
+
+    if !unresolved.is_empty() {
+        return Err(format!("has placeholders without a value in the config file's environment section: {}",
+                           unresolved.join(", ")))
+    }
+
+    Ok(rendered)
+}
+
+/// Replaces every `{{ name }}` placeholder in SQL with what `replace` returns for its name, see
+/// `render`
+pub fn replace_placeholders<F: FnMut(&str) -> String>(sql: &str, mut replace: F) -> String {
+    let mut rendered = String::with_capacity(sql.len());
+
+    let mut rest = sql;
+    while let Some(start) = rest.find("{{") {
+        let placeholder = rest[start + 2..].find("}}")
+            .map(|end| (rest[start + 2..start + 2 + end].trim(), start + 2 + end + 2))
+            .filter(|&(name, _)| !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                                 && !name.starts_with(|c: char| c.is_ascii_digit()));
+
+        match placeholder {
+            Some((name, end)) => {
+                rendered.push_str(&rest[..start]);
+                rendered.push_str(&replace(name));
+                rest = &rest[end..];
+            },
+            None => {
+                rendered.push_str(&rest[..start + 2]);
+                rest = &rest[start + 2..];
+            }
+        }
+    }
+    rendered.push_str(rest);
+
+    rendered
+}
+
+/// Hashes the contents of a SQL file, for files tracked by name rather than by version
+pub fn sql_hash(sql: &str) -> String {
+    Sha256::digest(sql.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Reads a file into a string
+fn read_file(path: &Path) -> Result<String, String> {
+    let mut contents = String::new();
+    match fs::File::open(path).and_then(|mut f| f.read_to_string(&mut contents)) {
+        Ok(_) => Ok(contents),
+        Err(err) => Err(format!("Failed to read \"{}\": {}", path.display(), err))
+    }
+}
+
+/// Reads a header comment from the top of a migration's SQL, ex. `-- migrate:lock-timeout 5s`
+///
+/// - `sql: &str` - Up or down SQL
+/// - `header: &str` - Header, see `STATEMENT_TIMEOUT_HEADER`, `LOCK_TIMEOUT_HEADER` and
+///   `NO_TRANSACTION_HEADER`
+/// - *returns*: `Option<&str>` - Value following the header, empty for headers without one, None if
+///   the comments before the first statement do not include it
+pub fn header<'a>(sql: &'a str, header: &str) -> Option<&'a str> {
+    sql.lines()
+        .map(|line| line.trim())
+        .take_while(|line| line.is_empty() || line.starts_with("--"))
+        .filter_map(|line| line.strip_prefix(header))
+        .find(|value| value.is_empty() || value.starts_with(char::is_whitespace))
+        .map(|value| value.trim())
+}
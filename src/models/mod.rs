@@ -2,4 +2,15 @@ extern crate clap;
 extern crate chrono;
 extern crate postgres;
 
-pub mod command;
\ No newline at end of file
+pub mod command;
+pub mod config;
+pub mod dbschema;
+pub mod history;
+pub mod hooks;
+pub mod internal_migrations;
+pub mod logger;
+pub mod migration;
+pub mod repeatable;
+pub mod rules;
+pub mod schema_dump;
+pub mod seed;
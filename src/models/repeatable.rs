@@ -0,0 +1,100 @@
+//! The `repeatable` module loads repeatable migrations and tracks which were applied.
+//!
+//! Repeatable migrations are SQL files in the `repeatable` directory of a migrations directory, ex.
+//! `repeatable/posts_view.sql`, which redefine objects such as views, functions and triggers. Rather
+//! than providing a version they are applied again whenever their content changes, after every
+//! versioned migration. The hash each was last applied with is recorded in the
+//! `schema_repeatables` table by name.
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+use drivers::{Dialect, Driver};
+use models::dbschema;
+use models::migration;
+
+/// Directory of a migrations directory which holds repeatable migrations
+pub const REPEATABLE_DIR: &str = "repeatable";
+
+/// A single repeatable migration
+pub struct Repeatable {
+    /// Name of the SQL file, without the driver and `.sql` extension
+    pub name: String,
+    /// SQL run whenever the migration changes
+    pub sql: String,
+    /// Hash of the SQL file selected for the driver
+    pub hash: String,
+}
+
+impl Repeatable {
+    /// Loads every repeatable migration of a migrations directory
+    ///
+    /// - `migrations_dir: &str` - Migrations directory, see the `--migrations-dir/-m` option
+    /// - `dialect: Dialect` - Dialect of the database the migrations will run on
+    /// - *returns*: `Vec<Repeatable>` - Repeatable migrations ordered by name, none if the directory
+    ///   has no `repeatable` directory, error message if fail
+    ///
+    /// Like versioned migrations, `<name>.<driver>.sql` is used instead of `<name>.sql` when running
+    /// on that driver. Repeatable migrations which only provide SQL for other drivers are left out.
+    pub fn load_dir(migrations_dir: &str, dialect: Dialect) -> Result<Vec<Repeatable>, String> {
+        let dir = Path::new(migrations_dir).join(REPEATABLE_DIR);
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let repeatables = migration::load_sql_files(&dir, dialect)?.into_iter()
+            .map(|(name, sql)| Repeatable {
+                name,
+                hash: migration::sql_hash(&sql),
+                sql
+            })
+            .collect();
+
+        Ok(repeatables)
+    }
+}
+
+/// Interface to the `schema_repeatables` table
+pub struct Repeatables<'a> {
+    conn: &'a dyn Driver
+}
+
+impl<'a> Repeatables<'a> {
+    pub fn new(conn: &'a dyn Driver) -> Repeatables<'a> {
+        Repeatables {
+            conn
+        }
+    }
+
+    /// Hashes repeatable migrations were last applied with
+    ///
+    /// - *returns*: `HashMap<String, String>` - Hash of every repeatable migration applied, by name,
+    ///   error message if fail
+    pub fn applied(&self) -> Result<HashMap<String, String>, String> {
+        let rows = match self.conn.query("SELECT name, migration_hash FROM schema_repeatables", &[]) {
+            Ok(rows) => rows,
+            Err(err) => return Err(format!("Failed to read 'schema_repeatables' table: {}", err))
+        };
+
+        rows.iter()
+            .map(|row| Ok((row.get("name")?, row.get("migration_hash")?)))
+            .collect()
+    }
+
+    /// Records that a repeatable migration was applied, replacing the hash it was applied with before
+    ///
+    /// - `repeatable: &Repeatable` - Migration applied
+    /// - `duration: Duration` - How long applying it took
+    /// - *returns*: Error message if fail
+    pub fn record(&self, repeatable: &Repeatable, duration: Duration) -> Result<(), String> {
+        let recordr = self.conn.execute("DELETE FROM schema_repeatables WHERE name = $1", &[repeatable.name.clone().into()])
+            .and_then(|_| self.conn.execute("INSERT INTO schema_repeatables (name, updated, migration_hash, duration_ms)
+                                             VALUES ($1, $2, $3, $4)",
+                                            &[repeatable.name.clone().into(), dbschema::now(),
+                                              repeatable.hash.clone().into(), (duration.as_millis() as i64).into()]));
+        match recordr {
+            Ok(_) => Ok(()),
+            Err(err) => Err(format!("Failed to record repeatable migration \"{}\": {}", repeatable.name, err))
+        }
+    }
+}
@@ -0,0 +1,312 @@
+//! The `rules` module flags destructive operations in migrations.
+//!
+//! Rules look at the statements of a migration's up SQL, parsed without a database. Each rule has a
+//! severity, `deny` by default, which a project can change in a `lint.ini` file in its migrations
+//! directory:
+//!
+//! ```ini
+//! [rules]
+//! non-concurrent-index = warn
+//! drop-table = allow
+//! ```
+//!
+//! A migration acknowledges an operation a rule flags with a comment in the comments before the first
+//! statement of its up SQL, like `migration::header`, which suppresses the rule for that migration
+//! only:
+//!
+//! ```sql
+//! -- migrate:allow drop-column
+//! ALTER TABLE posts DROP COLUMN title;
+//! ```
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+use ini::Ini;
+use sqlparser::ast::{AlterColumnOperation, AlterTableOperation, AlterTypeOperation, ColumnDef, ColumnOption,
+                     ObjectName, ObjectType, Statement};
+
+use drivers::Dialect;
+
+/// Name of the file in a migrations directory which configures rules
+pub const CONFIG_FILE: &str = "lint.ini";
+
+/// Comment which suppresses rules for a migration, followed by rule names
+pub const ALLOW_ANNOTATION: &str = "-- migrate:allow";
+
+/// Rule names and what they flag
+pub const RULES: &[(&str, &str)] = &[
+    ("drop-table", "DROP TABLE, which loses the table's data"),
+    ("drop-column", "ALTER TABLE ... DROP COLUMN, which loses the column's data"),
+    ("non-concurrent-index", "CREATE INDEX without CONCURRENTLY on Postgres, which blocks writes while the index builds"),
+    ("alter-column-type", "Changing a column's type, which rewrites the table while holding an exclusive lock"),
+    ("not-null-without-default", "Adding a NOT NULL column without a default, or setting NOT NULL on a column"),
+    ("table-rewrite", "Other operations which rewrite the table while holding an exclusive lock, ex. VACUUM FULL"),
+    ("drop-schema", "DROP SCHEMA ... CASCADE, which drops every object in the schema"),
+    ("truncate", "TRUNCATE, which deletes every row of the table"),
+    ("drop-view", "DROP VIEW and DROP MATERIALIZED VIEW, which breaks queries using the view"),
+    ("alter-type", "ALTER TYPE on Postgres, which breaks code using the type's old name or values"),
+];
+
+/// Functions which return a different value for each row, a column default calling one of them makes
+/// Postgres rewrite the table to fill the column in
+const VOLATILE_FUNCTIONS: &[&str] = &["random", "gen_random_uuid", "uuid_generate_v1", "uuid_generate_v4",
+                                      "clock_timestamp", "timeofday", "nextval"];
+
+/// What happens when a rule flags an operation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    /// Reported as a problem by `lint`, `run` refuses to perform the migration
+    Deny,
+    /// Reported as a warning
+    Warn,
+    /// Not reported
+    Allow,
+}
+
+impl Severity {
+    /// Parses a severity, as used in `lint.ini`
+    pub fn from_name(name: &str) -> Option<Severity> {
+        match name {
+            "deny" => Some(Severity::Deny),
+            "warn" => Some(Severity::Warn),
+            "allow" => Some(Severity::Allow),
+            _ => None
+        }
+    }
+}
+
+/// An operation a rule flagged
+#[derive(Debug)]
+pub struct Violation {
+    /// Name of the rule, see `RULES`
+    pub rule: &'static str,
+    /// Severity configured for the rule
+    pub severity: Severity,
+    /// Operation which was flagged
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} [{}]", self.message, self.rule)
+    }
+}
+
+/// Severity of every rule for a project
+#[derive(Debug)]
+pub struct RuleConfig {
+    severities: HashMap<&'static str, Severity>
+}
+
+impl RuleConfig {
+    /// Every rule at the default severity, `deny`
+    pub fn new() -> RuleConfig {
+        RuleConfig {
+            severities: RULES.iter().map(|&(rule, _)| (rule, Severity::Deny)).collect()
+        }
+    }
+
+    /// Loads the severity of rules from the `lint.ini` file of a migrations directory
+    ///
+    /// - `migrations_dir: &str` - Migrations directory, see the `--migrations-dir/-m` option
+    /// - *returns*: `RuleConfig` - Configured severities, defaults if the directory has no
+    ///   `lint.ini` file, error message if fail
+    ///
+    /// # Errors
+    /// - The `lint.ini` file can not be loaded
+    /// - A rule or severity in the `[rules]` section is unknown
+    pub fn load(migrations_dir: &str) -> Result<RuleConfig, String> {
+        let mut obj = RuleConfig::new();
+
+        let path = Path::new(migrations_dir).join(CONFIG_FILE);
+        if !path.exists() {
+            return Ok(obj);
+        }
+
+        let conf = match Ini::load_from_file(&path.to_string_lossy()) {
+            Ok(conf) => conf,
+            Err(err) => return Err(format!("Failed to load \"{}\": {}", path.display(), err))
+        };
+
+        if let Some(section) = conf.section(Some("rules")) {
+            for (rule, severity) in section.iter() {
+                let rule = match RULES.iter().find(|&&(name, _)| name == rule) {
+                    Some(&(name, _)) => name,
+                    None => return Err(format!("Unknown rule \"{}\" in \"{}\", expected one of {}", rule, path.display(),
+                                               RULES.iter().map(|&(name, _)| name).collect::<Vec<&str>>().join(", ")))
+                };
+
+                match Severity::from_name(severity) {
+                    Some(severity) => obj.severities.insert(rule, severity),
+                    None => return Err(format!("Unknown severity \"{}\" for rule \"{}\" in \"{}\", expected deny, warn \
+                                                or allow", severity, rule, path.display()))
+                };
+            }
+        }
+
+        Ok(obj)
+    }
+
+    /// Checks the statements of a migration's up SQL against every rule
+    ///
+    /// - `dialect: Dialect` - Dialect the SQL is written for
+    /// - `sql: &str` - Up SQL, its leading comments are read for `-- migrate:allow` annotations
+    /// - `statements: &[Statement]` - Statements parsed from `sql`
+    /// - *returns*: `Vec<Violation>` - Operations flagged by rules which are neither allowed by the
+    ///   config nor suppressed by an annotation
+    pub fn check(&self, dialect: Dialect, sql: &str, statements: &[Statement]) -> Vec<Violation> {
+        let suppressed = annotations(sql);
+
+        // Operations on tables and views the migration creates can not hurt existing data
+        let created: HashSet<String> = statements.iter()
+            .filter_map(|statement| match *statement {
+                Statement::CreateTable(ref create) => Some(create.name.to_string()),
+                Statement::CreateView(ref create) => Some(create.name.to_string()),
+                _ => None
+            })
+            .collect();
+
+        let mut found = Vec::new();
+        for statement in statements {
+            flag(dialect, statement, &created, &mut found);
+        }
+
+        found.into_iter()
+            .filter(|&(rule, _)| !suppressed.contains(rule))
+            .map(|(rule, message)| Violation {
+                rule,
+                severity: self.severities.get(rule).cloned().unwrap_or(Severity::Deny),
+                message
+            })
+            .filter(|violation| violation.severity != Severity::Allow)
+            .collect()
+    }
+}
+
+/// Finds the rules suppressed by `-- migrate:allow` annotations in the comments before the first
+/// statement of SQL
+fn annotations(sql: &str) -> HashSet<String> {
+    sql.lines()
+        .map(|line| line.trim())
+        .take_while(|line| line.is_empty() || line.starts_with("--"))
+        .filter_map(|line| line.strip_prefix(ALLOW_ANNOTATION))
+        .flat_map(|rules| rules.split(|c: char| c == ',' || c.is_whitespace()))
+        .filter(|rule| !rule.is_empty())
+        .map(|rule| rule.to_owned())
+        .collect()
+}
+
+/// Adds the operations rules flag in a statement to `found`, as (rule name, message)
+fn flag(dialect: Dialect, statement: &Statement, created: &HashSet<String>, found: &mut Vec<(&'static str, String)>) {
+    match *statement {
+        Statement::Drop { object_type: ObjectType::Table, ref names, .. } => {
+            for name in names.iter().filter(|name| !created.contains(&name.to_string())) {
+                found.push(("drop-table", format!("Drops table {}", name)));
+            }
+        },
+        Statement::Drop { object_type: ObjectType::Schema, ref names, cascade: true, .. } => {
+            for name in names {
+                found.push(("drop-schema", format!("Drops schema {} with CASCADE, which drops every object in it",
+                                                   name)));
+            }
+        },
+        Statement::Drop { object_type: ref object_type @ (ObjectType::View | ObjectType::MaterializedView),
+                          ref names, .. } => {
+            let kind = if *object_type == ObjectType::View { "view" } else { "materialized view" };
+            for name in names.iter().filter(|name| !created.contains(&name.to_string())) {
+                found.push(("drop-view", format!("Drops {} {}", kind, name)));
+            }
+        },
+        Statement::Truncate(ref truncate) => {
+            for target in truncate.table_names.iter().filter(|target| !created.contains(&target.name.to_string())) {
+                found.push(("truncate", format!("Truncates table {}, which deletes every row", target.name)));
+            }
+        },
+        Statement::AlterType(ref alter) if dialect == Dialect::Postgres => {
+            let message = match alter.operation {
+                AlterTypeOperation::Rename(_) => format!("Renames type {}, which breaks code using its old name",
+                                                         alter.name),
+                AlterTypeOperation::AddValue(_) => format!("Adds a value to type {}, which can not be used until the \
+                                                            migration's transaction commits", alter.name),
+                AlterTypeOperation::RenameValue(_) => format!("Renames a value of type {}, which breaks code using the \
+                                                               old value", alter.name),
+            };
+            found.push(("alter-type", message));
+        },
+        Statement::CreateIndex(ref index) if dialect == Dialect::Postgres && !index.concurrently
+                                             && !created.contains(&index.table_name.to_string()) => {
+            let name = index.name.as_ref().map_or(String::new(), |name| format!("{} ", name));
+            found.push(("non-concurrent-index", format!("Creates index {}on {} without CONCURRENTLY, which blocks \
+                                                         writes to {} while it builds", name, index.table_name,
+                                                        index.table_name)));
+        },
+        Statement::AlterTable(ref alter) if !created.contains(&alter.name.to_string()) => {
+            for operation in &alter.operations {
+                flag_alter(dialect, &alter.name, operation, found);
+            }
+        },
+        Statement::Vacuum(ref vacuum) if vacuum.full => {
+            let table = vacuum.table_name.as_ref().map_or("every table".to_owned(), |name| name.to_string());
+            found.push(("table-rewrite", format!("VACUUM FULL rewrites {}", table)));
+        },
+        _ => {}
+    }
+}
+
+/// Adds the operations rules flag in an `ALTER TABLE` operation to `found`
+fn flag_alter(dialect: Dialect, table: &ObjectName, operation: &AlterTableOperation,
+              found: &mut Vec<(&'static str, String)>) {
+    match *operation {
+        AlterTableOperation::DropColumn { ref column_names, .. } => {
+            for column in column_names {
+                found.push(("drop-column", format!("Drops column {}.{}", table, column)));
+            }
+        },
+        AlterTableOperation::AddColumn { ref column_def, .. } => flag_add_column(dialect, table, column_def, found),
+        AlterTableOperation::AlterColumn { ref column_name, op: AlterColumnOperation::SetDataType { .. } } => {
+            found.push(("alter-column-type", format!("Changes the type of column {}.{}, which rewrites {}",
+                                                     table, column_name, table)));
+        },
+        AlterTableOperation::AlterColumn { ref column_name, op: AlterColumnOperation::SetNotNull } => {
+            found.push(("not-null-without-default", format!("Sets NOT NULL on column {}.{}, which scans {} while \
+                                                            holding an exclusive lock", table, column_name, table)));
+        },
+        AlterTableOperation::ModifyColumn { ref col_name, .. } => {
+            found.push(("alter-column-type", format!("Modifies column {}.{}, which rewrites {}", table, col_name, table)));
+        },
+        AlterTableOperation::ChangeColumn { ref old_name, .. } => {
+            found.push(("alter-column-type", format!("Changes column {}.{}, which rewrites {}", table, old_name, table)));
+        },
+        AlterTableOperation::SetLogged | AlterTableOperation::SetUnlogged => {
+            found.push(("table-rewrite", format!("Changes whether {} is logged, which rewrites it", table)));
+        },
+        _ => {}
+    }
+}
+
+/// Adds the operations rules flag when adding a column to `found`
+fn flag_add_column(dialect: Dialect, table: &ObjectName, column: &ColumnDef, found: &mut Vec<(&'static str, String)>) {
+    let mut not_null = false;
+    let mut default = None;
+    for option in &column.options {
+        match option.option {
+            ColumnOption::NotNull => not_null = true,
+            ColumnOption::Default(ref expr) => default = Some(expr.to_string().to_lowercase()),
+            _ => {}
+        }
+    }
+
+    match default {
+        None if not_null => {
+            found.push(("not-null-without-default", format!("Adds NOT NULL column {}.{} without a default, which \
+                                                            fails if {} has rows", table, column.name, table)));
+        },
+        Some(ref default) if dialect == Dialect::Postgres
+                             && VOLATILE_FUNCTIONS.iter().any(|func| default.starts_with(&format!("{}(", func))) => {
+            found.push(("table-rewrite", format!("Adds column {}.{} with a volatile default, which rewrites {}",
+                                                 table, column.name, table)));
+        },
+        _ => {}
+    }
+}
@@ -0,0 +1,124 @@
+//! The `schema_dump` module writes the schema of a database to a file kept in the repository.
+//!
+//! The file describes types, sequences, tables, views, indexes, triggers and functions as SQL, always
+//! in the same order and layout for the same schema, so a change to the schema shows up as a
+//! reviewable diff and a database which drifted from it is easy to spot. Migrate's bookkeeping
+//! tables, and the objects it creates for them, are left out.
+use std::fs;
+
+use drivers::Driver;
+use drivers::schema::{Schema, SchemaObject, Table};
+
+/// Schema file written when no `--file/-f` is given
+pub const DEFAULT_FILE: &str = "schema.sql";
+
+/// Tables Migrate keeps its bookkeeping in, see `internal_migrations`
+const BOOKKEEPING_TABLES: &[&str] = &["schema_versions", "schema_versions_lib", "schema_history",
+                                      "schema_repeatables", "schema_seeds"];
+
+/// Types Migrate creates for its bookkeeping tables on Postgres. Sequences of their `SERIAL` columns,
+/// ex. `schema_versions_id_seq`, belong to the table and are left out with it
+const BOOKKEEPING_TYPES: &[&str] = &["schema_version_status"];
+
+/// Functions Migrate creates for its bookkeeping tables on Postgres
+const BOOKKEEPING_FUNCTIONS: &[&str] = &["schema_history_append_only()"];
+
+/// Reads the schema of a database and renders it
+///
+/// - `conn: &dyn Driver` - Database to read the schema of
+/// - `version: i64` - Version the database is at, noted in the header
+/// - *returns*: `String` - Content of the schema file, error message if fail
+pub fn dump(conn: &dyn Driver, version: i64) -> Result<String, String> {
+    match conn.schema() {
+        Ok(schema) => Ok(render(&schema, version)),
+        Err(err) => Err(format!("Failed to read the schema of {}: {}", conn.database_name(), err))
+    }
+}
+
+/// Renders a schema as SQL, leaving out the bookkeeping tables
+///
+/// Types and sequences come first, as the tables' columns can use them.
+///
+/// - `schema: &Schema` - Schema read from the database
+/// - `version: i64` - Version the database is at, noted in the header
+/// - *returns*: `String` - Content of the schema file
+pub fn render(schema: &Schema, version: i64) -> String {
+    let mut statements: Vec<String> = schema.types.iter()
+        .filter(|object| !BOOKKEEPING_TYPES.contains(&object.name.as_str()))
+        .chain(schema.sequences.iter())
+        .filter(|object| !belongs_to_bookkeeping(object))
+        .map(statement)
+        .collect();
+
+    statements.extend(schema.tables.iter()
+        .filter(|table| !is_bookkeeping(&table.name))
+        .map(table_ddl));
+
+    let objects = schema.views.iter()
+        .chain(schema.indexes.iter())
+        .chain(schema.triggers.iter())
+        .chain(schema.functions.iter().filter(|function| !BOOKKEEPING_FUNCTIONS.contains(&function.name.as_str())));
+    for object in objects.filter(|object| !belongs_to_bookkeeping(object)) {
+        statements.push(statement(object));
+    }
+
+    format!("-- Schema at version {}, written by `migrate dump-schema`. Do not edit, run it again instead.\n\n{}\n",
+            version, statements.join("\n\n"))
+}
+
+/// Writes a schema file
+///
+/// - `path: &str` - Path of the file, see the `--file/-f` option
+/// - `content: &str` - Content rendered by `dump`
+/// - *returns*: Error message if fail
+pub fn write(path: &str, content: &str) -> Result<(), String> {
+    fs::write(path, content).map_err(|err| format!("Failed to write schema file {}: {}", path, err))
+}
+
+/// Checks a schema file is up to date
+///
+/// - `path: &str` - Path of the file, see the `--file/-f` option
+/// - `content: &str` - Content rendered by `dump`
+/// - *returns*: Error message if the file is missing or differs
+pub fn check(path: &str, content: &str) -> Result<(), String> {
+    match fs::read_to_string(path) {
+        Ok(ref current) if current == content => Ok(()),
+        Ok(_) => Err(format!("Schema file {} is out of date, run dump-schema and commit the changes", path)),
+        Err(err) => Err(format!("Failed to read schema file {}: {}", path, err))
+    }
+}
+
+fn is_bookkeeping(table: &str) -> bool {
+    BOOKKEEPING_TABLES.contains(&table)
+}
+
+fn belongs_to_bookkeeping(object: &SchemaObject) -> bool {
+    object.table.as_ref().is_some_and(|table| is_bookkeeping(table))
+}
+
+/// `CREATE TABLE` statement with the columns in order, followed by the constraints
+fn table_ddl(table: &Table) -> String {
+    let mut lines: Vec<String> = table.columns.iter()
+        .map(|column| {
+            let mut line = format!("    {} {}", column.name, column.data_type);
+            if column.not_null {
+                line.push_str(" NOT NULL");
+            }
+            if let Some(ref default) = column.default {
+                line.push_str(&format!(" DEFAULT {}", default));
+            }
+            line
+        })
+        .collect();
+
+    lines.extend(table.constraints.iter()
+        .map(|constraint| format!("    CONSTRAINT {} {}", constraint.name, constraint.definition)));
+
+    format!("CREATE TABLE {} (\n{}\n);", table.name, lines.join(",\n"))
+}
+
+/// Definition of an object ending with a single `;`, databases report them with or without one
+fn statement(object: &SchemaObject) -> String {
+    let definition = object.definition.trim();
+    format!("{};", definition.strip_suffix(';').unwrap_or(definition).trim_end())
+}
@@ -0,0 +1,101 @@
+//! The `seed` module loads seed data and tracks which seeds were loaded into a database.
+//!
+//! Seeds are SQL files which insert data rather than change the schema, kept per environment in
+//! `<seeds dir>/<environment>/*.sql`, ex. `seeds/test/users.sql`. Each seed is loaded once per
+//! database, which is recorded in the `schema_seeds` table by environment and name.
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+use drivers::{Dialect, Driver};
+use models::dbschema;
+use models::migration;
+
+/// Name of the seeds directory used when no `--environment/-e` is selected
+pub const DEFAULT_ENVIRONMENT: &str = "default";
+
+/// A single seed file
+pub struct Seed {
+    /// Name of the SQL file, without the driver and `.sql` extension
+    pub name: String,
+    /// SQL which loads the data
+    pub sql: String,
+    /// Hash of the SQL file selected for the driver
+    pub hash: String,
+}
+
+impl Seed {
+    /// Loads the seeds of an environment
+    ///
+    /// - `seeds_dir: &str` - Seeds directory, see the `--seeds-dir` option
+    /// - `environment: &str` - Environment whose subdirectory of `seeds_dir` the seeds are loaded from
+    /// - `dialect: Dialect` - Dialect of the database the seeds will be loaded into
+    /// - *returns*: `Vec<Seed>` - Seeds ordered by name, none if the environment has no seeds
+    ///   directory, error message if fail
+    ///
+    /// `<name>.<driver>.sql` is used instead of `<name>.sql` when running on that driver, see
+    /// `migration::load_sql_files`.
+    pub fn load_dir(seeds_dir: &str, environment: &str, dialect: Dialect) -> Result<Vec<Seed>, String> {
+        let dir = Path::new(seeds_dir).join(environment);
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let seeds = migration::load_sql_files(&dir, dialect)?.into_iter()
+            .map(|(name, sql)| Seed {
+                name,
+                hash: migration::sql_hash(&sql),
+                sql
+            })
+            .collect();
+
+        Ok(seeds)
+    }
+}
+
+/// Interface to the `schema_seeds` table
+pub struct Seeds<'a> {
+    conn: &'a dyn Driver
+}
+
+impl<'a> Seeds<'a> {
+    pub fn new(conn: &'a dyn Driver) -> Seeds<'a> {
+        Seeds {
+            conn
+        }
+    }
+
+    /// Hashes the seeds of an environment were loaded with
+    ///
+    /// - `environment: &str` - Environment the seeds were loaded from
+    /// - *returns*: `HashMap<String, String>` - Hash of every seed of the environment loaded, by
+    ///   name, error message if fail
+    pub fn loaded(&self, environment: &str) -> Result<HashMap<String, String>, String> {
+        let rows = match self.conn.query("SELECT name, seed_hash FROM schema_seeds WHERE environment = $1",
+                                         &[environment.into()]) {
+            Ok(rows) => rows,
+            Err(err) => return Err(format!("Failed to read 'schema_seeds' table: {}", err))
+        };
+
+        rows.iter()
+            .map(|row| Ok((row.get("name")?, row.get("seed_hash")?)))
+            .collect()
+    }
+
+    /// Records that a seed was loaded
+    ///
+    /// - `seed: &Seed` - Seed loaded
+    /// - `environment: &str` - Environment the seed was loaded from
+    /// - `duration: Duration` - How long loading it took
+    /// - *returns*: Error message if fail
+    pub fn record(&self, seed: &Seed, environment: &str, duration: Duration) -> Result<(), String> {
+        let recordr = self.conn.execute("INSERT INTO schema_seeds (name, environment, loaded, seed_hash, duration_ms)
+                                         VALUES ($1, $2, $3, $4, $5)",
+                                        &[seed.name.clone().into(), environment.into(), dbschema::now(),
+                                          seed.hash.clone().into(), (duration.as_millis() as i64).into()]);
+        match recordr {
+            Ok(_) => Ok(()),
+            Err(err) => Err(format!("Failed to record seed \"{}\": {}", seed.name, err))
+        }
+    }
+}
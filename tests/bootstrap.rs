@@ -0,0 +1,67 @@
+//! Integration tests for bootstrapping the `schema_versions` table
+extern crate mysql;
+extern crate postgres;
+
+mod common;
+
+fn table_exists(db: &str) -> bool {
+    let conn = common::connect(db);
+    let rows = conn.query("SELECT to_regclass('schema_versions') IS NOT NULL", &[]).unwrap();
+    rows.get(0).get(0)
+}
+
+fn type_exists(db: &str) -> bool {
+    let conn = common::connect(db);
+    let rows = conn.query("SELECT to_regtype('schema_version_status') IS NOT NULL", &[]).unwrap();
+    rows.get(0).get(0)
+}
+
+#[test]
+#[ignore = "requires the test database, see test/Makefile"]
+fn bootstrap_creates_table_and_type() {
+    let db = common::fresh_db("bootstrap_creates");
+
+    let out = common::migrate_run(&db, &["-t", "0", "-m", &common::migrations_dir(&db, &[])]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    assert!(table_exists(&db));
+    assert!(type_exists(&db));
+}
+
+#[test]
+#[ignore = "requires the test database, see test/Makefile"]
+fn bootstrap_is_idempotent() {
+    let db = common::fresh_db("bootstrap_idempotent");
+
+    for _ in 0..2 {
+        let out = common::migrate_run(&db, &["-t", "0", "-m", &common::migrations_dir(&db, &[])]);
+        assert!(out.status.success(), "{}", common::stderr(&out));
+    }
+
+    assert!(table_exists(&db));
+}
+
+#[test]
+#[ignore = "requires the test database, see test/Makefile"]
+fn bootstrap_rejects_incompatible_table() {
+    let db = common::fresh_db("bootstrap_bad_table");
+    common::connect(&db).batch_execute("CREATE TABLE schema_versions (id TEXT PRIMARY KEY)").unwrap();
+
+    let out = common::migrate_run(&db, &["-t", "0", "-m", &common::migrations_dir(&db, &[])]);
+    assert!(common::stderr(&out).contains("incompatible_table"), "{}", common::stderr(&out));
+
+    // Bootstrap is transactional, the type created before the check must be rolled back
+    assert!(!type_exists(&db));
+}
+
+#[test]
+#[ignore = "requires the test database, see test/Makefile"]
+fn bootstrap_rejects_incompatible_type() {
+    let db = common::fresh_db("bootstrap_bad_type");
+    common::connect(&db).batch_execute("CREATE TYPE schema_version_status AS ENUM ('ongoing')").unwrap();
+
+    let out = common::migrate_run(&db, &["-t", "0", "-m", &common::migrations_dir(&db, &[])]);
+    assert!(common::stderr(&out).contains("incompatible_type"), "{}", common::stderr(&out));
+
+    assert!(!table_exists(&db));
+}
@@ -0,0 +1,135 @@
+//! Helpers shared by the integration tests
+//!
+//! Tests which need a database are ignored by default. Start the test database with
+//! `make -C test db` then run them with `cargo test -- --ignored`. The `MIGRATE_TEST_HOST`,
+//! `MIGRATE_TEST_USER` and `MIGRATE_TEST_PASSWORD` environment variables override the connection
+//! details in `test/database.ini`.
+//!
+//! MySQL tests are ignored as well, start a local `mysqld` with `make -C test mysql` then run them
+//! with `cargo test --test mysql -- --ignored`. `MIGRATE_TEST_MYSQL_HOST` and
+//! `MIGRATE_TEST_MYSQL_USER` override its host and user.
+#![allow(dead_code)]
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+use postgres::{Connection, SslMode};
+
+/// Database server host
+pub fn host() -> String {
+    env::var("MIGRATE_TEST_HOST").unwrap_or_else(|_| "localhost:5432".to_owned())
+}
+
+/// Database server user
+pub fn user() -> String {
+    env::var("MIGRATE_TEST_USER").unwrap_or_else(|_| "test_user".to_owned())
+}
+
+/// Database server password
+pub fn password() -> String {
+    env::var("MIGRATE_TEST_PASSWORD").unwrap_or_else(|_| "test_password".to_owned())
+}
+
+/// Connect to a database on the test server
+pub fn connect(database: &str) -> Connection {
+    let url = format!("postgresql://{}:{}@{}/{}", user(), password(), host(), database);
+    Connection::connect(url.as_str(), SslMode::None).expect("Failed to connect to test database")
+}
+
+/// Create an empty database for a single test, dropping any left over from a previous run
+pub fn fresh_db(name: &str) -> String {
+    let db_name = format!("migrate_test_{}", name);
+    let conn = connect("test_db");
+    conn.execute(&format!("DROP DATABASE IF EXISTS {}", db_name), &[]).expect("Failed to drop test database");
+    conn.execute(&format!("CREATE DATABASE {}", db_name), &[]).expect("Failed to create test database");
+    db_name
+}
+
+/// Path of an empty SQLite database file for a single test, removing any left over from a previous run
+pub fn fresh_sqlite_db(name: &str) -> String {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.sqlite", name));
+    if path.exists() {
+        fs::remove_file(&path).expect("Failed to remove old SQLite database");
+    }
+    path.to_string_lossy().into_owned()
+}
+
+/// MySQL server host
+pub fn mysql_host() -> String {
+    env::var("MIGRATE_TEST_MYSQL_HOST").unwrap_or_else(|_| "localhost:3306".to_owned())
+}
+
+/// MySQL server user, must be able to create databases
+pub fn mysql_user() -> String {
+    env::var("MIGRATE_TEST_MYSQL_USER").unwrap_or_else(|_| "root".to_owned())
+}
+
+/// URL of a database on the MySQL test server
+pub fn mysql_url(database: &str) -> String {
+    format!("mysql://{}:{}@{}/{}", mysql_user(), password(), mysql_host(), database)
+}
+
+/// Create an empty database on the MySQL test server for a single test, dropping any left over from
+/// a previous run
+pub fn fresh_mysql_db(name: &str) -> String {
+    use mysql::prelude::Queryable;
+
+    let db_name = format!("migrate_test_{}", name);
+    let mut conn = mysql::Conn::new(mysql::Opts::from_url(&mysql_url("test_db")).unwrap())
+        .expect("Failed to connect to MySQL test database");
+    conn.query_drop(format!("DROP DATABASE IF EXISTS {}", db_name)).expect("Failed to drop test database");
+    conn.query_drop(format!("CREATE DATABASE {}", db_name)).expect("Failed to create test database");
+    db_name
+}
+
+/// A migration to write into a test migrations directory, (directory name, version, up.sql, down.sql)
+pub type TestMigration<'a> = (&'a str, &'a str, &'a str, Option<&'a str>);
+
+/// Write a migrations directory for a single test, replacing any left over from a previous run
+pub fn migrations_dir(name: &str, migrations: &[TestMigration]) -> String {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    if dir.exists() {
+        fs::remove_dir_all(&dir).expect("Failed to remove old migrations directory");
+    }
+    fs::create_dir_all(&dir).expect("Failed to create migrations directory");
+
+    for &(migration, version, up, down) in migrations {
+        let migration_dir = dir.join(migration);
+        fs::create_dir(&migration_dir).expect("Failed to create migration directory");
+        fs::write(migration_dir.join("version"), version).unwrap();
+        fs::write(migration_dir.join("up.sql"), up).unwrap();
+        if let Some(down) = down {
+            fs::write(migration_dir.join("down.sql"), down).unwrap();
+        }
+    }
+
+    dir.to_string_lossy().into_owned()
+}
+
+/// Run the Migrate binary with the provided arguments
+pub fn migrate(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_migrate"))
+        .args(args)
+        .output()
+        .expect("Failed to run migrate")
+}
+
+/// Run the `run` sub-command against a test database
+pub fn migrate_run(database: &str, args: &[&str]) -> Output {
+    let (host, user, password) = (host(), user(), password());
+    let mut full_args = vec!["run", "-h", &host, "-u", &user, "-p", &password, "-d", database];
+    full_args.extend_from_slice(args);
+    migrate(&full_args)
+}
+
+/// Standard output of a Migrate invocation
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+/// Standard error of a Migrate invocation, where logs are written
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
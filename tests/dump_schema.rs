@@ -0,0 +1,136 @@
+//! Integration tests for the dump-schema sub-command and the run --dump-schema option
+extern crate mysql;
+extern crate postgres;
+
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+const MIGRATIONS: &[common::TestMigration] = &[
+    ("add-users-table", "1",
+     "CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT NOT NULL UNIQUE, created TEXT DEFAULT CURRENT_TIMESTAMP);",
+     Some("DROP TABLE users;")),
+    ("add-posts-table", "2",
+     "CREATE TABLE posts (id INT PRIMARY KEY, user_id INT NOT NULL REFERENCES users (id) ON DELETE CASCADE, title TEXT);
+      CREATE INDEX posts_title_idx ON posts (title);
+      CREATE VIEW titles AS SELECT title FROM posts;",
+     Some("DROP VIEW titles; DROP TABLE posts;")),
+];
+
+/// Path of a schema file for a single test, removing any left over from a previous run
+fn schema_file(name: &str) -> String {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.sql", name));
+    if path.exists() {
+        fs::remove_file(&path).unwrap();
+    }
+    path.to_string_lossy().into_owned()
+}
+
+#[test]
+fn schema_is_dumped_after_run_and_checked() {
+    let path = common::fresh_sqlite_db("dump_schema_sqlite");
+    let dir = common::migrations_dir("dump_schema_sqlite", MIGRATIONS);
+    let file = schema_file("dump_schema_sqlite");
+
+    let out = common::migrate(&["run", "--driver", "sqlite", "-d", &path, "-m", &dir,
+                                "--dump-schema", &file]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    let schema = fs::read_to_string(&file).unwrap();
+    assert!(schema.starts_with("-- Schema at version 2,"), "{}", schema);
+    assert!(schema.contains("CREATE TABLE posts (\n    \
+                             id INT,\n    \
+                             user_id INT NOT NULL,\n    \
+                             title TEXT,\n    \
+                             CONSTRAINT posts_pkey PRIMARY KEY (id),\n    \
+                             CONSTRAINT posts_user_id_fkey FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE\n\
+                             );\n\n\
+                             CREATE TABLE users (\n    \
+                             id INTEGER,\n    \
+                             email TEXT NOT NULL,\n    \
+                             created TEXT DEFAULT CURRENT_TIMESTAMP,\n    \
+                             CONSTRAINT users_email_key UNIQUE (email),\n    \
+                             CONSTRAINT users_pkey PRIMARY KEY (id)\n\
+                             );\n\n\
+                             CREATE VIEW titles AS SELECT title FROM posts;\n\n\
+                             CREATE INDEX posts_title_idx ON posts (title);\n"), "{}", schema);
+    assert!(!schema.contains("schema_versions") && !schema.contains("schema_history"), "{}", schema);
+
+    let check = ["dump-schema", "--driver", "sqlite", "-d", &path, "-f", &file, "--check"];
+    let out = common::migrate(&check);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    // A change made outside of the migrations shows up as drift
+    let out = common::migrate(&["run", "--driver", "sqlite", "-d", &path, "-m", &dir, "-t", "1"]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    let out = common::migrate(&check);
+    assert!(common::stderr(&out).contains("is out of date, run dump-schema and commit the changes"),
+            "{}", common::stderr(&out));
+    assert_eq!(fs::read_to_string(&file).unwrap(), schema);
+
+    let out = common::migrate(&["dump-schema", "--driver", "sqlite", "-d", &path, "-f", &file]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    let schema = fs::read_to_string(&file).unwrap();
+    assert!(schema.starts_with("-- Schema at version 1,"), "{}", schema);
+    assert!(!schema.contains("posts"), "{}", schema);
+}
+
+#[test]
+fn dry_runs_do_not_dump_the_schema() {
+    let path = common::fresh_sqlite_db("dump_schema_dry_run");
+    let dir = common::migrations_dir("dump_schema_dry_run", MIGRATIONS);
+    let file = schema_file("dump_schema_dry_run");
+
+    let out = common::migrate(&["run", "--driver", "sqlite", "-d", &path, "-m", &dir, "--dry-run",
+                                "--dump-schema", &file]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert!(!PathBuf::from(&file).exists());
+}
+
+#[test]
+#[ignore = "requires the test database, see test/Makefile"]
+fn schema_is_dumped_from_postgres_catalog() {
+    let db_name = common::fresh_db("dump_schema");
+    let dir = common::migrations_dir("dump_schema_postgres", &[
+        ("add-users-table", "1",
+         "CREATE TYPE mood AS ENUM ('sad', 'happy');
+          CREATE DOMAIN rating AS INT NOT NULL CHECK (VALUE BETWEEN 1 AND 5);
+          CREATE SEQUENCE invoice_numbers START 1000;
+          CREATE TABLE users (id SERIAL PRIMARY KEY, email VARCHAR(200) NOT NULL UNIQUE, CHECK (email <> ''),
+                              mood mood, rating rating, token BIGINT GENERATED ALWAYS AS IDENTITY);
+          CREATE FUNCTION touch() RETURNS trigger AS $$ BEGIN RETURN NEW; END $$ LANGUAGE plpgsql;
+          CREATE TRIGGER users_touch BEFORE INSERT ON users FOR EACH ROW EXECUTE PROCEDURE touch();",
+         None),
+    ]);
+    let file = schema_file("dump_schema_postgres");
+
+    let out = common::migrate_run(&db_name, &["-m", &dir, "--dump-schema", &file]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    let schema = fs::read_to_string(&file).unwrap();
+    assert!(schema.contains("CREATE TYPE mood AS ENUM ('sad', 'happy');\n\n\
+                             CREATE DOMAIN rating AS integer NOT NULL \
+                             CONSTRAINT rating_check CHECK (((VALUE >= 1) AND (VALUE <= 5)));\n\n\
+                             CREATE SEQUENCE invoice_numbers AS bigint INCREMENT BY 1 MINVALUE 1 \
+                             MAXVALUE 9223372036854775807 START WITH 1000 CACHE 1 NO CYCLE;\n\n\
+                             CREATE SEQUENCE users_id_seq AS integer INCREMENT BY 1 MINVALUE 1 MAXVALUE 2147483647 \
+                             START WITH 1 CACHE 1 NO CYCLE;\n\n\
+                             CREATE TABLE users (\n    \
+                             id integer NOT NULL DEFAULT nextval('users_id_seq'::regclass),\n    \
+                             email character varying(200) NOT NULL,\n    \
+                             mood mood,\n    \
+                             rating rating,\n    \
+                             token bigint NOT NULL,\n    \
+                             CONSTRAINT users_email_check CHECK (((email)::text <> ''::text)),\n    \
+                             CONSTRAINT users_email_key UNIQUE (email),\n    \
+                             CONSTRAINT users_pkey PRIMARY KEY (id)\n\
+                             );"), "{}", schema);
+    assert!(schema.contains("CREATE TRIGGER users_touch BEFORE INSERT ON public.users"), "{}", schema);
+    assert!(schema.contains("CREATE OR REPLACE FUNCTION public.touch()"), "{}", schema);
+    assert!(!schema.contains("schema_history"), "{}", schema);
+    // Objects Migrate creates for its bookkeeping, and the sequence of the identity column
+    assert!(!schema.contains("schema_version_status") && !schema.contains("schema_versions_id_seq"), "{}", schema);
+    assert!(!schema.contains("users_token_seq"), "{}", schema);
+}
@@ -0,0 +1,70 @@
+//! Integration tests for the schema history log
+extern crate mysql;
+extern crate postgres;
+
+mod common;
+
+const POSTS: common::TestMigration = ("add-posts-table", "1", "CREATE TABLE posts (id INT);", Some("DROP TABLE posts;"));
+const USERS: common::TestMigration = ("add-users-table", "2", "CREATE TABLE users (id INT);", Some("DROP TABLE users;"));
+
+fn actions(db: &str) -> Vec<(i64, String, bool)> {
+    let conn = common::connect(db);
+    let rows = conn.query("SELECT version, action, error IS NOT NULL FROM schema_history ORDER BY id", &[]).unwrap();
+    rows.iter().map(|row| (row.get(0), row.get(1), row.get(2))).collect()
+}
+
+#[test]
+#[ignore = "requires the test database, see test/Makefile"]
+fn history_records_applies_rollbacks_and_failures() {
+    let db = common::fresh_db("history_records");
+    let dir = common::migrations_dir(&db, &[POSTS, USERS]);
+
+    common::migrate_run(&db, &["-t", "2", "-m", &dir]);
+    common::migrate_run(&db, &["-t", "1", "-m", &dir]);
+
+    let bad_users = ("add-users-table", "2", "CREATE TABLE posts (id INT);", None);
+    let dir = common::migrations_dir(&db, &[POSTS, bad_users]);
+    common::migrate_run(&db, &["-t", "2", "-m", &dir]);
+
+    assert_eq!(actions(&db), vec![(1, "apply".to_owned(), false),
+                                  (2, "apply".to_owned(), false),
+                                  (2, "rollback".to_owned(), false),
+                                  (2, "fail".to_owned(), true)]);
+
+    let conn = common::connect(&db);
+    let rows = conn.query("SELECT count(*) FROM schema_history
+                           WHERE run_by <> '' AND host <> '' AND tool_version <> '' AND duration_ms IS NOT NULL", &[]).unwrap();
+    let complete: i64 = rows.get(0).get(0);
+    assert_eq!(complete, 4);
+}
+
+#[test]
+#[ignore = "requires the test database, see test/Makefile"]
+fn history_is_append_only() {
+    let db = common::fresh_db("history_append_only");
+    let dir = common::migrations_dir(&db, &[POSTS]);
+    common::migrate_run(&db, &["-t", "1", "-m", &dir]);
+
+    let conn = common::connect(&db);
+    assert!(conn.execute("UPDATE schema_history SET action = 'rollback'", &[]).is_err());
+    assert!(conn.execute("DELETE FROM schema_history", &[]).is_err());
+    assert!(conn.execute("TRUNCATE schema_history", &[]).is_err());
+    assert_eq!(actions(&db).len(), 1);
+}
+
+#[test]
+#[ignore = "requires the test database, see test/Makefile"]
+fn history_command_shows_changes() {
+    let db = common::fresh_db("history_command");
+    let dir = common::migrations_dir(&db, &[POSTS, USERS]);
+    common::migrate_run(&db, &["-t", "2", "-m", &dir]);
+
+    let (host, user, password) = (common::host(), common::user(), common::password());
+    let out = common::migrate(&["history", "-h", &host, "-u", &user, "-p", &password, "-d", &db, "-s", "2"]);
+    let stdout = common::stdout(&out);
+
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert!(lines[0].starts_with("RECORDED"), "{}", stdout);
+    assert_eq!(lines.len(), 2, "{}", stdout);
+    assert!(lines[1].contains("add-users-table") && lines[1].contains("apply"), "{}", stdout);
+}
@@ -0,0 +1,136 @@
+//! Integration tests for hooks run around migrations, run against SQLite
+extern crate mysql;
+extern crate postgres;
+extern crate rusqlite;
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+const MIGRATIONS: &[common::TestMigration] = &[
+    ("add-posts-table", "1", "CREATE TABLE posts (id INT);", Some("DROP TABLE posts;")),
+    ("add-users-table", "2", "CREATE TABLE users (id INT);", Some("DROP TABLE users;")),
+];
+
+/// Writes `hooks.ini` and a hook SQL file to a migrations directory, returns the file commands log to
+fn write_hooks(dir: &str, name: &str) -> String {
+    let log = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.log", name));
+    let _ = fs::remove_file(&log);
+    let log = log.to_string_lossy().into_owned();
+
+    let command = |stage: &str| format!(
+        "command = echo \"{} $MIGRATE_DRIVER $MIGRATE_CURRENT_VERSION->$MIGRATE_TARGET_VERSION \
+         $MIGRATE_ACTION $MIGRATE_VERSION $MIGRATE_NAME $MIGRATE_ERROR\" >> {}", stage, log);
+    let ini = format!("[before_run]\n{}\n\n[before_each]\n{}\n\n[after_each]\nsql = count.sql\n{}\n\n\
+                       [after_run]\n{}\n\n[on_failure]\n{}\n",
+                      command("before_run"), command("before_each"), command("after_each"), command("after_run"),
+                      command("on_failure"));
+    fs::write(Path::new(dir).join("hooks.ini"), ini).unwrap();
+    fs::write(Path::new(dir).join("count.sql"),
+              "CREATE TABLE IF NOT EXISTS hook_runs (n INT); INSERT INTO hook_runs VALUES (1);").unwrap();
+
+    log
+}
+
+fn lines(log: &str) -> Vec<String> {
+    fs::read_to_string(log).unwrap_or_default().lines().map(|line| line.trim_end().to_owned()).collect()
+}
+
+#[test]
+fn hooks_run_around_migrations() {
+    let path = common::fresh_sqlite_db("hooks_run");
+    let dir = common::migrations_dir("hooks_run", MIGRATIONS);
+    let log = write_hooks(&dir, "hooks_run");
+
+    let out = common::migrate(&["run", "--driver", "sqlite", "-d", &path, "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    assert_eq!(lines(&log), vec![
+        "before_run sqlite 0->2",
+        "before_each sqlite 0->2 apply 1 add-posts-table",
+        "after_each sqlite 0->2 apply 1 add-posts-table",
+        "before_each sqlite 0->2 apply 2 add-users-table",
+        "after_each sqlite 0->2 apply 2 add-users-table",
+        "after_run sqlite 0->2",
+    ]);
+
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    let runs: i64 = conn.query_row("SELECT count(*) FROM hook_runs", [], |row| row.get(0)).unwrap();
+    assert_eq!(runs, 2);
+
+    let log = write_hooks(&dir, "hooks_run");
+    let out = common::migrate(&["run", "--driver", "sqlite", "-d", &path, "-m", &dir, "-t", "1"]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    assert_eq!(lines(&log), vec![
+        "before_run sqlite 2->1",
+        "before_each sqlite 2->1 rollback 2 add-users-table",
+        "after_each sqlite 2->1 rollback 2 add-users-table",
+        "after_run sqlite 2->1",
+    ]);
+}
+
+#[test]
+fn failure_hook_gets_the_failed_migration() {
+    let path = common::fresh_sqlite_db("hooks_failure");
+    let dir = common::migrations_dir("hooks_failure", &[
+        MIGRATIONS[0],
+        ("break-things", "2", "CREATE TABLE posts (id INT);", None),
+    ]);
+    let log = write_hooks(&dir, "hooks_failure");
+
+    let out = common::migrate(&["run", "--driver", "sqlite", "-d", &path, "-m", &dir]);
+    assert!(!out.status.success(), "{}", common::stderr(&out));
+
+    let lines = lines(&log);
+    assert_eq!(lines.len(), 5, "{:?}", lines);
+    assert_eq!(lines[3], "before_each sqlite 0->2 apply 2 break-things");
+    assert!(lines[4].starts_with("on_failure sqlite 0->2 apply 2 break-things Migration \"break-things\" (version 2) \
+                                  failed: table posts already exists"), "{}", lines[4]);
+}
+
+#[test]
+fn failing_hooks_stop_the_run() {
+    let path = common::fresh_sqlite_db("hooks_stop");
+    let dir = common::migrations_dir("hooks_stop", MIGRATIONS);
+    fs::write(Path::new(&dir).join("hooks.ini"), "[after_each]\ncommand = test \"$MIGRATE_VERSION\" != 1\n").unwrap();
+
+    let out = common::migrate(&["run", "--driver", "sqlite", "-d", &path, "-m", &dir]);
+    assert!(common::stderr(&out).contains("Hook after_each `test \"$MIGRATE_VERSION\" != 1` failed"),
+            "{}", common::stderr(&out));
+
+    let out = common::migrate(&["status", "--driver", "sqlite", "-d", &path, "-m", &dir]);
+    assert!(common::stdout(&out).contains("is at version 1"), "{}", common::stdout(&out));
+
+    fs::write(Path::new(&dir).join("hooks.ini"), "[after_everything]\ncommand = true\n").unwrap();
+    let out = common::migrate(&["run", "--driver", "sqlite", "-d", &path, "-m", &dir]);
+    assert!(common::stderr(&out).contains("Unknown hook stage \"after_everything\""), "{}", common::stderr(&out));
+}
+
+#[test]
+fn run_hooks_fire_once_per_invocation() {
+    let path = common::fresh_sqlite_db("hooks_once");
+    let dir = common::migrations_dir("hooks_once", MIGRATIONS);
+
+    let out = common::migrate(&["run", "--driver", "sqlite", "-d", &path, "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    let log = write_hooks(&dir, "hooks_once");
+    let out = common::migrate(&["redo", "--driver", "sqlite", "-d", &path, "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    let logged = lines(&log);
+    assert_eq!(logged.iter().filter(|line| line.starts_with("before_run")).count(), 1, "{:?}", logged);
+    assert_eq!(logged.iter().filter(|line| line.starts_with("after_run")).count(), 1, "{:?}", logged);
+    assert_eq!(logged.len(), 6, "{:?}", logged);
+
+    // Runs refused before anything started do not fire on_failure
+    let dir = common::migrations_dir("hooks_once", &[
+        MIGRATIONS[0], MIGRATIONS[1], ("add-tags-table", "3", "CREATE TABLE {{ tags_table }} (id INT);", None),
+    ]);
+    let log = write_hooks(&dir, "hooks_once");
+    let out = common::migrate(&["run", "--driver", "sqlite", "-d", &path, "-m", &dir]);
+    assert!(common::stderr(&out).contains("placeholders without a value"), "{}", common::stderr(&out));
+    assert!(lines(&log).is_empty(), "{:?}", lines(&log));
+}
@@ -0,0 +1,70 @@
+//! Integration tests for upgrading Migrate's bookkeeping tables
+extern crate mysql;
+extern crate postgres;
+
+mod common;
+
+fn lib_vers(db: &str) -> Vec<i32> {
+    let conn = common::connect(db);
+    let rows = conn.query("SELECT lib_ver FROM schema_versions_lib ORDER BY lib_ver", &[]).unwrap();
+    rows.iter().map(|row| row.get(0)).collect()
+}
+
+#[test]
+#[ignore = "requires the test database, see test/Makefile"]
+fn fresh_database_is_upgraded_to_current_layout() {
+    let db = common::fresh_db("internal_fresh");
+
+    let out = common::migrate_run(&db, &["-t", "0", "-m", &common::migrations_dir(&db, &[])]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    let vers = lib_vers(&db);
+    assert_eq!(vers.first(), Some(&2));
+
+    // Upgrading again is a no-op
+    let out = common::migrate_run(&db, &["-t", "0", "-m", &common::migrations_dir(&db, &[])]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert_eq!(lib_vers(&db), vers);
+}
+
+#[test]
+#[ignore = "requires the test database, see test/Makefile"]
+fn older_layout_is_upgraded_in_place() {
+    let db = common::fresh_db("internal_older");
+    let conn = common::connect(&db);
+    // Layout created by versions of Migrate before schema_versions_lib existed
+    conn.batch_execute("CREATE TYPE schema_version_status AS ENUM ('ongoing', 'success', 'fail');
+                        CREATE TABLE schema_versions (
+                            id INT PRIMARY KEY NOT NULL,
+                            updated TIMESTAMP NOT NULL,
+                            version INT NOT NULL,
+                            migration_hash TEXT NOT NULL,
+                            status schema_version_status NOT NULL,
+                            lib_ver INT NOT NULL);
+                        INSERT INTO schema_versions VALUES (7, now(), 1, 'abc', 'success', 1);").unwrap();
+
+    let dir = common::migrations_dir(&db, &[("first", "1", "SELECT 1;", None)]);
+    let out = common::migrate_run(&db, &["-t", "1", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert_eq!(lib_vers(&db).first(), Some(&2));
+
+    // Existing rows are kept and new ids continue after them
+    conn.execute("INSERT INTO schema_versions (updated, version, migration_hash, status, lib_ver, name)
+                  VALUES (now(), 2, 'def', 'success', 2, 'second')", &[]).unwrap();
+    let rows = conn.query("SELECT id, name FROM schema_versions ORDER BY id", &[]).unwrap();
+    let ids: Vec<(i32, Option<String>)> = rows.iter().map(|row| (row.get(0), row.get(1))).collect();
+    assert_eq!(ids, vec![(7, None), (8, Some("second".to_owned()))]);
+}
+
+#[test]
+#[ignore = "requires the test database, see test/Makefile"]
+fn newer_layout_is_refused() {
+    let db = common::fresh_db("internal_newer");
+
+    let out = common::migrate_run(&db, &["-t", "0", "-m", &common::migrations_dir(&db, &[])]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    common::connect(&db).execute("INSERT INTO schema_versions_lib VALUES (999, now(), '99.0.0')", &[]).unwrap();
+
+    let out = common::migrate_run(&db, &["-t", "0", "-m", &common::migrations_dir(&db, &[])]);
+    assert!(common::stderr(&out).contains("newer_lib_ver"), "{}", common::stderr(&out));
+}
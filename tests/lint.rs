@@ -0,0 +1,173 @@
+//! Integration tests for the lint sub-command, which does not connect to a database
+extern crate mysql;
+extern crate postgres;
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+/// Lints a migrations directory, returning whether it passed and the problems printed on stdout
+/// followed by the logs on stderr, where a failed lint's summary goes
+fn lint(dir: &str, args: &[&str]) -> (bool, String) {
+    let mut full_args = vec!["lint", "-m", dir];
+    full_args.extend_from_slice(args);
+    let out = common::migrate(&full_args);
+    (out.status.success(), common::stdout(&out) + &common::stderr(&out))
+}
+
+#[test]
+fn valid_migrations_pass() {
+    let dir = common::migrations_dir("lint_valid", &[
+        ("add-posts-table", "1", "CREATE TABLE posts (id INT PRIMARY KEY);", Some("DROP TABLE posts;")),
+        ("add-users-table", "20261018120000", "CREATE TABLE users (id INT);", Some("DROP TABLE users;")),
+    ]);
+    fs::write(Path::new(&dir).join("add-users-table").join("migration.rs"), "pub fn up() -> i64 { 1 }").unwrap();
+
+    let (success, out) = lint(&dir, &[]);
+    assert!(success, "{}", out);
+    assert!(out.contains("2 migrations"), "{}", out);
+    assert!(out.contains("migration.rs is not checked, Rust migrations are not supported"), "{}", out);
+
+    // verify is another name for lint
+    let out = common::migrate(&["verify", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+}
+
+#[test]
+fn every_problem_is_reported() {
+    let dir = common::migrations_dir("lint_problems", &[
+        ("add-posts-table", "1", "CREATE TABLE posts (id INT);", Some("DROP TABLE posts;")),
+        ("add-users-table", "1", "CREATE TABLE users (id INT);", Some("DROP TABLE users;")),
+        ("add-tags-table", "2", "CREATE TABLE tags (id INT);", None),
+        ("add-comments-table", "3", "CREATE TABLEE comments (id INT);", Some("DROP TABLE comments;")),
+        ("add-likes-table", "three", "CREATE TABLE likes (id INT);", Some("DROP TABLE likes;")),
+        ("add-votes-table", "29991018120000", "CREATE TABLE votes (id INT);", Some("DROP TABLE votes;")),
+    ]);
+    let migration = Path::new(&dir).join("add-tags-table");
+    fs::write(migration.join("up.oracle.sql"), "CREATE TABLE tags (id NUMBER);").unwrap();
+
+    let (success, out) = lint(&dir, &[]);
+    assert!(!success, "{}", out);
+    assert!(out.contains("all provide version 1"), "{}", out);
+    assert!(out.contains("has up.sql but no down.sql"), "{}", out);
+    assert!(out.contains("up.sql does not parse as postgres SQL"), "{}", out);
+    assert!(out.contains("must contain an integer greater than 0"), "{}", out);
+    assert!(out.contains("is a timestamp in the future"), "{}", out);
+    assert!(out.contains("up.oracle.sql is not for a known driver"), "{}", out);
+    assert!(out.contains("Found 6 problems in 6 migrations"), "{}", out);
+}
+
+#[test]
+fn driver_specific_files_are_parsed_with_their_driver() {
+    let dir = common::migrations_dir("lint_driver_specific", &[
+        ("add-posts-table", "1", "CREATE TABLE posts (id SERIAL PRIMARY KEY);", Some("DROP TABLE posts;")),
+    ]);
+    let migration = Path::new(&dir).join("add-posts-table");
+    fs::write(migration.join("up.mysql.sql"), "CREATE TABLE posts (id INT AUTO_INCREMENT PRIMARY KEY) ENGINE=InnoDB;").unwrap();
+
+    let (success, out) = lint(&dir, &[]);
+    assert!(success, "{}", out);
+
+    // Without a down file for either driver the MySQL migration can not be reversed
+    fs::remove_file(migration.join("down.sql")).unwrap();
+    let (success, out) = lint(&dir, &["--driver", "mysql"]);
+    assert!(!success, "{}", out);
+    assert!(out.contains("has up.mysql.sql but no down.mysql.sql"), "{}", out);
+}
+
+#[test]
+fn destructive_operations_are_flagged_unless_acknowledged() {
+    let dir = common::migrations_dir("lint_destructive", &[
+        ("add-posts-table", "1", "CREATE TABLE posts (id INT, title TEXT);\nCREATE INDEX posts_id ON posts (id);",
+         Some("DROP TABLE posts;")),
+        ("drop-title", "2", "ALTER TABLE posts DROP COLUMN title;", Some("ALTER TABLE posts ADD COLUMN title TEXT;")),
+        ("index-posts", "3", "CREATE INDEX posts_title ON posts (title);", Some("DROP INDEX posts_title;")),
+        ("change-id", "4", "ALTER TABLE posts ALTER COLUMN id TYPE BIGINT;", Some("ALTER TABLE posts ALTER COLUMN id TYPE INT;")),
+        ("add-author", "5", "ALTER TABLE posts ADD COLUMN author TEXT NOT NULL;", Some("ALTER TABLE posts DROP COLUMN author;")),
+        ("drop-posts", "6", "-- migrate:allow drop-table\nDROP TABLE posts;", Some("CREATE TABLE posts (id INT);")),
+    ]);
+
+    let (success, out) = lint(&dir, &[]);
+    assert!(!success, "{}", out);
+    assert!(out.contains("Drops column posts.title [drop-column]"), "{}", out);
+    assert!(out.contains("[non-concurrent-index]"), "{}", out);
+    assert!(out.contains("[alter-column-type]"), "{}", out);
+    assert!(out.contains("[not-null-without-default]"), "{}", out);
+    // Acknowledged, and operations on tables created by the same migration are not flagged
+    assert!(!out.contains("[drop-table]"), "{}", out);
+    assert!(!out.contains("posts_id"), "{}", out);
+    assert!(out.contains("Found 4 problems"), "{}", out);
+
+    // Annotations only count in the comments before the first statement
+    fs::write(Path::new(&dir).join("drop-posts").join("up.sql"), "DROP TABLE posts;\n-- migrate:allow drop-table").unwrap();
+    let (success, out) = lint(&dir, &[]);
+    assert!(!success, "{}", out);
+    assert!(out.contains("Drops table posts [drop-table]"), "{}", out);
+    assert!(out.contains("Found 5 problems"), "{}", out);
+    fs::write(Path::new(&dir).join("drop-posts").join("up.sql"), "-- migrate:allow drop-table\nDROP TABLE posts;").unwrap();
+
+    fs::write(Path::new(&dir).join("lint.ini"), "[rules]\nnon-concurrent-index = warn\nalter-column-type = allow\n").unwrap();
+    let (success, out) = lint(&dir, &[]);
+    assert!(!success, "{}", out);
+    assert!(out.contains("- warning: Migration \"index-posts\""), "{}", out);
+    assert!(!out.contains("[alter-column-type]"), "{}", out);
+    assert!(out.contains("Found 2 problems"), "{}", out);
+
+    // Built concurrently the index is not flagged, but has to run outside of a transaction
+    fs::write(Path::new(&dir).join("index-posts").join("up.sql"), "CREATE INDEX CONCURRENTLY posts_title ON posts (title);").unwrap();
+    let (success, out) = lint(&dir, &[]);
+    assert!(!success, "{}", out);
+    assert!(!out.contains("[non-concurrent-index]"), "{}", out);
+    assert!(out.contains("builds an index CONCURRENTLY, which can not run in a transaction, \
+                          add a \"-- migrate:no-transaction\" header"), "{}", out);
+
+    fs::write(Path::new(&dir).join("index-posts").join("up.sql"),
+              "-- migrate:no-transaction\nCREATE INDEX CONCURRENTLY posts_title ON posts (title);").unwrap();
+    let (success, out) = lint(&dir, &[]);
+    assert!(!success, "{}", out);
+    assert!(!out.contains("index-posts"), "{}", out);
+    assert!(out.contains("Found 2 problems"), "{}", out);
+
+    fs::write(Path::new(&dir).join("lint.ini"), "[rules]\ndrop-everything = allow\n").unwrap();
+    let (success, out) = lint(&dir, &[]);
+    assert!(!success, "{}", out);
+    assert!(out.contains("Unknown rule"), "{}", out);
+}
+
+#[test]
+fn dropping_schemas_views_rows_and_type_values_is_flagged_unless_acknowledged() {
+    let dir = common::migrations_dir("lint_destructive_more", &[
+        ("add-posts-table", "1", "CREATE TABLE posts (id INT);\nCREATE VIEW new_posts AS SELECT id FROM posts;\n\
+                                  DROP VIEW new_posts;\nDROP SCHEMA empty;", Some("DROP TABLE posts;")),
+        ("drop-archive", "2", "DROP SCHEMA archive CASCADE;", Some("CREATE SCHEMA archive;")),
+        ("empty-posts", "3", "TRUNCATE posts;", Some("SELECT 1;")),
+        ("drop-recent-posts", "4", "DROP VIEW recent_posts;", Some("CREATE VIEW recent_posts AS SELECT id FROM posts;")),
+        ("drop-post-counts", "5", "DROP MATERIALIZED VIEW post_counts;",
+         Some("CREATE MATERIALIZED VIEW post_counts AS SELECT count(*) FROM posts;")),
+        ("add-mood", "6", "ALTER TYPE mood ADD VALUE 'sad';", Some("SELECT 1;")),
+    ]);
+
+    let (success, out) = lint(&dir, &[]);
+    assert!(!success, "{}", out);
+    assert!(out.contains("Drops schema archive with CASCADE, which drops every object in it [drop-schema]"), "{}", out);
+    assert!(out.contains("Truncates table posts, which deletes every row [truncate]"), "{}", out);
+    assert!(out.contains("Drops view recent_posts [drop-view]"), "{}", out);
+    assert!(out.contains("Drops materialized view post_counts [drop-view]"), "{}", out);
+    assert!(out.contains("Adds a value to type mood, which can not be used until the migration's transaction \
+                          commits [alter-type]"), "{}", out);
+    // A schema dropped without CASCADE must be empty, a view created by the same migration has no users
+    assert!(!out.contains("add-posts-table"), "{}", out);
+    assert!(out.contains("Found 5 problems"), "{}", out);
+
+    let acknowledged = [("drop-archive", "drop-schema", "DROP SCHEMA archive CASCADE;"),
+                        ("empty-posts", "truncate", "TRUNCATE posts;"),
+                        ("drop-recent-posts", "drop-view", "DROP VIEW recent_posts;"),
+                        ("drop-post-counts", "drop-view", "DROP MATERIALIZED VIEW post_counts;"),
+                        ("add-mood", "alter-type", "ALTER TYPE mood ADD VALUE 'sad';")];
+    for &(migration, rule, sql) in &acknowledged {
+        fs::write(Path::new(&dir).join(migration).join("up.sql"), format!("-- migrate:allow {}\n{}", rule, sql)).unwrap();
+    }
+    let (success, out) = lint(&dir, &[]);
+    assert!(success, "{}", out);
+}
@@ -0,0 +1,52 @@
+//! Integration tests for the logger, run against SQLite
+extern crate mysql;
+extern crate postgres;
+extern crate serde_json;
+
+mod common;
+
+use serde_json::Value;
+
+const MIGRATIONS: &[common::TestMigration] = &[
+    ("add-posts-table", "1", "CREATE TABLE posts (id INT);", Some("DROP TABLE posts;")),
+    ("add-users-table", "2", "CREATE TABLE users (id INTT", None),
+];
+
+#[test]
+fn json_logs_have_one_event_per_migration_step() {
+    let path = common::fresh_sqlite_db("logging_json");
+    let dir = common::migrations_dir("logging_json", MIGRATIONS);
+
+    let out = common::migrate(&["run", "--driver", "sqlite", "-d", &path, "-m", &dir, "--log-format", "json"]);
+    assert!(!out.status.success(), "{}", common::stderr(&out));
+
+    let records: Vec<Value> = common::stderr(&out).lines()
+        .map(|line| serde_json::from_str(line).expect("Log line is not JSON"))
+        .collect();
+    let steps: Vec<&Value> = records.iter().filter(|record| record["event"] == "migration").collect();
+
+    assert_eq!(steps.len(), 2, "{:?}", records);
+    assert_eq!(steps[0]["action"], "apply");
+    assert_eq!(steps[0]["version"], 1);
+    assert_eq!(steps[0]["name"], "add-posts-table");
+    assert_eq!(steps[0]["status"], "success");
+    assert!(steps[0]["duration_ms"].is_u64());
+    assert_eq!(steps[1]["version"], 2);
+    assert_eq!(steps[1]["status"], "fail");
+    assert_eq!(steps[1]["level"], "error");
+    assert!(steps[1]["error"].is_string());
+}
+
+#[test]
+fn verbosity_flags_change_what_is_logged() {
+    let path = common::fresh_sqlite_db("logging_verbosity");
+    let dir = common::migrations_dir("logging_verbosity", &MIGRATIONS[..1]);
+
+    let out = common::migrate(&["run", "--driver", "sqlite", "-d", &path, "-m", &dir]);
+    assert!(common::stderr(&out).contains("INFO  Applied migration \"add-posts-table\" (version 1)"),
+            "{}", common::stderr(&out));
+
+    let out = common::migrate(&["-q", "run", "--driver", "sqlite", "-d", &path, "-m", &dir, "-t", "0"]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert_eq!(common::stderr(&out), "");
+}
@@ -0,0 +1,139 @@
+//! Integration tests for the MySQL driver
+extern crate mysql;
+extern crate postgres;
+
+mod common;
+
+use mysql::prelude::Queryable;
+
+const POSTS: common::TestMigration = ("add-posts-table", "1", "CREATE TABLE posts (id INT);", Some("DROP TABLE posts;"));
+const USERS: common::TestMigration = ("add-users-table", "2", "CREATE TABLE users (id INT);", Some("DROP TABLE users;"));
+
+fn connect(db: &str) -> mysql::Conn {
+    mysql::Conn::new(mysql::Opts::from_url(&common::mysql_url(db)).unwrap()).expect("Failed to connect to MySQL test database")
+}
+
+fn versions(db: &str) -> Vec<(i64, String, Option<String>)> {
+    connect(db).query("SELECT version, status, name FROM schema_versions ORDER BY version").unwrap()
+}
+
+fn table_exists(db: &str, table: &str) -> bool {
+    let count: Option<i64> = connect(db).exec_first("SELECT count(*) FROM information_schema.tables
+                                                     WHERE table_schema = DATABASE() AND table_name = ?", (table,)).unwrap();
+    count == Some(1)
+}
+
+#[test]
+#[ignore = "requires the MySQL test database, see test/Makefile"]
+fn mysql_run_applies_and_reverses_migrations() {
+    let db = common::fresh_mysql_db("mysql_up_down");
+    let dir = common::migrations_dir("mysql_up_down", &[POSTS, USERS]);
+    let url = common::mysql_url(&db);
+
+    let out = common::migrate(&["run", "--url", &url, "-t", "2", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert!(table_exists(&db, "posts"));
+    assert!(table_exists(&db, "users"));
+    assert_eq!(versions(&db), vec![(1, "success".to_owned(), Some("add-posts-table".to_owned())),
+                                   (2, "success".to_owned(), Some("add-users-table".to_owned()))]);
+
+    let out = common::migrate(&["run", "--url", &url, "-t", "0", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert!(!table_exists(&db, "posts"));
+    assert!(versions(&db).is_empty());
+
+    let out = common::migrate(&["history", "--url", &url]);
+    let history = common::stdout(&out);
+    assert_eq!(history.lines().filter(|line| line.contains(" apply ")).count(), 2, "{}", history);
+    assert_eq!(history.lines().filter(|line| line.contains(" rollback ")).count(), 2, "{}", history);
+}
+
+#[test]
+#[ignore = "requires the MySQL test database, see test/Makefile"]
+fn mysql_history_is_append_only() {
+    let db = common::fresh_mysql_db("mysql_history");
+    let dir = common::migrations_dir("mysql_history", &[POSTS]);
+
+    let out = common::migrate(&["run", "--url", &common::mysql_url(&db), "-t", "1", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    let mut conn = connect(&db);
+    let db_user: Option<String> = conn.query_first("SELECT db_user FROM schema_history").unwrap();
+    assert!(db_user.unwrap().starts_with(&format!("{}@", common::mysql_user())));
+    assert!(conn.query_drop("DELETE FROM schema_history").is_err());
+}
+
+#[test]
+#[ignore = "requires the MySQL test database, see test/Makefile"]
+fn mysql_interrupted_upgrade_is_resumed() {
+    let db = common::fresh_mysql_db("mysql_resume_upgrade");
+    let dir = common::migrations_dir("mysql_resume_upgrade", &[POSTS]);
+    let url = common::mysql_url(&db);
+
+    let out = common::migrate(&["run", "--url", &url, "-t", "0", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    // MySQL commits DDL as it runs, leave internal migration 3 half done as if creating a trigger failed
+    let mut conn = connect(&db);
+    conn.query_drop("DELETE FROM schema_versions_lib WHERE lib_ver >= 3").unwrap();
+    conn.query_drop("ALTER TABLE schema_versions DROP COLUMN duration_ms").unwrap();
+    conn.query_drop("DROP TRIGGER schema_history_no_delete").unwrap();
+
+    let out = common::migrate(&["run", "--url", &url, "-t", "1", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert_eq!(versions(&db), vec![(1, "success".to_owned(), Some("add-posts-table".to_owned()))]);
+    assert!(conn.query_drop("DELETE FROM schema_history").is_err());
+}
+
+#[test]
+#[ignore = "requires the MySQL test database, see test/Makefile"]
+fn mysql_migration_with_ddl_is_not_retried() {
+    let db = common::fresh_mysql_db("mysql_no_retry");
+    let seed = ("add-post", "2", "INSERT INTO posts VALUES (1);", None);
+    let dir = common::migrations_dir("mysql_no_retry", &[POSTS, seed]);
+    let url = common::mysql_url(&db);
+
+    let out = common::migrate(&["run", "--url", &url, "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    // CREATE TABLE is committed before the UPDATE waits for the lock, running it again would fail
+    let blocked = ("add-users-table", "3", "CREATE TABLE users (id INT); UPDATE posts SET id = 2;", None);
+    let dir = common::migrations_dir("mysql_no_retry", &[POSTS, seed, blocked]);
+
+    let mut holder = connect(&db);
+    holder.query_drop("BEGIN").unwrap();
+    holder.query_drop("SELECT * FROM posts FOR UPDATE").unwrap();
+
+    let out = common::migrate(&["run", "--url", &url, "-m", &dir, "--lock-timeout", "1s", "--retry-delay", "10ms"]);
+    holder.query_drop("ROLLBACK").unwrap();
+
+    assert!(!out.status.success());
+    assert!(!common::stderr(&out).contains("retrying"), "{}", common::stderr(&out));
+    assert!(table_exists(&db, "users"));
+}
+
+#[test]
+#[ignore = "requires the MySQL test database, see test/Makefile"]
+fn mysql_timeouts_are_reset_after_each_migration() {
+    let db = common::fresh_mysql_db("mysql_reset_timeouts");
+    let table = ("add-timeouts-table", "1", "CREATE TABLE timeouts (lock_wait BIGINT, execution BIGINT,
+                                                                   default_lock_wait BIGINT, default_execution BIGINT);", None);
+    let failing = ("select-missing-column", "2", "SELECT missing_column FROM timeouts;", None);
+    let dir = common::migrations_dir("mysql_reset_timeouts", &[table, failing]);
+
+    // Hooks run on the same session as the migrations
+    let record = "INSERT INTO timeouts SELECT @@SESSION.innodb_lock_wait_timeout, @@SESSION.max_execution_time,
+                                              @@GLOBAL.innodb_lock_wait_timeout, @@GLOBAL.max_execution_time;";
+    std::fs::write(std::path::Path::new(&dir).join("record.sql"), record).unwrap();
+    std::fs::write(std::path::Path::new(&dir).join("hooks.ini"), "[after_each]\nsql = record.sql\n\n[on_failure]\nsql = record.sql\n").unwrap();
+
+    let out = common::migrate(&["run", "--url", &common::mysql_url(&db), "-m", &dir,
+                                "--statement-timeout", "2s", "--lock-timeout", "7s"]);
+    assert!(!out.status.success());
+
+    let recorded: Vec<(i64, i64, i64, i64)> = connect(&db).query("SELECT * FROM timeouts").unwrap();
+    assert_eq!(recorded.len(), 2, "{}", common::stderr(&out));
+    for (lock_wait, execution, default_lock_wait, default_execution) in recorded {
+        assert_eq!((lock_wait, execution), (default_lock_wait, default_execution));
+    }
+}
@@ -0,0 +1,99 @@
+//! Integration tests for `--output json`, run against SQLite
+extern crate mysql;
+extern crate postgres;
+extern crate serde_json;
+
+mod common;
+
+use serde_json::Value;
+
+const MIGRATIONS: &[common::TestMigration] = &[
+    ("add-posts-table", "1", "CREATE TABLE posts (id INT);", Some("DROP TABLE posts;")),
+    ("add-users-table", "2", "CREATE TABLE users (id INT);", Some("DROP TABLE users;")),
+    ("add-tags-table", "3", "CREATE TABLE tags (id INTT", Some("DROP TABLE tags;")),
+    ("add-likes-table", "4", "CREATE TABLE likes (id INT);", Some("DROP TABLE likes;")),
+];
+
+/// Parses stdout, which must hold nothing but a JSON document
+fn json(out: &std::process::Output) -> Value {
+    serde_json::from_str(&common::stdout(out)).expect("Output is not JSON")
+}
+
+#[test]
+fn run_reports_every_migration_planned() {
+    let path = common::fresh_sqlite_db("output_run");
+    let dir = common::migrations_dir("output_run", MIGRATIONS);
+
+    let out = common::migrate(&["run", "--driver", "sqlite", "-d", &path, "-m", &dir, "--output", "json"]);
+    assert!(!out.status.success());
+
+    let report = json(&out);
+    assert_eq!(report["status"], "fail");
+    assert!(report["error"].as_str().unwrap().contains("add-tags-table"), "{}", report);
+    assert_eq!(report["initial_version"], 0);
+    assert_eq!(report["target_version"], 4);
+    assert_eq!(report["final_version"], 2);
+    assert_eq!(report["summary"]["applied"], 2);
+    assert_eq!(report["summary"]["failed"], 1);
+    assert_eq!(report["summary"]["planned"], 1);
+
+    let migrations = report["migrations"].as_array().unwrap();
+    let statuses: Vec<&str> = migrations.iter().map(|m| m["status"].as_str().unwrap()).collect();
+    assert_eq!(statuses, ["applied", "applied", "failed", "planned"]);
+    assert_eq!(migrations[0]["action"], "apply");
+    assert!(migrations[0]["duration_ms"].is_u64());
+    assert!(migrations[2]["error"].is_string());
+    assert!(migrations[3]["duration_ms"].is_null());
+}
+
+#[test]
+fn run_reports_reversed_migrations() {
+    let path = common::fresh_sqlite_db("output_rollback");
+    let dir = common::migrations_dir("output_rollback", &MIGRATIONS[..2]);
+
+    common::migrate(&["run", "--driver", "sqlite", "-d", &path, "-m", &dir]);
+
+    let out = common::migrate(&["run", "--driver", "sqlite", "-d", &path, "-m", &dir, "-t", "0", "--output", "json"]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    let report = json(&out);
+    assert_eq!(report["status"], "success");
+    assert!(report["error"].is_null());
+    assert_eq!(report["initial_version"], 2);
+    assert_eq!(report["final_version"], 0);
+    assert_eq!(report["migrations"][0]["version"], 2);
+    assert_eq!(report["migrations"][0]["action"], "rollback");
+    assert_eq!(report["migrations"][0]["status"], "reversed");
+    assert_eq!(report["summary"]["reversed"], 2);
+}
+
+#[test]
+fn status_and_history_print_json() {
+    let path = common::fresh_sqlite_db("output_status");
+    let dir = common::migrations_dir("output_status", MIGRATIONS);
+
+    common::migrate(&["run", "--driver", "sqlite", "-d", &path, "-m", &dir, "-t", "2"]);
+
+    let out = common::migrate(&["status", "--driver", "sqlite", "-d", &path, "-m", &dir, "--output", "json"]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    let status = json(&out);
+    assert_eq!(status["current_version"], 2);
+    assert_eq!(status["latest_version"], 4);
+    assert_eq!(status["pending"], 2);
+    let statuses: Vec<&str> = status["migrations"].as_array().unwrap().iter()
+        .map(|m| m["status"].as_str().unwrap())
+        .collect();
+    assert_eq!(statuses, ["applied", "applied", "pending", "pending"]);
+    assert!(status["migrations"][0]["updated"].is_string());
+    assert_eq!(status["migrations"][0]["changed"], false);
+
+    let out = common::migrate(&["history", "--driver", "sqlite", "-d", &path, "--output", "json"]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    let history = json(&out);
+    let entries = history.as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[1]["version"], 2);
+    assert_eq!(entries[1]["action"], "apply");
+}
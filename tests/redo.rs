@@ -0,0 +1,108 @@
+//! Integration tests for the redo sub-command, run against SQLite
+extern crate mysql;
+extern crate postgres;
+extern crate rusqlite;
+extern crate serde_json;
+
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Output;
+
+const MIGRATIONS: &[common::TestMigration] = &[
+    ("add-posts-table", "1", "CREATE TABLE posts (id INT);", Some("DROP TABLE posts;")),
+    ("add-users-table", "2", "CREATE TABLE users (id INT);", Some("DROP TABLE users;")),
+    ("add-tags-table", "3", "CREATE TABLE tags (id INT);", Some("DROP TABLE tags;")),
+];
+
+/// Actions recorded in the history, oldest first, as (version, action)
+fn actions(path: &str) -> Vec<(i64, String)> {
+    let conn = rusqlite::Connection::open(path).unwrap();
+    let mut stmt = conn.prepare("SELECT version, action FROM schema_history ORDER BY id").unwrap();
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+    rows.map(|row| row.unwrap()).collect()
+}
+
+fn redo(path: &str, dir: &str, args: &[&str]) -> Output {
+    let mut full_args = vec!["redo", "--driver", "sqlite", "-d", path, "-m", dir];
+    full_args.extend_from_slice(args);
+    common::migrate(&full_args)
+}
+
+#[test]
+fn redo_reverses_and_reapplies_the_last_migrations() {
+    let path = common::fresh_sqlite_db("redo_last");
+    let dir = common::migrations_dir("redo_last", MIGRATIONS);
+
+    let out = common::migrate(&["run", "--driver", "sqlite", "-d", &path, "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    let out = redo(&path, &dir, &[]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert_eq!(actions(&path)[3..], [(3, "rollback".to_owned()), (3, "apply".to_owned())]);
+
+    let out = redo(&path, &dir, &["2"]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert_eq!(actions(&path)[5..], [(3, "rollback".to_owned()), (2, "rollback".to_owned()),
+                                     (2, "apply".to_owned()), (3, "apply".to_owned())]);
+
+    let out = redo(&path, &dir, &["4"]);
+    assert!(common::stderr(&out).contains("only 3 are applied"), "{}", common::stderr(&out));
+}
+
+#[test]
+fn redo_is_refused_in_production() {
+    let path = common::fresh_sqlite_db("redo_production");
+    let dir = common::migrations_dir("redo_production", MIGRATIONS);
+
+    let config = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("redo_production.ini");
+    fs::write(&config, format!("[production]\ndriver=sqlite\ndatabase={}\n\n\
+                                [staging]\ndriver=sqlite\ndatabase={}\nproduction=true\n\n\
+                                [development]\ndriver=sqlite\ndatabase={}\n", path, path, path)).unwrap();
+    let config = config.to_string_lossy().into_owned();
+
+    let out = common::migrate(&["run", "-c", &config, "-e", "development", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    for env in &["production", "staging"] {
+        let out = common::migrate(&["redo", "-c", &config, "-e", env, "-m", &dir]);
+        assert!(common::stderr(&out).contains("Refusing to redo migrations in the production environment"),
+                "{}", common::stderr(&out));
+    }
+    assert_eq!(actions(&path).len(), 3);
+
+    let out = common::migrate(&["redo", "-c", &config, "-e", "development", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert_eq!(actions(&path).len(), 5);
+}
+
+#[test]
+fn redo_takes_the_options_of_run() {
+    let path = common::fresh_sqlite_db("redo_options");
+    let dir = common::migrations_dir("redo_options", MIGRATIONS);
+
+    let out = common::migrate(&["run", "--driver", "sqlite", "-d", &path, "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    // A dry run prints the down then the up SQL, without changing anything
+    let out = redo(&path, &dir, &["--dry-run"]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    let sql = common::stdout(&out);
+    let down = sql.find("DROP TABLE tags;").expect(&sql);
+    let up = sql.find("CREATE TABLE tags (id INT);").expect(&sql);
+    assert!(down < up, "{}", sql);
+    assert!(!sql.contains("users"), "{}", sql);
+    assert_eq!(actions(&path).len(), 3);
+
+    let out = redo(&path, &dir, &["--output", "json", "--retries", "0", "--lock-timeout", "5s"]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    let report: serde_json::Value = serde_json::from_str(&common::stdout(&out)).expect("Output is not JSON");
+    assert_eq!(report["status"], "success");
+    assert_eq!(report["initial_version"], 3);
+    assert_eq!(report["final_version"], 3);
+    let steps: Vec<(i64, &str, &str)> = report["migrations"].as_array().unwrap().iter()
+        .map(|m| (m["version"].as_i64().unwrap(), m["action"].as_str().unwrap(), m["status"].as_str().unwrap()))
+        .collect();
+    assert_eq!(steps, [(3, "rollback", "reversed"), (3, "apply", "applied")]);
+}
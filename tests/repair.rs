@@ -0,0 +1,88 @@
+//! Integration tests for repairing the bookkeeping tables
+extern crate mysql;
+extern crate postgres;
+
+mod common;
+
+const POSTS: common::TestMigration = ("add-posts-table", "1", "CREATE TABLE posts (id INT);", Some("DROP TABLE posts;"));
+
+fn ids(db: &str) -> Vec<i32> {
+    let conn = common::connect(db);
+    let rows = conn.query("SELECT id FROM schema_versions ORDER BY id", &[]).unwrap();
+    rows.iter().map(|row| row.get(0)).collect()
+}
+
+fn repair(db: &str, args: &[&str]) -> String {
+    let (host, user, password) = (common::host(), common::user(), common::password());
+    let mut full_args = vec!["repair", "duplicates", "-h", &host, "-u", &user, "-p", &password, "-d", db];
+    full_args.extend_from_slice(args);
+    common::stdout(&common::migrate(&full_args))
+}
+
+/// Applies a migration then adds an older duplicate row for its version
+fn setup(name: &str) -> (String, String) {
+    let db = common::fresh_db(name);
+    let dir = common::migrations_dir(&db, &[POSTS]);
+    common::migrate_run(&db, &["-t", "1", "-m", &dir]);
+
+    common::connect(&db).execute("INSERT INTO schema_versions (updated, version, migration_hash, status, lib_ver, name)
+                                  VALUES ('2000-01-01', 1, 'old', 'fail', 1, 'add-posts-table')", &[]).unwrap();
+    (db, dir)
+}
+
+#[test]
+#[ignore = "requires the test database, see test/Makefile"]
+fn duplicates_are_reported_without_changes() {
+    let (db, dir) = setup("repair_reported");
+
+    let out = common::migrate_run(&db, &["-t", "1", "-m", &dir]);
+    assert!(common::stderr(&out).contains("duplicate_versions"), "{}", common::stderr(&out));
+    assert_eq!(ids(&db), vec![1, 2]);
+}
+
+/// The duplicate appears while the migration is reversed, after the run checked the recorded versions
+#[test]
+#[ignore = "requires the test database, see test/Makefile"]
+fn rollback_refuses_to_remove_duplicates() {
+    let db = common::fresh_db("repair_rollback");
+    let down = "DROP TABLE posts;
+                INSERT INTO schema_versions (updated, version, migration_hash, status, lib_ver, name)
+                VALUES ('2000-01-01', 1, 'old', 'fail', 1, 'add-posts-table');";
+    let dir = common::migrations_dir(&db, &[("add-posts-table", "1", "CREATE TABLE posts (id INT);", Some(down))]);
+    common::migrate_run(&db, &["-t", "1", "-m", &dir]);
+
+    let out = common::migrate_run(&db, &["-t", "0", "-m", &dir]);
+    assert!(!out.status.success());
+    assert!(common::stderr(&out).contains("duplicate_versions"), "{}", common::stderr(&out));
+    assert!(common::stderr(&out).contains("repair duplicates"), "{}", common::stderr(&out));
+    assert_eq!(ids(&db), vec![1]);
+}
+
+#[test]
+#[ignore = "requires the test database, see test/Makefile"]
+fn dry_run_shows_plan_without_changes() {
+    let (db, _) = setup("repair_dry_run");
+
+    let out = repair(&db, &["--dry-run"]);
+    assert!(out.contains("keep   row 1 (success"), "{}", out);
+    assert!(out.contains("delete row 2 (fail"), "{}", out);
+    assert_eq!(ids(&db), vec![1, 2]);
+}
+
+#[test]
+#[ignore = "requires the test database, see test/Makefile"]
+fn repair_deletes_duplicates_and_records_history() {
+    let (db, dir) = setup("repair_deletes");
+
+    let out = repair(&db, &[]);
+    assert!(out.contains("Deleted 1 duplicate rows"), "{}", out);
+    assert_eq!(ids(&db), vec![1]);
+
+    let conn = common::connect(&db);
+    let rows = conn.query("SELECT action FROM schema_history ORDER BY id DESC LIMIT 1", &[]).unwrap();
+    let action: String = rows.get(0).get(0);
+    assert_eq!(action, "repair");
+
+    let out = common::migrate_run(&db, &["-t", "1", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+}
@@ -0,0 +1,89 @@
+//! Integration tests for repeatable migrations, run against SQLite
+extern crate mysql;
+extern crate postgres;
+extern crate rusqlite;
+extern crate serde_json;
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use serde_json::Value;
+
+const MIGRATIONS: &[common::TestMigration] = &[
+    ("add-posts-table", "1", "CREATE TABLE posts (id INT, title TEXT);", Some("DROP TABLE posts;")),
+    ("add-posts-body", "2", "ALTER TABLE posts ADD COLUMN body TEXT;", Some("ALTER TABLE posts DROP COLUMN body;")),
+];
+
+/// Writes a repeatable migration to a migrations directory
+fn write_repeatable(dir: &str, file: &str, sql: &str) {
+    let repeatable_dir = Path::new(dir).join("repeatable");
+    fs::create_dir_all(&repeatable_dir).unwrap();
+    fs::write(repeatable_dir.join(file), sql).unwrap();
+}
+
+fn run_json(path: &str, dir: &str, args: &[&str]) -> Value {
+    let mut run_args = vec!["run", "--driver", "sqlite", "-d", path, "-m", dir, "--output", "json"];
+    run_args.extend_from_slice(args);
+    let out = common::migrate(&run_args);
+    serde_json::from_str(&common::stdout(&out)).unwrap()
+}
+
+fn recorded_hashes(path: &str) -> Vec<(String, String)> {
+    let conn = rusqlite::Connection::open(path).unwrap();
+    let mut stmt = conn.prepare("SELECT name, migration_hash FROM schema_repeatables ORDER BY name").unwrap();
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+    rows.map(|row| row.unwrap()).collect()
+}
+
+#[test]
+fn repeatables_are_applied_again_when_they_change() {
+    let path = common::fresh_sqlite_db("repeatable_changes");
+    let dir = common::migrations_dir("repeatable_changes", MIGRATIONS);
+    write_repeatable(&dir, "posts_titles.sql", "DROP VIEW IF EXISTS posts_titles;\nCREATE VIEW posts_titles AS SELECT title FROM posts;");
+    write_repeatable(&dir, "posts_search.postgres.sql", "CREATE FUNCTION posts_search() RETURNS void AS $$ $$ LANGUAGE sql;");
+
+    // Not applied until the database reaches the latest version
+    let report = run_json(&path, &dir, &["-t", "1"]);
+    assert_eq!(report["status"], "success", "{}", report);
+    assert_eq!(report["repeatables"].as_array().unwrap().len(), 0, "{}", report);
+
+    let report = run_json(&path, &dir, &[]);
+    assert_eq!(report["status"], "success", "{}", report);
+    assert_eq!(report["migrations"][0]["version"], 2);
+    let repeatables = report["repeatables"].as_array().unwrap();
+    assert_eq!(repeatables.len(), 1, "{}", report);
+    assert_eq!(repeatables[0]["name"], "posts_titles");
+    assert_eq!(repeatables[0]["status"], "applied");
+
+    let hashes = recorded_hashes(&path);
+    assert_eq!(hashes.len(), 1);
+    assert_eq!(hashes[0].0, "posts_titles");
+
+    let report = run_json(&path, &dir, &[]);
+    assert_eq!(report["repeatables"].as_array().unwrap().len(), 0, "{}", report);
+
+    write_repeatable(&dir, "posts_titles.sql", "DROP VIEW IF EXISTS posts_titles;\nCREATE VIEW posts_titles AS SELECT title, body FROM posts;");
+    let report = run_json(&path, &dir, &[]);
+    assert_eq!(report["repeatables"][0]["status"], "applied", "{}", report);
+    assert_ne!(recorded_hashes(&path)[0].1, hashes[0].1);
+
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    let cols = conn.prepare("SELECT * FROM posts_titles").unwrap().column_count();
+    assert_eq!(cols, 2);
+}
+
+#[test]
+fn failed_repeatables_are_not_recorded() {
+    let path = common::fresh_sqlite_db("repeatable_failure");
+    let dir = common::migrations_dir("repeatable_failure", MIGRATIONS);
+    write_repeatable(&dir, "broken.sql", "CREATE VIEW broken AS SELEC 1;");
+
+    let report = run_json(&path, &dir, &[]);
+    assert_eq!(report["status"], "fail", "{}", report);
+    assert_eq!(report["final_version"], 2);
+    assert_eq!(report["repeatables"][0]["status"], "failed");
+    assert!(report["error"].as_str().unwrap().starts_with("Repeatable migration \"broken\" failed"), "{}", report);
+    assert!(recorded_hashes(&path).is_empty());
+}
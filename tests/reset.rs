@@ -0,0 +1,168 @@
+//! Integration tests for the reset, drop and recreate sub-commands
+extern crate mysql;
+extern crate postgres;
+extern crate rusqlite;
+extern crate serde_json;
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const MIGRATIONS: &[common::TestMigration] = &[
+    ("add-posts-table", "1", "CREATE TABLE posts (id INT);", Some("DROP TABLE posts;")),
+    ("add-users-table", "2", "CREATE TABLE users (id INT);", Some("DROP TABLE users;")),
+];
+
+/// Actions recorded in the history, oldest first, as (version, action)
+fn actions(path: &str) -> Vec<(i64, String)> {
+    let conn = rusqlite::Connection::open(path).unwrap();
+    let mut stmt = conn.prepare("SELECT version, action FROM schema_history ORDER BY id").unwrap();
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+    rows.map(|row| row.unwrap()).collect()
+}
+
+/// Write a config file with a development and a protected environment for a SQLite database
+fn config(name: &str, path: &str) -> String {
+    let config = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.ini", name));
+    fs::write(&config, format!("[development]\ndriver=sqlite\ndatabase={}\n\n\
+                                [shared]\ndriver=sqlite\ndatabase={}\nprotected=true\n", path, path)).unwrap();
+    config.to_string_lossy().into_owned()
+}
+
+#[test]
+fn reset_reverses_and_reapplies_every_migration() {
+    let path = common::fresh_sqlite_db("reset_all");
+    let dir = common::migrations_dir("reset_all", MIGRATIONS);
+    let config = config("reset_all", &path);
+
+    let out = common::migrate(&["run", "-c", &config, "-e", "development", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    let out = common::migrate(&["reset", "-c", &config, "-e", "shared", "-m", &dir]);
+    assert!(common::stderr(&out).contains("Refusing to reset the database in the protected environment"),
+            "{}", common::stderr(&out));
+    assert_eq!(actions(&path).len(), 2);
+
+    let out = common::migrate(&["reset", "-c", &config, "-e", "development", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    let actions: Vec<String> = actions(&path).into_iter().map(|(_, action)| action).collect();
+    assert_eq!(actions, vec!["apply", "apply", "rollback", "rollback", "apply", "apply"]);
+}
+
+#[test]
+fn reset_takes_the_options_of_run() {
+    let path = common::fresh_sqlite_db("reset_options");
+    let dir = common::migrations_dir("reset_options", MIGRATIONS);
+    let config = config("reset_options", &path);
+
+    let out = common::migrate(&["run", "-c", &config, "-e", "development", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    // A dry run prints every down then every up SQL, without changing anything
+    let out = common::migrate(&["reset", "-c", &config, "-e", "development", "-m", &dir, "--dry-run"]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    let sql = common::stdout(&out);
+    let positions: Vec<usize> = ["DROP TABLE users;", "DROP TABLE posts;", "CREATE TABLE posts", "CREATE TABLE users"].iter()
+        .map(|statement| sql.find(statement).expect(&sql))
+        .collect();
+    assert!(positions.windows(2).all(|pair| pair[0] < pair[1]), "{}", sql);
+    assert_eq!(actions(&path).len(), 2);
+
+    let out = common::migrate(&["reset", "-c", &config, "-e", "development", "-m", &dir, "--output", "json",
+                                "--retries", "0"]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    let report: serde_json::Value = serde_json::from_str(&common::stdout(&out)).expect("Output is not JSON");
+    assert_eq!(report["status"], "success");
+    assert_eq!(report["summary"]["reversed"], 2);
+    assert_eq!(report["summary"]["applied"], 2);
+    assert_eq!(report["final_version"], 2);
+}
+
+#[test]
+fn drop_and_recreate_sqlite_database() {
+    let path = common::fresh_sqlite_db("drop_sqlite");
+    let dir = common::migrations_dir("drop_sqlite", MIGRATIONS);
+    let config = config("drop_sqlite", &path);
+
+    let out = common::migrate(&["run", "-c", &config, "-e", "development", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    for cmd in &["drop", "recreate"] {
+        let out = common::migrate(&[cmd, "-c", &config, "-e", "shared"]);
+        assert!(common::stderr(&out).contains("in the protected environment"), "{}", common::stderr(&out));
+        assert!(Path::new(&path).exists());
+    }
+
+    let out = common::migrate(&["recreate", "-c", &config, "-e", "development"]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert!(Path::new(&path).exists());
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    let tables: i64 = conn.query_row("SELECT count(*) FROM sqlite_master", [], |row| row.get(0)).unwrap();
+    assert_eq!(tables, 0);
+
+    let out = common::migrate(&["drop", "-c", &config, "-e", "development"]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert!(!Path::new(&path).exists());
+}
+
+#[test]
+#[ignore = "requires the test database, see test/Makefile"]
+fn drop_and_recreate_postgres_database() {
+    let db = common::fresh_db("drop_postgres");
+    let dir = common::migrations_dir(&db, MIGRATIONS);
+
+    let out = common::migrate_run(&db, &["-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    let (host, user, password) = (common::host(), common::user(), common::password());
+    let url = format!("postgres://{}:{}@{}/{}", user, password, host, db);
+
+    // Selecting the maintenance database with the one being dropped is refused
+    let out = common::migrate(&["drop", "--url", &url, "--maintenance-database", &db]);
+    assert!(common::stderr(&out).contains("Can not drop the maintenance database"), "{}", common::stderr(&out));
+
+    let out = common::migrate(&["recreate", "--url", &url, "--maintenance-database", "test_db"]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    {
+        let conn = common::connect(&db);
+        let rows = conn.query("SELECT to_regclass('posts') IS NULL", &[]).unwrap();
+        assert!(rows.get(0).get::<_, bool>(0));
+    }
+
+    let out = common::migrate(&["drop", "-h", &host, "-u", &user, "-p", &password, "-d", &db,
+                                "--maintenance-database", "test_db"]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    let conn = common::connect("test_db");
+    let rows = conn.query("SELECT count(*) FROM pg_database WHERE datname = $1", &[&db]).unwrap();
+    assert_eq!(rows.get(0).get::<_, i64>(0), 0);
+}
+
+#[test]
+fn protected_environments_require_confirmation_to_go_down() {
+    let path = common::fresh_sqlite_db("protected_confirm");
+    let dir = common::migrations_dir("protected_confirm", MIGRATIONS);
+    let config = config("protected_confirm", &path);
+
+    // Going up needs no confirmation
+    let out = common::migrate(&["run", "-c", &config, "-e", "shared", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    // Tests run without a terminal to confirm on
+    let out = common::migrate(&["run", "-c", &config, "-e", "shared", "-m", &dir, "-t", "0"]);
+    assert!(common::stderr(&out).contains("without confirmation, run with --yes-i-am-sure to confirm"),
+            "{}", common::stderr(&out));
+    let out = common::migrate(&["reset", "-c", &config, "-e", "shared", "-m", &dir]);
+    assert!(common::stderr(&out).contains("without confirmation"), "{}", common::stderr(&out));
+    assert_eq!(actions(&path).len(), 2);
+
+    let out = common::migrate(&["run", "-c", &config, "-e", "shared", "-m", &dir, "-t", "1",
+                                "--yes-i-am-sure"]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    // Confirmed once for the whole reset
+    let out = common::migrate(&["reset", "-c", &config, "-e", "shared", "-m", &dir, "--yes-i-am-sure"]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    let actions: Vec<String> = actions(&path).into_iter().map(|(_, action)| action).collect();
+    assert_eq!(actions, vec!["apply", "apply", "rollback", "rollback", "apply", "apply"]);
+}
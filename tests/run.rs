@@ -0,0 +1,111 @@
+//! Integration tests for running migrations
+extern crate mysql;
+extern crate postgres;
+
+mod common;
+
+const POSTS: common::TestMigration = ("add-posts-table", "1", "CREATE TABLE posts (id INT);", Some("DROP TABLE posts;"));
+const USERS: common::TestMigration = ("add-users-table", "2", "CREATE TABLE users (id INT);", Some("DROP TABLE users;"));
+
+fn versions(db: &str) -> Vec<(i64, String, Option<String>)> {
+    let conn = common::connect(db);
+    let rows = conn.query("SELECT version, status::text, name FROM schema_versions ORDER BY version", &[]).unwrap();
+    rows.iter().map(|row| (row.get(0), row.get(1), row.get(2))).collect()
+}
+
+fn table_exists(db: &str, table: &str) -> bool {
+    let conn = common::connect(db);
+    let rows = conn.query("SELECT to_regclass($1) IS NOT NULL", &[&table]).unwrap();
+    rows.get(0).get(0)
+}
+
+#[test]
+#[ignore = "requires the test database, see test/Makefile"]
+fn run_applies_and_reverses_migrations() {
+    let db = common::fresh_db("run_up_down");
+    let dir = common::migrations_dir(&db, &[POSTS, USERS]);
+
+    let out = common::migrate_run(&db, &["-t", "2", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert!(table_exists(&db, "posts"));
+    assert!(table_exists(&db, "users"));
+    assert_eq!(versions(&db), vec![(1, "success".to_owned(), Some("add-posts-table".to_owned())),
+                                   (2, "success".to_owned(), Some("add-users-table".to_owned()))]);
+
+    let out = common::migrate_run(&db, &["-t", "1", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert!(table_exists(&db, "posts"));
+    assert!(!table_exists(&db, "users"));
+    assert_eq!(versions(&db), vec![(1, "success".to_owned(), Some("add-posts-table".to_owned()))]);
+
+    let out = common::migrate_run(&db, &["-t", "0", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert!(!table_exists(&db, "posts"));
+    assert!(versions(&db).is_empty());
+}
+
+#[test]
+#[ignore = "requires the test database, see test/Makefile"]
+fn failed_migration_is_recorded_and_retried() {
+    let db = common::fresh_db("run_fail");
+    let bad_users = ("add-users-table", "2", "CREATE TABLE users (id INT); SELECT missing_column FROM users;", None);
+    let dir = common::migrations_dir(&db, &[POSTS, bad_users]);
+
+    let out = common::migrate_run(&db, &["-t", "2", "-m", &dir]);
+    assert!(common::stderr(&out).contains("failed"), "{}", common::stderr(&out));
+    // Failed migration is rolled back, earlier migrations are kept
+    assert!(table_exists(&db, "posts"));
+    assert!(!table_exists(&db, "users"));
+    assert_eq!(versions(&db)[1], (2, "fail".to_owned(), Some("add-users-table".to_owned())));
+
+    let dir = common::migrations_dir(&db, &[POSTS, USERS]);
+    let out = common::migrate_run(&db, &["-t", "2", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert!(table_exists(&db, "users"));
+    assert_eq!(versions(&db)[1], (2, "success".to_owned(), Some("add-users-table".to_owned())));
+}
+
+#[test]
+#[ignore = "requires the test database, see test/Makefile"]
+fn unknown_status_is_reported() {
+    let db = common::fresh_db("run_bad_status");
+    let dir = common::migrations_dir(&db, &[POSTS]);
+
+    let out = common::migrate_run(&db, &["-t", "1", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    let conn = common::connect(&db);
+    conn.batch_execute("ALTER TYPE schema_version_status ADD VALUE 'paused'").unwrap();
+    conn.batch_execute("UPDATE schema_versions SET status = 'paused'").unwrap();
+
+    let out = common::migrate_run(&db, &["-t", "1", "-m", &dir]);
+    assert!(common::stderr(&out).contains("row_construct_fail"), "{}", common::stderr(&out));
+}
+
+#[test]
+#[ignore = "requires the test database, see test/Makefile"]
+fn no_transaction_header_allows_concurrent_indexes() {
+    let db = common::fresh_db("run_no_transaction");
+    let index = ("index-posts", "2", "CREATE INDEX CONCURRENTLY posts_id_idx ON posts (id);",
+                 Some("DROP INDEX CONCURRENTLY posts_id_idx;"));
+    let dir = common::migrations_dir(&db, &[POSTS, index]);
+
+    let out = common::migrate_run(&db, &["-m", &dir]);
+    assert!(common::stderr(&out).contains("cannot run inside a transaction block"), "{}", common::stderr(&out));
+    assert!(!table_exists(&db, "posts_id_idx"));
+
+    let index = ("index-posts", "2", "-- migrate:no-transaction\nCREATE INDEX CONCURRENTLY posts_id_idx ON posts (id);",
+                 Some("-- migrate:no-transaction\nDROP INDEX CONCURRENTLY posts_id_idx;"));
+    let dir = common::migrations_dir(&db, &[POSTS, index]);
+
+    let out = common::migrate_run(&db, &["-m", &dir, "--lock-timeout", "1s"]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert!(common::stderr(&out).contains("runs outside of a transaction, ignoring its statement and lock timeouts"),
+            "{}", common::stderr(&out));
+    assert!(table_exists(&db, "posts_id_idx"));
+    assert_eq!(versions(&db)[1], (2, "success".to_owned(), Some("index-posts".to_owned())));
+
+    let out = common::migrate_run(&db, &["-t", "1", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert!(!table_exists(&db, "posts_id_idx"));
+}
@@ -0,0 +1,175 @@
+//! Integration tests for the seed sub-command, run against SQLite
+extern crate mysql;
+extern crate postgres;
+extern crate rusqlite;
+
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+const MIGRATIONS: &[common::TestMigration] = &[
+    ("add-users-table", "1", "CREATE TABLE users (name TEXT);", Some("DROP TABLE users;")),
+];
+
+/// Write a config file with a test and a protected environment for a SQLite database
+fn config(name: &str, path: &str) -> String {
+    let config = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.ini", name));
+    fs::write(&config, format!("[test]\ndriver=sqlite\ndatabase={}\n\n\
+                                [production]\ndriver=sqlite\ndatabase={}\n", path, path)).unwrap();
+    config.to_string_lossy().into_owned()
+}
+
+/// Write a seeds directory with the seeds of each environment, as (environment, file, SQL)
+fn seeds_dir(name: &str, seeds: &[(&str, &str, &str)]) -> String {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{}_seeds", name));
+    if dir.exists() {
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    for &(environment, file, sql) in seeds {
+        fs::create_dir_all(dir.join(environment)).unwrap();
+        fs::write(dir.join(environment).join(file), sql).unwrap();
+    }
+
+    dir.to_string_lossy().into_owned()
+}
+
+fn users(path: &str) -> Vec<String> {
+    let conn = rusqlite::Connection::open(path).unwrap();
+    let mut stmt = conn.prepare("SELECT name FROM users ORDER BY name").unwrap();
+    let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+    rows.map(|row| row.unwrap()).collect()
+}
+
+#[test]
+fn seeds_are_loaded_once_per_database() {
+    let path = common::fresh_sqlite_db("seed_once");
+    let dir = common::migrations_dir("seed_once", MIGRATIONS);
+    let config = config("seed_once", &path);
+    let seeds = seeds_dir("seed_once", &[
+        ("test", "01_admins.sql", "INSERT INTO users VALUES ('admin');"),
+        ("test", "02_users.sql", "INSERT INTO users VALUES ('alice'); INSERT INTO users VALUES ('bob');"),
+        ("production", "01_admins.sql", "INSERT INTO users VALUES ('root');"),
+    ]);
+    let seed = |env: &str| common::migrate(&["seed", "-c", &config, "-e", env, "-m", &dir, "--seeds-dir", &seeds]);
+
+    let out = seed("test");
+    assert!(common::stderr(&out).contains("run migrations up to version 1 before loading seeds"),
+            "{}", common::stderr(&out));
+
+    let out = common::migrate(&["run", "-c", &config, "-e", "test", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    let out = seed("test");
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert_eq!(users(&path), vec!["admin", "alice", "bob"]);
+
+    // Changed seeds are not loaded again either
+    fs::write(PathBuf::from(&seeds).join("test").join("01_admins.sql"), "INSERT INTO users VALUES ('carol');").unwrap();
+    let out = common::migrate(&["seed", "-c", &config, "-e", "test", "-m", &dir, "--seeds-dir", &seeds]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert!(common::stderr(&out).contains("Seed \"01_admins\" has changed since it was loaded"), "{}", common::stderr(&out));
+    assert_eq!(users(&path), vec!["admin", "alice", "bob"]);
+
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    let recorded: i64 = conn.query_row("SELECT count(*) FROM schema_seeds WHERE environment = 'test'", [],
+                                       |row| row.get(0)).unwrap();
+    assert_eq!(recorded, 2);
+}
+
+#[test]
+fn seeds_are_skipped_in_protected_environments_unless_included() {
+    let path = common::fresh_sqlite_db("seed_protected");
+    let dir = common::migrations_dir("seed_protected", MIGRATIONS);
+    let config = config("seed_protected", &path);
+    let seeds = seeds_dir("seed_protected", &[("production", "01_admins.sql", "INSERT INTO users VALUES ('root');")]);
+
+    let out = common::migrate(&["run", "-c", &config, "-e", "production", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    let args = ["seed", "-c", &config, "-e", "production", "-m", &dir, "--seeds-dir", &seeds];
+    let out = common::migrate(&args);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert!(common::stderr(&out).contains("Not loading seeds in the protected environment \"production\""),
+            "{}", common::stderr(&out));
+    assert!(users(&path).is_empty());
+
+    let out = common::migrate(&[&args[..], &["--include-protected"]].concat());
+    assert!(common::stderr(&out).contains("Refusing to load 1 seeds in the protected environment"),
+            "{}", common::stderr(&out));
+    assert!(users(&path).is_empty());
+
+    let out = common::migrate(&[&args[..], &["--include-protected", "--yes-i-am-sure"]].concat());
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert_eq!(users(&path), vec!["root"]);
+}
+
+#[test]
+fn environments_sharing_a_database_track_seeds_of_the_same_name_apart() {
+    let path = common::fresh_sqlite_db("seed_shared");
+    let dir = common::migrations_dir("seed_shared", MIGRATIONS);
+    let config = config("seed_shared", &path);
+    let seeds = seeds_dir("seed_shared", &[
+        ("test", "01_admins.sql", "INSERT INTO users VALUES ('admin');"),
+        ("production", "01_admins.sql", "INSERT INTO users VALUES ('root');"),
+    ]);
+    let seed = |env: &str| common::migrate(&["seed", "-c", &config, "-e", env, "-m", &dir, "--seeds-dir", &seeds,
+                                            "--include-protected", "--yes-i-am-sure"]);
+
+    let out = common::migrate(&["run", "-c", &config, "-e", "test", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    let out = seed("test");
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    // Seeds recorded before they were tracked by environment are kept by the upgrade
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    conn.execute_batch("CREATE TABLE schema_seeds_old (
+                            name TEXT PRIMARY KEY NOT NULL,
+                            environment TEXT NOT NULL,
+                            loaded TIMESTAMP NOT NULL,
+                            seed_hash TEXT NOT NULL,
+                            duration_ms INTEGER);
+                        INSERT INTO schema_seeds_old SELECT name, environment, loaded, seed_hash, duration_ms FROM schema_seeds;
+                        DROP TABLE schema_seeds;
+                        ALTER TABLE schema_seeds_old RENAME TO schema_seeds;
+                        DELETE FROM schema_versions_lib WHERE lib_ver = 9;").unwrap();
+
+    let out = common::migrate(&["status", "-c", &config, "-e", "test", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    let out = seed("production");
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert_eq!(users(&path), vec!["admin", "root"]);
+
+    let out = seed("test");
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert_eq!(users(&path), vec!["admin", "root"]);
+
+    let recorded: Vec<String> = conn.prepare("SELECT environment FROM schema_seeds WHERE name = '01_admins' ORDER BY environment")
+        .unwrap().query_map([], |row| row.get(0)).unwrap().map(|row| row.unwrap()).collect();
+    assert_eq!(recorded, vec!["production", "test"]);
+}
+
+#[test]
+fn failed_seeds_are_not_recorded() {
+    let path = common::fresh_sqlite_db("seed_failure");
+    let dir = common::migrations_dir("seed_failure", MIGRATIONS);
+    let seeds = seeds_dir("seed_failure", &[
+        ("default", "01_users.sql", "INSERT INTO users VALUES ('alice');"),
+        ("default", "02_broken.sql", "INSERT INTO users VALUES ('bob'); INSERT INTO nowhere VALUES (1);"),
+    ]);
+
+    let out = common::migrate(&["run", "--driver", "sqlite", "-d", &path, "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    let out = common::migrate(&["seed", "--driver", "sqlite", "-d", &path, "-m", &dir, "--seeds-dir", &seeds]);
+    assert!(common::stderr(&out).contains("Seed \"02_broken\" failed"), "{}", common::stderr(&out));
+    assert_eq!(users(&path), vec!["alice"]);
+
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    let recorded: Vec<String> = conn.prepare("SELECT name FROM schema_seeds").unwrap()
+        .query_map([], |row| row.get(0)).unwrap().map(|row| row.unwrap()).collect();
+    assert_eq!(recorded, vec!["01_users"]);
+}
@@ -0,0 +1,116 @@
+//! Integration tests for the SQLite driver, which need no database server
+extern crate mysql;
+extern crate postgres;
+extern crate rusqlite;
+
+mod common;
+
+const POSTS: common::TestMigration = ("add-posts-table", "1", "CREATE TABLE posts (id INT);", Some("DROP TABLE posts;"));
+const USERS: common::TestMigration = ("add-users-table", "2", "CREATE TABLE users (id INT);", Some("DROP TABLE users;"));
+
+fn versions(path: &str) -> Vec<(i64, String, Option<String>)> {
+    let conn = rusqlite::Connection::open(path).unwrap();
+    let mut stmt = conn.prepare("SELECT version, status, name FROM schema_versions ORDER BY version").unwrap();
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).unwrap();
+    rows.map(|row| row.unwrap()).collect()
+}
+
+fn table_exists(path: &str, table: &str) -> bool {
+    let conn = rusqlite::Connection::open(path).unwrap();
+    conn.query_row("SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = ?1", [table],
+                   |row| row.get::<_, i64>(0)).unwrap() == 1
+}
+
+#[test]
+fn run_applies_and_reverses_migrations() {
+    let path = common::fresh_sqlite_db("sqlite_up_down");
+    let dir = common::migrations_dir("sqlite_up_down", &[POSTS, USERS]);
+    let url = format!("sqlite://{}", path);
+
+    let out = common::migrate(&["run", "--url", &url, "-t", "2", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert!(table_exists(&path, "posts"));
+    assert!(table_exists(&path, "users"));
+    assert_eq!(versions(&path), vec![(1, "success".to_owned(), Some("add-posts-table".to_owned())),
+                                     (2, "success".to_owned(), Some("add-users-table".to_owned()))]);
+
+    // Selecting the driver explicitly works the same as a URL
+    let out = common::migrate(&["run", "--driver", "sqlite", "-d", &path, "-t", "0", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert!(!table_exists(&path, "posts"));
+    assert!(!table_exists(&path, "users"));
+    assert!(versions(&path).is_empty());
+
+    let out = common::migrate(&["history", "--url", &url]);
+    let history = common::stdout(&out);
+    assert_eq!(history.lines().filter(|line| line.contains(" apply ")).count(), 2, "{}", history);
+    assert_eq!(history.lines().filter(|line| line.contains(" rollback ")).count(), 2, "{}", history);
+}
+
+#[test]
+fn failed_migration_is_recorded_and_history_is_append_only() {
+    let path = common::fresh_sqlite_db("sqlite_fail");
+    let bad_users = ("add-users-table", "2", "CREATE TABLE users (id INT); SELECT missing_column FROM users;", None);
+    let dir = common::migrations_dir("sqlite_fail", &[POSTS, bad_users]);
+
+    let out = common::migrate(&["run", "--driver", "sqlite", "-d", &path, "-t", "2", "-m", &dir]);
+    assert!(common::stderr(&out).contains("failed"), "{}", common::stderr(&out));
+    assert!(table_exists(&path, "posts"));
+    assert!(!table_exists(&path, "users"));
+    assert_eq!(versions(&path)[1], (2, "fail".to_owned(), Some("add-users-table".to_owned())));
+
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    assert!(conn.execute("DELETE FROM schema_history", []).is_err());
+}
+
+#[test]
+fn mismatched_driver_and_url_are_refused() {
+    let out = common::migrate(&["run", "--driver", "postgres", "--url", "sqlite://db.sqlite", "-t", "0"]);
+    assert!(common::stderr(&out).contains("is for the sqlite driver"), "{}", common::stderr(&out));
+
+    let out = common::migrate(&["run", "--url", "oracle://localhost/db", "-t", "0"]);
+    assert!(common::stderr(&out).contains("not supported"), "{}", common::stderr(&out));
+
+    let out = common::migrate(&["run", "--driver", "sqlite", "-t", "0"]);
+    assert!(common::stderr(&out).contains("Missing database connection settings for the sqlite driver: database"),
+            "{}", common::stderr(&out));
+}
+
+#[test]
+fn driver_specific_sql_is_preferred() {
+    let path = common::fresh_sqlite_db("sqlite_dialect");
+    let dir = common::migrations_dir("sqlite_dialect", &[("add-posts-table", "1", "CREATE TABLE wrong (id INT);", None)]);
+    let migration_dir = std::path::Path::new(&dir).join("add-posts-table");
+    std::fs::write(migration_dir.join("up.sqlite.sql"), "CREATE TABLE posts (id INTEGER PRIMARY KEY AUTOINCREMENT);").unwrap();
+    std::fs::write(migration_dir.join("up.postgres.sql"), "CREATE TABLE posts (id SERIAL PRIMARY KEY);").unwrap();
+
+    let out = common::migrate(&["run", "--driver", "sqlite", "-d", &path, "-t", "1", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert!(table_exists(&path, "posts"));
+    assert!(!table_exists(&path, "wrong"));
+
+    // Without a portable up.sql a migration can only run on drivers it provides SQL for
+    std::fs::remove_file(migration_dir.join("up.sql")).unwrap();
+    std::fs::remove_file(migration_dir.join("up.sqlite.sql")).unwrap();
+    let out = common::migrate(&["run", "--driver", "sqlite", "-d", &path, "-t", "1", "-m", &dir]);
+    assert!(common::stderr(&out).contains("has no up.sqlite.sql or up.sql file"), "{}", common::stderr(&out));
+}
+
+#[test]
+fn destructive_operations_are_refused_before_running() {
+    let path = common::fresh_sqlite_db("sqlite_destructive");
+    let dir = common::migrations_dir("sqlite_destructive", &[
+        ("add-posts-table", "1", "CREATE TABLE posts (id INT, title TEXT);", Some("DROP TABLE posts;")),
+        ("drop-posts-table", "2", "DROP TABLE posts;", Some("CREATE TABLE posts (id INT, title TEXT);")),
+    ]);
+
+    // Nothing is performed, not even the migrations before the destructive one
+    let out = common::migrate(&["run", "--driver", "sqlite", "-d", &path, "-m", &dir]);
+    assert!(common::stderr(&out).contains("Drops table posts [drop-table]"), "{}", common::stderr(&out));
+    assert!(versions(&path).is_empty());
+
+    std::fs::write(std::path::Path::new(&dir).join("drop-posts-table").join("up.sql"), "-- migrate:allow drop-table\nDROP TABLE posts;").unwrap();
+    let out = common::migrate(&["run", "--driver", "sqlite", "-d", &path, "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert_eq!(versions(&path).len(), 2);
+}
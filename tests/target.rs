@@ -0,0 +1,75 @@
+//! Integration tests for resolving the target of the run sub-command, run against SQLite
+extern crate mysql;
+extern crate postgres;
+extern crate rusqlite;
+
+mod common;
+
+use std::process::Output;
+
+const MIGRATIONS: &[common::TestMigration] = &[
+    ("add-posts-table", "1", "CREATE TABLE posts (id INT);", Some("DROP TABLE posts;")),
+    ("add-users-table", "2", "CREATE TABLE users (id INT);", Some("DROP TABLE users;")),
+    ("add-tags-table", "3", "CREATE TABLE tags (id INT);", Some("DROP TABLE tags;")),
+];
+
+fn current_version(path: &str) -> i64 {
+    let conn = rusqlite::Connection::open(path).unwrap();
+    conn.query_row("SELECT COALESCE(MAX(version), 0) FROM schema_versions WHERE status = 'success'", [],
+                   |row| row.get(0)).unwrap()
+}
+
+fn run(path: &str, dir: &str, target: Option<&str>) -> Output {
+    let mut args = vec!["run", "--driver", "sqlite", "-d", path, "-m", dir];
+    if let Some(target) = target {
+        args.push("-t");
+        args.push(target);
+    }
+    common::migrate(&args)
+}
+
+#[test]
+fn targets_resolve_against_migrations_and_current_version() {
+    let path = common::fresh_sqlite_db("target_resolve");
+    let dir = common::migrations_dir("target_resolve", MIGRATIONS);
+
+    let out = run(&path, &dir, Some("+1"));
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert_eq!(current_version(&path), 1);
+
+    let out = run(&path, &dir, Some("add-tags-table"));
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert_eq!(current_version(&path), 3);
+
+    let out = run(&path, &dir, Some("-2"));
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert_eq!(current_version(&path), 1);
+
+    // Latest is the default
+    let out = run(&path, &dir, None);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert_eq!(current_version(&path), 3);
+
+    let out = run(&path, &dir, Some("-3"));
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert_eq!(current_version(&path), 0);
+}
+
+#[test]
+fn unresolvable_targets_are_refused() {
+    let path = common::fresh_sqlite_db("target_refused");
+    let dir = common::migrations_dir("target_refused", MIGRATIONS);
+
+    let out = run(&path, &dir, Some("+4"));
+    assert!(common::stderr(&out).contains("only 3 are pending"), "{}", common::stderr(&out));
+
+    let out = run(&path, &dir, Some("-1"));
+    assert!(common::stderr(&out).contains("only 0 are applied"), "{}", common::stderr(&out));
+
+    let out = run(&path, &dir, Some("add-comments-table"));
+    assert!(common::stderr(&out).contains("No migration is named"), "{}", common::stderr(&out));
+
+    let out = run(&path, &dir, Some("7"));
+    assert!(common::stderr(&out).contains("No migration provides version 7"), "{}", common::stderr(&out));
+    assert_eq!(current_version(&path), 0);
+}
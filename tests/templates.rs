@@ -0,0 +1,104 @@
+//! Integration tests for placeholders in migrations filled in from the config file, run against SQLite
+extern crate mysql;
+extern crate postgres;
+extern crate rusqlite;
+extern crate serde_json;
+
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use serde_json::Value;
+
+const MIGRATIONS: &[common::TestMigration] = &[
+    ("add-posts-table", "1",
+     "CREATE TABLE posts_{{ suffix }} (id INT, owner TEXT DEFAULT '{{app_role}}', tags TEXT DEFAULT '{{1,2},{3,4}}');",
+     Some("DROP TABLE posts_{{ suffix }};")),
+];
+
+/// Write a config file whose test environment sets the variables the migrations use
+fn config(name: &str, path: &str) -> String {
+    let config = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.ini", name));
+    fs::write(&config, format!("[test]\ndriver=sqlite\ndatabase={}\npassword=secret\nsuffix=v2\napp_role=reader\n\n\
+                                [bare]\ndriver=sqlite\ndatabase={}\n", path, path)).unwrap();
+    config.to_string_lossy().into_owned()
+}
+
+fn tables(path: &str) -> Vec<String> {
+    let conn = rusqlite::Connection::open(path).unwrap();
+    let mut stmt = conn.prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name LIKE 'posts%'").unwrap();
+    let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+    rows.map(|row| row.unwrap()).collect()
+}
+
+#[test]
+fn placeholders_are_filled_in_from_the_environment() {
+    let path = common::fresh_sqlite_db("templates_filled");
+    let dir = common::migrations_dir("templates_filled", MIGRATIONS);
+    let config = config("templates_filled", &path);
+
+    let out = common::migrate(&["run", "-c", &config, "-e", "test", "-m", &dir, "--dry-run"]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert!(common::stdout(&out).contains("-- Migration \"add-posts-table\" (version 1), apply\n\
+                                          CREATE TABLE posts_v2 (id INT, owner TEXT DEFAULT 'reader', \
+                                          tags TEXT DEFAULT '{{1,2},{3,4}}');"),
+            "{}", common::stdout(&out));
+
+    // Dry runs do not even bootstrap the bookkeeping tables
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    let objects: i64 = conn.query_row("SELECT count(*) FROM sqlite_master", [], |row| row.get(0)).unwrap();
+    assert_eq!(objects, 0);
+
+    let out = common::migrate(&["run", "-c", &config, "-e", "test", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert_eq!(tables(&path), vec!["posts_v2"]);
+
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    conn.execute("INSERT INTO posts_v2 (id) VALUES (1)", []).unwrap();
+    let (owner, tags): (String, String) = conn.query_row("SELECT owner, tags FROM posts_v2", [],
+                                                         |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+    assert_eq!(owner, "reader");
+    assert_eq!(tags, "{{1,2},{3,4}}");
+
+    let out = common::migrate(&["run", "-c", &config, "-e", "test", "-m", &dir, "-t", "0", "--dry-run", "--output", "json"]);
+    let report: Value = serde_json::from_str(&common::stdout(&out)).unwrap();
+    assert_eq!(report["dry_run"], true);
+    assert_eq!(report["migrations"][0]["status"], "planned");
+    assert_eq!(report["migrations"][0]["sql"], "DROP TABLE posts_v2;");
+    assert_eq!(tables(&path), vec!["posts_v2"]);
+
+    let out = common::migrate(&["lint", "--driver", "sqlite", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+}
+
+#[test]
+fn placeholders_without_a_value_stop_the_run() {
+    let path = common::fresh_sqlite_db("templates_missing");
+    let dir = common::migrations_dir("templates_missing", &[
+        ("add-users-table", "1", "CREATE TABLE users (id INT);", Some("DROP TABLE users;")),
+        ("add-posts-table", "2", MIGRATIONS[0].2, MIGRATIONS[0].3),
+    ]);
+    let config = config("templates_missing", &path);
+
+    for args in &[&[][..], &["--dry-run"][..]] {
+        let out = common::migrate(&[&["run", "-c", &config, "-e", "bare", "-m", &dir][..], args].concat());
+        assert!(common::stderr(&out).contains("Migration \"add-posts-table\" (version 2) up SQL has placeholders \
+                                              without a value in the config file's environment section: \
+                                              suffix, app_role"), "{}", common::stderr(&out));
+    }
+
+    // Connection settings are not variables, credentials never end up in rendered SQL
+    let dir = common::migrations_dir("templates_missing", &[
+        ("add-users-table", "1", "CREATE TABLE users (id INT); -- {{ password }} {{ database }}", None),
+    ]);
+    let out = common::migrate(&["run", "-c", &config, "-e", "test", "-m", &dir, "--dry-run"]);
+    let err = common::stderr(&out);
+    assert!(err.contains("placeholders without a value in the config file's environment section: password, database"),
+            "{}", err);
+    assert!(!err.contains("secret") && !common::stdout(&out).contains("secret"), "{}", err);
+
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    let users: i64 = conn.query_row("SELECT count(*) FROM sqlite_master WHERE name = 'users'", [], |row| row.get(0)).unwrap();
+    assert_eq!(users, 0);
+}
@@ -0,0 +1,100 @@
+//! Integration tests for statement and lock timeouts
+extern crate mysql;
+extern crate postgres;
+
+mod common;
+
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+const POSTS: common::TestMigration = ("add-posts-table", "1", "CREATE TABLE posts (id INT);", Some("DROP TABLE posts;"));
+
+/// Actions and errors recorded in the history for a version, oldest first
+fn history(db: &str, version: i64) -> Vec<(String, Option<String>)> {
+    let conn = common::connect(db);
+    let rows = conn.query("SELECT action, error FROM schema_history WHERE version = $1 ORDER BY id", &[&version]).unwrap();
+    rows.iter().map(|row| (row.get(0), row.get(1))).collect()
+}
+
+#[test]
+#[ignore = "requires the test database, see test/Makefile"]
+fn migration_waiting_for_a_lock_fails_fast() {
+    let db = common::fresh_db("timeouts_lock");
+    let alter = ("add-posts-title", "2", "ALTER TABLE posts ADD COLUMN title TEXT;", Some("ALTER TABLE posts DROP COLUMN title;"));
+    let dir = common::migrations_dir(&db, &[POSTS, alter]);
+
+    let out = common::migrate_run(&db, &["-t", "1", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    // Hold a lock on posts until the migration gives up
+    let conn = common::connect(&db);
+    let trans = conn.transaction().unwrap();
+    trans.execute("LOCK TABLE posts IN ACCESS SHARE MODE", &[]).unwrap();
+
+    let out = common::migrate_run(&db, &["-m", &dir, "--lock-timeout", "100ms", "--retries", "0"]);
+    trans.finish().unwrap();
+
+    assert!(common::stderr(&out).contains("lock timeout"), "{}", common::stderr(&out));
+    let recorded = history(&db, 2);
+    assert_eq!(recorded.len(), 1);
+    assert_eq!(recorded[0].0, "fail");
+    assert!(recorded[0].1.as_ref().unwrap().contains("lock timeout"), "{:?}", recorded);
+}
+
+#[test]
+#[ignore = "requires the test database, see test/Makefile"]
+fn migration_is_retried_until_the_lock_is_free() {
+    let db = common::fresh_db("timeouts_retry");
+    let alter = ("add-posts-title", "2", "ALTER TABLE posts ADD COLUMN title TEXT;", Some("ALTER TABLE posts DROP COLUMN title;"));
+    let dir = common::migrations_dir(&db, &[POSTS, alter]);
+
+    let out = common::migrate_run(&db, &["-t", "1", "-m", &dir]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    // Hold a lock on posts for longer than the first attempt waits
+    let (locked_tx, locked_rx) = mpsc::channel();
+    let holder_db = db.clone();
+    let holder = thread::spawn(move || {
+        let conn = common::connect(&holder_db);
+        let trans = conn.transaction().unwrap();
+        trans.execute("LOCK TABLE posts IN ACCESS SHARE MODE", &[]).unwrap();
+        locked_tx.send(()).unwrap();
+        thread::sleep(Duration::from_millis(400));
+        trans.finish().unwrap();
+    });
+    locked_rx.recv().unwrap();
+
+    let out = common::migrate_run(&db, &["-m", &dir, "--lock-timeout", "100ms", "--retry-delay", "500ms"]);
+    holder.join().unwrap();
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert!(common::stderr(&out).contains("Attempt 1 at migration \"add-posts-title\" (version 2) failed, retrying"),
+            "{}", common::stderr(&out));
+
+    let recorded = history(&db, 2);
+    assert_eq!(recorded.len(), 2, "{:?}", recorded);
+    assert_eq!(recorded[0].0, "fail");
+    assert!(recorded[0].1.as_ref().unwrap().contains("(attempt 1, retried)"), "{:?}", recorded);
+    assert_eq!(recorded[1].0, "apply");
+}
+
+#[test]
+#[ignore = "requires the test database, see test/Makefile"]
+fn header_overrides_statement_timeout() {
+    let db = common::fresh_db("timeouts_header");
+    let slow = ("slow-migration", "2", "-- migrate:statement-timeout 50ms\nSELECT pg_sleep(1);", None);
+    let dir = common::migrations_dir(&db, &[POSTS, slow]);
+
+    let out = common::migrate_run(&db, &["-m", &dir, "--statement-timeout", "10s"]);
+    assert!(common::stderr(&out).contains("statement timeout"), "{}", common::stderr(&out));
+    assert_eq!(history(&db, 2)[0].0, "fail");
+
+    let slow = ("slow-migration", "2", "-- migrate:statement-timeout soon\nSELECT 1;", None);
+    let dir = common::migrations_dir(&db, &[POSTS, slow]);
+
+    let out = common::migrate_run(&db, &["-m", &dir]);
+    assert!(common::stderr(&out).contains("header must be a duration"), "{}", common::stderr(&out));
+
+    let out = common::migrate(&["lint", "-m", &dir]);
+    assert!(common::stdout(&out).contains("header must be a duration"), "{}", common::stdout(&out));
+}
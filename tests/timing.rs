@@ -0,0 +1,64 @@
+//! Integration tests for timing migrations and their statements, run against SQLite
+extern crate mysql;
+extern crate postgres;
+extern crate rusqlite;
+extern crate serde_json;
+
+mod common;
+
+use serde_json::Value;
+
+const MIGRATIONS: &[common::TestMigration] = &[
+    ("add-posts-table", "1",
+     "-- Posts; one per article\nCREATE TABLE posts (id INT, title TEXT DEFAULT 'a;b');\n/* ; */\nINSERT INTO posts (id) VALUES (1);",
+     Some("DROP TABLE posts;")),
+    ("add-posts-trigger", "2",
+     "CREATE TRIGGER posts_title AFTER INSERT ON posts BEGIN UPDATE posts SET title = 'x'; END;",
+     Some("DROP TRIGGER posts_title;")),
+];
+
+#[test]
+fn statements_are_timed_and_durations_recorded() {
+    let path = common::fresh_sqlite_db("timing_statements");
+    let dir = common::migrations_dir("timing_statements", MIGRATIONS);
+
+    let out = common::migrate(&["run", "--driver", "sqlite", "-d", &path, "-m", &dir, "--output", "json"]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    let report: Value = serde_json::from_str(&common::stdout(&out)).unwrap();
+    let statements = report["migrations"][0]["statements"].as_array().unwrap();
+    assert_eq!(statements.len(), 2, "{}", report);
+    assert!(statements[0]["sql"].as_str().unwrap().contains("'a;b'"));
+    assert!(statements[1]["sql"].as_str().unwrap().starts_with("INSERT"));
+    assert!(statements[1]["duration_ms"].is_u64());
+
+    // Semicolons inside the trigger can not be told apart, it runs as one batch
+    assert_eq!(report["migrations"][1]["status"], "applied");
+    assert_eq!(report["migrations"][1]["statements"].as_array().unwrap().len(), 0);
+
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    let unrecorded: i64 = conn.query_row("SELECT count(*) FROM schema_versions WHERE duration_ms IS NULL", [],
+                                         |row| row.get(0)).unwrap();
+    assert_eq!(unrecorded, 0);
+}
+
+#[test]
+fn slow_migrations_are_warned_about_and_summarized() {
+    let path = common::fresh_sqlite_db("timing_slow");
+    let dir = common::migrations_dir("timing_slow", &MIGRATIONS[..1]);
+
+    let out = common::migrate(&["run", "--driver", "sqlite", "-d", &path, "-m", &dir, "--slow-threshold", "0ms"]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    let stderr = common::stderr(&out);
+    assert!(stderr.contains("longer than the slow threshold of 0ms"), "{}", stderr);
+    assert!(stderr.contains("Summary of 1 migrations"), "{}", stderr);
+    assert!(stderr.contains("SLOWEST STATEMENT"), "{}", stderr);
+
+    let out = common::migrate(&["run", "--driver", "sqlite", "-d", &path, "-m", &dir, "--slow-threshold", "soon"]);
+    assert!(common::stderr(&out).contains("--slow-threshold must be a duration"), "{}", common::stderr(&out));
+
+    let out = common::migrate(&["run", "--driver", "sqlite", "-d", &path, "-m", &dir,
+                                "--slow-threshold", "999999999999999999m"]);
+    assert!(common::stderr(&out).contains("--slow-threshold must be a duration"), "{}", common::stderr(&out));
+}
@@ -0,0 +1,105 @@
+//! Integration tests for timestamp versions, out of order migrations and the create sub-command,
+//! run against SQLite
+extern crate mysql;
+extern crate postgres;
+extern crate rusqlite;
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::process::Output;
+
+const POSTS: common::TestMigration = ("add-posts-table", "20261018120000", "CREATE TABLE posts (id INT);", Some("DROP TABLE posts;"));
+const USERS: common::TestMigration = ("add-users-table", "20261018130000", "CREATE TABLE users (id INT);", Some("DROP TABLE users;"));
+const TAGS: common::TestMigration = ("add-tags-table", "20261018140000", "CREATE TABLE tags (id INT);", Some("DROP TABLE tags;"));
+
+fn applied(path: &str) -> Vec<i64> {
+    let conn = rusqlite::Connection::open(path).unwrap();
+    let mut stmt = conn.prepare("SELECT version FROM schema_versions WHERE status = 'success' ORDER BY version").unwrap();
+    let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+    rows.map(|row| row.unwrap()).collect()
+}
+
+fn run(path: &str, dir: &str, args: &[&str]) -> Output {
+    let mut full_args = vec!["run", "--driver", "sqlite", "-d", path, "-m", dir];
+    full_args.extend_from_slice(args);
+    common::migrate(&full_args)
+}
+
+#[test]
+fn out_of_order_migrations_are_refused_unless_allowed() {
+    let path = common::fresh_sqlite_db("out_of_order");
+
+    // The users migration is merged after the tags migration was applied
+    let dir = common::migrations_dir("out_of_order", &[POSTS, TAGS]);
+    let out = run(&path, &dir, &[]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert_eq!(applied(&path), vec![20261018120000, 20261018140000]);
+
+    let dir = common::migrations_dir("out_of_order", &[POSTS, USERS, TAGS]);
+    let out = run(&path, &dir, &[]);
+    let err = common::stderr(&out);
+    assert!(err.contains("add-users-table") && err.contains("(version 20261018130000)"), "{}", err);
+    assert!(err.contains("--allow-out-of-order"), "{}", err);
+    assert_eq!(applied(&path), vec![20261018120000, 20261018140000]);
+
+    let out = run(&path, &dir, &["--allow-out-of-order"]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert_eq!(applied(&path), vec![20261018120000, 20261018130000, 20261018140000]);
+
+    // Reversing past an out of order migration reverses it as well
+    let out = run(&path, &dir, &["-t", "0"]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert!(applied(&path).is_empty());
+}
+
+#[test]
+fn relative_targets_only_count_applied_migrations() {
+    let path = common::fresh_sqlite_db("out_of_order_relative");
+
+    let dir = common::migrations_dir("out_of_order_relative", &[POSTS, TAGS]);
+    let out = run(&path, &dir, &[]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+
+    // The users migration was never applied, -2 reverses the tags and posts migrations
+    let dir = common::migrations_dir("out_of_order_relative", &[POSTS, USERS, TAGS]);
+    let out = run(&path, &dir, &["-t", "-3"]);
+    assert!(common::stderr(&out).contains("only 2 are applied"), "{}", common::stderr(&out));
+
+    let out = run(&path, &dir, &["-t", "-2"]);
+    assert!(out.status.success(), "{}", common::stderr(&out));
+    assert!(applied(&path).is_empty());
+}
+
+#[test]
+fn create_scaffolds_sequential_and_timestamp_versions() {
+    let dir = common::migrations_dir("create_versions", &[("add-posts-table", "7", "CREATE TABLE posts (id INT);", None)]);
+
+    let out = common::stdout(&common::migrate(&["create", "add-users-table", "-m", &dir]));
+    assert!(out.contains("(version 8)"), "{}", out);
+
+    let migration = Path::new(&dir).join("add-users-table");
+    assert_eq!(fs::read_to_string(migration.join("version")).unwrap().trim(), "8");
+    assert!(migration.join("up.sql").exists());
+    assert!(migration.join("down.sql").exists());
+
+    let out = common::stdout(&common::migrate(&["create", "add-tags-table", "-m", &dir, "--timestamp"]));
+    let version = fs::read_to_string(Path::new(&dir).join("add-tags-table").join("version")).unwrap();
+    assert_eq!(version.trim().len(), 14, "{}", out);
+    assert!(version.trim().starts_with("20"), "{}", out);
+
+    // Created in the same second, or after a migration versioned ahead of the clock
+    let out = common::stdout(&common::migrate(&["create", "add-likes-table", "-m", &dir, "--timestamp"]));
+    let next = fs::read_to_string(Path::new(&dir).join("add-likes-table").join("version")).unwrap();
+    assert!(next.trim().parse::<i64>().unwrap() > version.trim().parse::<i64>().unwrap(), "{}", out);
+
+    fs::create_dir_all(Path::new(&dir).join("add-future-table")).unwrap();
+    fs::write(Path::new(&dir).join("add-future-table").join("version"), "29991231235959").unwrap();
+    fs::write(Path::new(&dir).join("add-future-table").join("up.sql"), "").unwrap();
+    let out = common::stdout(&common::migrate(&["create", "add-comments-table", "-m", &dir, "--timestamp"]));
+    assert!(out.contains("(version 30000101000000)"), "{}", out);
+
+    let out = common::migrate(&["create", "add-users-table", "-m", &dir]);
+    assert!(common::stderr(&out).contains("already exists"), "{}", common::stderr(&out));
+}